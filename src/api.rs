@@ -0,0 +1,335 @@
+use std::{fmt, time::Duration};
+
+use axum::{extract::rejection::JsonRejection, http::{header, StatusCode}, response::{IntoResponse, Response}, Json};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::{auth::AuthError, board::Board, engine::Engine, evaluation::{evaluate, evaluate_breakdown}, moves::Move, pgn::{game_result, san}, piece::PieceColor, r#const::{API_DEFAULT_DEPTH, API_MAX_MOVE_TIME, API_MAX_MULTIPV, MAX_PLIES}, search::{to_centipawns, SearchInfo}, time_manager::{TimeControl, TimeManager}};
+
+// Typed JSON endpoints next to the UCI ones. Positions are a FEN, the start position
+// when it is missing, followed by moves in UCI notation. Scores are centipawns from
+// the point of view of the side to move, as in UCI.
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PositionRequest {
+    pub fen: Option<String>,
+    #[serde(default)]
+    pub moves: Vec<String>
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AnalyseRequest {
+    #[serde(flatten)]
+    pub position: PositionRequest,
+    pub depth: Option<u8>,
+    // milliseconds
+    pub movetime: Option<u64>,
+    pub multipv: Option<usize>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisLine {
+    #[serde(rename = "move")]
+    pub m: String,
+    pub san: String,
+    pub score: i32,
+    pub pv: Vec<String>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalyseResponse {
+    // none when the game is over
+    pub best_move: Option<String>,
+    pub score: Option<i32>,
+    pub depth: u32,
+    pub nodes: u64,
+    // milliseconds
+    pub time: u64,
+    pub pv: Vec<String>,
+    // best first, fewer than multipv when there are fewer legal moves or the time ran out before all were searched
+    pub lines: Vec<AnalysisLine>,
+    pub result: &'static str
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LegalMove {
+    pub uci: String,
+    pub san: String
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LegalMovesResponse {
+    pub turn: &'static str,
+    pub in_check: bool,
+    pub result: &'static str,
+    pub moves: Vec<LegalMove>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvaluationTerm {
+    pub name: &'static str,
+    pub white: f64,
+    pub black: f64
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvaluateResponse {
+    pub turn: &'static str,
+    // the static evaluation from the point of view of the side to move
+    pub score: i32,
+    // in pawns, as the evaluation adds them up
+    pub terms: Vec<EvaluationTerm>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub clients: usize,
+    // positions in the opening book
+    pub book: Option<usize>,
+    pub tablebases: Option<usize>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+    // sent with 429 so the client knows when to come back
+    pub retry_after: Option<Duration>
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        ApiError { status, message: message.into(), retry_after: None }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::new(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(error: AuthError) -> Self {
+        let status = match error {
+            AuthError::MissingKey | AuthError::InvalidKey => StatusCode::UNAUTHORIZED,
+            AuthError::TooManySearches(_) | AuthError::CpuTimeExceeded(_) => StatusCode::TOO_MANY_REQUESTS
+        };
+
+        ApiError { status, message: error.to_string(), retry_after: error.retry_after() }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, Json(ErrorBody { error: self.message })).into_response();
+
+        if self.status == StatusCode::UNAUTHORIZED {
+            response.headers_mut().insert(header::WWW_AUTHENTICATE, header::HeaderValue::from_static("Bearer"));
+        }
+
+        if let Some(retry_after) = self.retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, retry_after.as_secs().max(1).into());
+        }
+
+        response
+    }
+}
+
+fn color_name(color: PieceColor) -> &'static str {
+    match color {
+        PieceColor::White => "white",
+        PieceColor::Black => "black"
+    }
+}
+
+fn uci(m: &Move) -> String {
+    format!("{:?}", m)
+}
+
+// the board after the moves, a bad FEN is a syntax error and a bad move an illegal one
+pub fn position(request: &PositionRequest) -> Result<Board, ApiError> {
+    let mut board = match &request.fen {
+        Some(fen) => Board::try_from_fen(fen).map_err(|e| ApiError::bad_request(e.to_string()))?,
+        None => Board::startpos()
+    };
+
+    for (index, uci_move) in request.moves.iter().enumerate() {
+        let uci_move = uci_move.trim().to_lowercase();
+
+        let Some(m) = board.get_total_legal_moves(None).into_iter().find(|m| uci(m) == uci_move) else {
+            return Err(ApiError::unprocessable(format!("illegal move {} at index {}", uci_move, index)));
+        };
+
+        board.make_move(&m);
+    }
+
+    Ok(board)
+}
+
+pub fn legal_moves(request: &PositionRequest) -> Result<LegalMovesResponse, ApiError> {
+    let mut board = position(request)?;
+
+    let moves = board.get_total_legal_moves(None).iter()
+        .map(|m| LegalMove { uci: uci(m), san: san(&mut board, m) })
+        .collect();
+
+    Ok(LegalMovesResponse {
+        turn: color_name(board.turn),
+        in_check: board.get_check(board.turn).checked != 0,
+        result: game_result(&mut board),
+        moves
+    })
+}
+
+pub fn evaluation(request: &PositionRequest) -> Result<EvaluateResponse, ApiError> {
+    let mut board = position(request)?;
+    let value = evaluate(&mut board).to_value();
+
+    let terms = evaluate_breakdown(&mut board).terms.into_iter()
+        .map(|(name, term)| EvaluationTerm { name, white: term.white, black: term.black })
+        .collect();
+
+    Ok(EvaluateResponse {
+        turn: color_name(board.turn),
+        score: to_centipawns(if board.turn == PieceColor::White { value } else { -value }),
+        terms
+    })
+}
+
+// searches the position with the engine, which should have no book so every answer is searched
+pub fn analyse(engine: &mut Engine, request: &AnalyseRequest) -> Result<AnalyseResponse, ApiError> {
+    let mut board = position(&request.position)?;
+
+    if request.depth == Some(0) {
+        return Err(ApiError::bad_request("depth must be at least 1"));
+    }
+
+    let multipv = request.multipv.unwrap_or(1);
+
+    if multipv == 0 || multipv > API_MAX_MULTIPV {
+        return Err(ApiError::bad_request(format!("multipv must be between 1 and {}", API_MAX_MULTIPV)));
+    }
+
+    // a depth alone is still bounded in time, a move time alone searches as deep as it gets
+    let depth = match (request.depth, request.movetime) {
+        (Some(depth), _) => depth.min(MAX_PLIES),
+        (None, Some(_)) => MAX_PLIES,
+        (None, None) => API_DEFAULT_DEPTH
+    };
+
+    let move_time = request.movetime.unwrap_or(API_MAX_MOVE_TIME).clamp(1, API_MAX_MOVE_TIME);
+    let control = TimeControl { move_time: Some(move_time), ..TimeControl::default() };
+
+    let result = game_result(&mut board);
+    let root_moves = board.get_total_legal_moves(None);
+
+    if root_moves.is_empty() {
+        return Ok(AnalyseResponse { best_move: None, score: None, depth: 0, nodes: 0, time: 0, pv: Vec::new(), lines: Vec::new(), result });
+    }
+
+    let time_manager = TimeManager::new(&control, 0);
+
+    // every line shares the time, they are searched together so their scores compare
+    if multipv > 1 {
+        let (mut lines, reached, nodes) = engine.lines(&mut board, depth.saturating_sub(1).max(1), &time_manager, u64::MAX);
+
+        if lines.is_empty() {
+            return Err(ApiError::internal("the search returned no move"));
+        }
+
+        lines.truncate(multipv);
+
+        let lines: Vec<AnalysisLine> = lines.iter()
+            .map(|line| AnalysisLine { m: uci(&line.m), san: san(&mut board, &line.m), score: line.score, pv: line.pv.iter().map(uci).collect() })
+            .collect();
+
+        return Ok(AnalyseResponse {
+            best_move: Some(lines[0].m.clone()),
+            score: Some(lines[0].score),
+            depth: reached as u32,
+            nodes,
+            time: time_manager.elapsed(),
+            pv: lines[0].pv.clone(),
+            lines,
+            result
+        });
+    }
+
+    let (best, info) = search(engine, &mut board, depth, time_manager.clone());
+
+    let Some(best) = best else {
+        return Err(ApiError::internal("the search returned no move"));
+    };
+
+    // a tablebase move comes without progress
+    let info = info.unwrap_or(SearchInfo {
+        depth: 0,
+        score: engine.last_score.map_or(0, |score| if board.turn == PieceColor::White { score } else { -score }),
+        nodes: 0,
+        time: std::time::Duration::from_millis(time_manager.elapsed()),
+        pv: vec![best.clone()]
+    });
+
+    let lines = vec![AnalysisLine {
+        m: uci(&best),
+        san: san(&mut board, &best),
+        score: info.score,
+        pv: info.pv.iter().map(uci).collect()
+    }];
+
+    Ok(AnalyseResponse {
+        best_move: Some(uci(&best)),
+        score: Some(info.score),
+        depth: info.depth,
+        nodes: info.nodes,
+        time: info.time.as_millis() as u64,
+        pv: lines[0].pv.clone(),
+        lines,
+        result
+    })
+}
+
+// the move and the last completed iteration
+fn search(engine: &mut Engine, board: &mut Board, depth: u8, time_manager: TimeManager) -> (Option<Move>, Option<SearchInfo>) {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    engine.set_info_sender(Some(sender));
+    let m = engine.iterative_deepening(board, depth, time_manager);
+    engine.set_info_sender(None);
+
+    let mut last = None;
+
+    while let Ok(info) = receiver.try_recv() {
+        last = Some(info);
+    }
+
+    (m, last)
+}
\ No newline at end of file
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, env, fmt, sync::Mutex, time::{Duration, Instant}};
+
+use crate::r#const::{CPU_LIMIT_WINDOW, SEARCH_LIMIT_WINDOW};
+
+// API keys and the limits on what a key may search. Without keys the server is open
+// and the limits apply to the address a request comes from instead.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RateLimits {
+    // searches per minute, none without a limit
+    pub searches: Option<u32>,
+    // search time per hour
+    pub cpu_time: Option<Duration>
+}
+
+impl RateLimits {
+    // RATE_LIMIT_SEARCHES per minute and RATE_LIMIT_CPU in seconds per hour, 0 turns a limit off
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<u64> {
+            env::var(name).ok()?.trim().parse().ok().filter(|value| *value > 0)
+        }
+
+        RateLimits {
+            searches: var("RATE_LIMIT_SEARCHES").map(|searches| searches.min(u32::MAX as u64) as u32),
+            cpu_time: var("RATE_LIMIT_CPU").map(Duration::from_secs)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    MissingKey,
+    InvalidKey,
+    // with the time until the limit resets
+    TooManySearches(Duration),
+    CpuTimeExceeded(Duration)
+}
+
+impl AuthError {
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AuthError::TooManySearches(retry_after) | AuthError::CpuTimeExceeded(retry_after) => Some(*retry_after),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingKey => write!(f, "missing API key"),
+            AuthError::InvalidKey => write!(f, "invalid API key"),
+            AuthError::TooManySearches(retry_after) => write!(f, "search limit reached, retry in {} s", retry_after.as_secs().max(1)),
+            AuthError::CpuTimeExceeded(retry_after) => write!(f, "search time limit reached, retry in {} s", retry_after.as_secs().max(1))
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+struct Usage {
+    searches_since: Instant,
+    searches: u32,
+    cpu_since: Instant,
+    cpu_time: Duration
+}
+
+impl Usage {
+    fn new(now: Instant) -> Self {
+        Usage { searches_since: now, searches: 0, cpu_since: now, cpu_time: Duration::ZERO }
+    }
+
+    // starts new windows for the limits whose window has passed
+    fn refresh(&mut self, now: Instant) {
+        if now.saturating_duration_since(self.searches_since) >= Duration::from_secs(SEARCH_LIMIT_WINDOW) {
+            self.searches_since = now;
+            self.searches = 0;
+        }
+
+        if now.saturating_duration_since(self.cpu_since) >= Duration::from_secs(CPU_LIMIT_WINDOW) {
+            self.cpu_since = now;
+            self.cpu_time = Duration::ZERO;
+        }
+    }
+}
+
+pub struct Auth {
+    keys: Vec<String>,
+    limits: RateLimits,
+    usage: Mutex<HashMap<String, Usage>>
+}
+
+impl Auth {
+    pub fn new(keys: Vec<String>, limits: RateLimits) -> Self {
+        Auth {
+            keys: keys.into_iter().map(|key| key.trim().to_string()).filter(|key| !key.is_empty()).collect(),
+            limits,
+            usage: Mutex::new(HashMap::new())
+        }
+    }
+
+    // API_KEYS separated by commas
+    pub fn from_env() -> Self {
+        let keys = env::var("API_KEYS").unwrap_or_default();
+        Auth::new(keys.split(',').map(|key| key.to_string()).collect(), RateLimits::from_env())
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    pub fn limits(&self) -> RateLimits {
+        self.limits
+    }
+
+    pub fn authenticate(&self, key: Option<&str>) -> Result<(), AuthError> {
+        if !self.enabled() {
+            return Ok(());
+        }
+
+        let key = key.map(str::trim).filter(|key| !key.is_empty()).ok_or(AuthError::MissingKey)?;
+
+        // every key is compared in full so the time taken does not give a key away
+        let found = self.keys.iter().fold(false, |found, candidate| constant_time_eq(candidate.as_bytes(), key.as_bytes()) | found);
+
+        if found { Ok(()) } else { Err(AuthError::InvalidKey) }
+    }
+
+    // counts a search for the identity if its limits allow one more
+    pub fn start_search(&self, identity: &str, now: Instant) -> Result<(), AuthError> {
+        if self.limits == RateLimits::default() {
+            return Ok(());
+        }
+
+        let mut usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(e) => e.into_inner()
+        };
+
+        let usage = usage.entry(identity.to_string()).or_insert_with(|| Usage::new(now));
+        usage.refresh(now);
+
+        if self.limits.cpu_time.is_some_and(|limit| usage.cpu_time >= limit) {
+            return Err(AuthError::CpuTimeExceeded((usage.cpu_since + Duration::from_secs(CPU_LIMIT_WINDOW)).saturating_duration_since(now)));
+        }
+
+        if self.limits.searches.is_some_and(|limit| usage.searches >= limit) {
+            return Err(AuthError::TooManySearches((usage.searches_since + Duration::from_secs(SEARCH_LIMIT_WINDOW)).saturating_duration_since(now)));
+        }
+
+        usage.searches += 1;
+        Ok(())
+    }
+
+    // the time a search took, checked against the limit before the next one
+    pub fn record_search(&self, identity: &str, time: Duration, now: Instant) {
+        if self.limits.cpu_time.is_none() {
+            return;
+        }
+
+        let mut usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(e) => e.into_inner()
+        };
+
+        let usage = usage.entry(identity.to_string()).or_insert_with(|| Usage::new(now));
+        usage.refresh(now);
+        usage.cpu_time += time;
+    }
+
+    // forgets identities whose windows have all passed
+    pub fn evict(&self, now: Instant) {
+        let mut usage = match self.usage.lock() {
+            Ok(usage) => usage,
+            Err(e) => e.into_inner()
+        };
+
+        usage.retain(|_, usage| {
+            now.saturating_duration_since(usage.searches_since) < Duration::from_secs(SEARCH_LIMIT_WINDOW)
+                || now.saturating_duration_since(usage.cpu_since) < Duration::from_secs(CPU_LIMIT_WINDOW)
+        });
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
\ No newline at end of file
@@ -5,6 +5,7 @@ use std::{collections::HashMap, i64};
 use crate::r#const::{MAX_PHASE, MOBILITY_VALUE, MOVE_PREALLOC};
 use crate::piece::{BasePiece, PartialPiece, Piece, PieceColor, PieceType};
 use crate::moves::{Move, MoveType, Pin, Position, Vector};
+use crate::nnue::{Feature, NnueState};
 use crate::pieces::bishop::{get_controlled_squares_bishop, get_legal_moves_bishop, get_pins_bishop};
 use crate::pieces::bitboard::COLOR_MASK;
 use crate::pieces::king::{get_controlled_squares_king, get_legal_moves_king};
@@ -36,6 +37,43 @@ impl ResultType {
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum SanError {
+    Syntax(String),
+    Illegal(String),
+    Ambiguous(String)
+}
+
+impl fmt::Display for SanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanError::Syntax(san) => write!(f, "invalid move {}", san),
+            SanError::Illegal(san) => write!(f, "illegal move {}", san),
+            SanError::Ambiguous(san) => write!(f, "ambiguous move {}", san)
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FenError {
+    Syntax(String),
+    // well formed, but not a position that can be played from
+    Illegal(String)
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::Syntax(message) => write!(f, "invalid FEN: {}", message),
+            FenError::Illegal(message) => write!(f, "illegal position: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
 #[derive(Debug, Clone)]
 pub struct Castling {
     pub white: (bool, bool),
@@ -169,6 +207,7 @@ impl ControlBitboards {
 #[derive(Clone)]
 pub struct MoveInfo {
     pub hash: i64,
+    pub pawn_hash: i64,
     pub captured_piece: Option<Piece>,
     pub halfmove_clock: i32,
     pub white_check: CheckInfo,
@@ -223,7 +262,9 @@ pub struct Board {
     pub black_check: CheckInfo,
     pub hash_table: Vec<i64>,
     pub hash: i64,
+    pub pawn_hash: i64,
     pub mobility_cache: HashMap<usize, f64>,
+    pub nnue: Option<NnueState>,
 
     pub control_bitboards: ControlBitboards
 }
@@ -283,7 +324,9 @@ impl Board {
             black_check: CheckInfo::default(),
             hash_table: Vec::with_capacity(782),
             hash: i64::MAX,
+            pawn_hash: i64::MAX,
             mobility_cache: HashMap::new(),
+            nnue: None,
 
             control_bitboards: ControlBitboards { 
                 piece_control: HashMap::new(),
@@ -298,6 +341,95 @@ impl Board {
         }
     }
 
+    // from_fen for untrusted input, which panics on anything it cannot set up
+    pub fn try_from_fen(fen: &str) -> Result<Board, FenError> {
+        let syntax = |message: String| Err(FenError::Syntax(message));
+        let illegal = |message: &str| Err(FenError::Illegal(message.to_string()));
+
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+
+        if parts.len() != 6 {
+            return syntax(format!("expected 6 fields, found {}", parts.len()));
+        }
+
+        let ranks: Vec<&str> = parts[0].split('/').collect();
+
+        if ranks.len() != 8 {
+            return syntax(format!("expected 8 ranks, found {}", ranks.len()));
+        }
+
+        let mut kings = (0, 0);
+
+        for (y, rank) in ranks.iter().enumerate() {
+            let mut squares = 0;
+
+            for c in rank.chars() {
+                match c {
+                    '1'..='8' => squares += c.to_digit(10).unwrap() as usize,
+                    'P' | 'N' | 'B' | 'R' | 'Q' | 'K' | 'p' | 'n' | 'b' | 'r' | 'q' | 'k' => {
+                        if c.eq_ignore_ascii_case(&'p') && (y == 0 || y == 7) {
+                            return illegal("pawn on the first or last rank");
+                        }
+
+                        match c {
+                            'K' => kings.0 += 1,
+                            'k' => kings.1 += 1,
+                            _ => {}
+                        }
+
+                        squares += 1;
+                    },
+                    _ => return syntax(format!("invalid piece {}", c))
+                }
+            }
+
+            if squares != 8 {
+                return syntax(format!("rank {} has {} squares", 8 - y, squares));
+            }
+        }
+
+        if kings != (1, 1) {
+            return illegal("each side needs exactly one king");
+        }
+
+        if parts[1] != "w" && parts[1] != "b" {
+            return syntax(format!("invalid side to move {}", parts[1]));
+        }
+
+        if parts[2] != "-" && !parts[2].chars().all(|c| "KQkq".contains(c)) {
+            return syntax(format!("invalid castling rights {}", parts[2]));
+        }
+
+        let target = parts[3].as_bytes();
+
+        if parts[3] != "-" && (target.len() != 2 || !(b'a'..=b'h').contains(&target[0]) || (target[1] != b'3' && target[1] != b'6')) {
+            return syntax(format!("invalid en passant square {}", parts[3]));
+        }
+
+        if parts[4].parse::<u32>().is_err() || parts[5].parse::<u32>().is_err() {
+            return syntax(format!("invalid move counters {} {}", parts[4], parts[5]));
+        }
+
+        let board = Board::from_fen(fen);
+
+        // castling needs the king and the rook on their squares
+        let on_square = |piece_type: PieceType, color: PieceColor, x: usize, y: usize| {
+            board.get_piece_at(y, x).is_some_and(|piece| piece.piece_type == piece_type && piece.color == color)
+        };
+
+        for (right, color, rook_x, y) in [('K', PieceColor::White, 7, 7), ('Q', PieceColor::White, 0, 7), ('k', PieceColor::Black, 7, 0), ('q', PieceColor::Black, 0, 0)] {
+            if parts[2].contains(right) && !(on_square(PieceType::King, color, 4, y) && on_square(PieceType::Rook, color, rook_x, y)) {
+                return illegal("castling rights without the king and rook in place");
+            }
+        }
+
+        if board.get_check(board.turn.opposite()).checked != 0 {
+            return illegal("the side not to move is in check");
+        }
+
+        Ok(board)
+    }
+
     pub fn from_fen(fen: &str) -> Board {
         let mut board = Board::new(None, None, None, None, None);
         let parts: Vec<&str> = fen.split_whitespace().collect();
@@ -576,6 +708,7 @@ impl Board {
     pub fn make_move(&mut self, m: &Move) -> MoveInfo {
         let history = MoveInfo {
             hash: self.hash,
+            pawn_hash: self.pawn_hash,
             captured_piece: m.captured.clone(),
             halfmove_clock: self.halfmove_clock,
             white_check: self.white_check.clone(),
@@ -595,6 +728,10 @@ impl Board {
 
         let piece_index = m.piece_index;
 
+        if self.nnue.is_some() {
+            self.update_accumulator(m);
+        }
+
         self.update_bitboard_pos((m.piece_type, m.piece_color), m.from, m.to);
 
         self.white_check = CheckInfo::default();
@@ -621,6 +758,10 @@ impl Board {
             let captured_piece_index = captured.to_piece_index();
             self.hash ^= self.hash_table[captured_piece_index * 64 + captured.pos.y * 8 + captured.pos.x];
 
+            if captured.piece_type == PieceType::Pawn {
+                self.pawn_hash ^= self.hash_table[captured_piece_index * 64 + captured.pos.y * 8 + captured.pos.x];
+            }
+
             self.board[captured.pos.x][captured.pos.y] = -1;
 
             if captured.piece_type == PieceType::Rook &&
@@ -649,6 +790,11 @@ impl Board {
         self.hash ^= self.hash_table[hash_index * 64 + pos.y * 8 + pos.x];
         self.hash ^= self.hash_table[hash_index * 64 + m.to.y * 8 + m.to.x];
 
+        if m.piece_type == PieceType::Pawn {
+            self.pawn_hash ^= self.hash_table[hash_index * 64 + pos.y * 8 + pos.x];
+            self.pawn_hash ^= self.hash_table[hash_index * 64 + m.to.y * 8 + m.to.x];
+        }
+
         self.board[pos.x][pos.y] = -1;
         self.board[m.to.x][m.to.y] = piece_index as isize;
 
@@ -699,7 +845,34 @@ impl Board {
         history
     }
 
+    fn update_accumulator(&mut self, m: &Move) {
+        let mut removed: Vec<Feature> = vec![(m.piece_type, m.piece_color, m.from)];
+        let mut added: Vec<Feature> = vec![];
+
+        match m.promote_to {
+            Some(promote_to) if m.move_type.contains(&MoveType::Promotion) => added.push((promote_to, m.piece_color, m.to)),
+            _ => added.push((m.piece_type, m.piece_color, m.to))
+        }
+
+        if let Some(captured) = m.captured.as_ref().filter(|_| m.move_type.contains(&MoveType::Capture)) {
+            removed.push((captured.piece_type, captured.color, captured.pos));
+        }
+
+        if let Some(rook) = m.with.as_ref().filter(|_| m.piece_type == PieceType::King && m.move_type.contains(&MoveType::Castling)) {
+            removed.push((PieceType::Rook, rook.color, rook.pos));
+            added.push((PieceType::Rook, rook.color, Position { x: if m.to.x == 2 { 3 } else { 5 }, y: m.from.y }));
+        }
+
+        if let Some(nnue) = self.nnue.as_mut() {
+            nnue.push(&removed, &added);
+        }
+    }
+
     pub fn unmake_move(&mut self, m: &Move, history: &MoveInfo) {
+        if self.nnue.as_mut().is_some_and(|nnue| !nnue.pop()) {
+            self.nnue = None;
+        }
+
         let current_position = {
             let piece = self.pieces.get(&m.piece_index).unwrap();
             piece.pos.clone()
@@ -743,6 +916,7 @@ impl Board {
         }
 
         self.hash = history.hash;
+        self.pawn_hash = history.pawn_hash;
         self.halfmove_clock = history.halfmove_clock;
         self.turn = history.turn;
         self.castling = history.castling.clone();
@@ -946,6 +1120,10 @@ impl Board {
         let piece = self.pieces.get_mut(&piece_index).unwrap();
         
         self.hash ^= self.hash_table[piece.to_piece_index() * 64 + piece.pos.y * 8 + piece.pos.x];
+
+        if piece.piece_type == PieceType::Pawn {
+            self.pawn_hash ^= self.hash_table[piece.to_piece_index() * 64 + piece.pos.y * 8 + piece.pos.x];
+        }
         
         piece.piece_type = piece_type;
 
@@ -1037,7 +1215,8 @@ impl Board {
     pub fn get_total_legal_moves_quiescence(&mut self, _color: Option<PieceColor>, quiescence: bool) -> Vec<Move> {
         let color = _color.unwrap_or(self.turn);
 
-        if let Some(cached) = self.total_moves_cache.get(&color) {
+        // only full move lists are cached, a capture list would hide the quiet moves
+        if let Some(cached) = self.total_moves_cache.get(&color).filter(|_| !quiescence) {
             if !cached.is_empty() {
                 return cached.clone();
             }
@@ -1062,7 +1241,9 @@ impl Board {
             self.collect_all_legal_moves(color, &mut result, quiescence);
         }
 
-        self.total_moves_cache.insert(color, result.clone());
+        if !quiescence {
+            self.total_moves_cache.insert(color, result.clone());
+        }
 
         result
     }
@@ -1251,9 +1432,109 @@ impl Board {
         phase as f64 / MAX_PHASE as f64
     }
 
+    // accepts the usual variants: zeros for castling, `e.p.`, promotions without `=`,
+    // redundant disambiguation, long algebraic moves, check and annotation suffixes
+    pub fn parse_san(&mut self, san: &str) -> Result<Move, SanError> {
+        let syntax = || SanError::Syntax(san.to_string());
+
+        let mut text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        text = text.strip_suffix("e.p.").unwrap_or(text).trim_end().trim_end_matches(['+', '#']);
+
+        let legal_moves = self.get_total_legal_moves(None);
+
+        let castling_file = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None
+        };
+
+        if let Some(file) = castling_file {
+            return legal_moves.into_iter()
+                .find(|m| m.move_type.contains(&MoveType::Castling) && m.to.x == file)
+                .ok_or_else(|| SanError::Illegal(san.to_string()));
+        }
+
+        let mut chars: Vec<char> = text.chars().filter(|c| !matches!(c, 'x' | ':' | '-' | '(' | ')')).collect();
+
+        let piece_type = match chars.first() {
+            Some('K') => Some(PieceType::King),
+            Some('Q') => Some(PieceType::Queen),
+            Some('R') => Some(PieceType::Rook),
+            Some('B') => Some(PieceType::Bishop),
+            Some('N') => Some(PieceType::Knight),
+            _ => None
+        };
+
+        if piece_type.is_some() {
+            chars.remove(0);
+        }
+
+        let piece_type = piece_type.unwrap_or(PieceType::Pawn);
+
+        let promotion = match chars.last() {
+            Some('Q' | 'q') => Some(PieceType::Queen),
+            Some('R' | 'r') => Some(PieceType::Rook),
+            Some('B') => Some(PieceType::Bishop),
+            Some('N' | 'n') => Some(PieceType::Knight),
+            _ => None
+        };
+
+        if promotion.is_some() {
+            chars.pop();
+
+            if matches!(chars.last(), Some('=' | '/')) {
+                chars.pop();
+            }
+        }
+
+        if chars.len() < 2 || chars.len() > 4 {
+            return Err(syntax());
+        }
+
+        let square = |file: char, rank: char| -> Option<Position> {
+            let x = "abcdefgh".find(file)?;
+            let rank = rank.to_digit(10).filter(|rank| (1..=8).contains(rank))?;
+
+            Some(Position { x, y: 8 - rank as usize })
+        };
+
+        let (prefix, destination) = chars.split_at(chars.len() - 2);
+        let to = square(destination[0], destination[1]).ok_or_else(syntax)?;
+
+        let mut from_file = None;
+        let mut from_rank = None;
+
+        for &c in prefix {
+            match c {
+                'a'..='h' if from_file.is_none() => from_file = "abcdefgh".find(c),
+                '1'..='8' if from_rank.is_none() => from_rank = c.to_digit(10).map(|rank| 8 - rank as usize),
+                _ => return Err(syntax())
+            }
+        }
+
+        let candidates: Vec<Move> = legal_moves.into_iter()
+            .filter(|m| m.piece_type == piece_type && m.to == to)
+            .filter(|m| !m.move_type.contains(&MoveType::Castling))
+            .filter(|m| from_file.is_none_or(|x| m.from.x == x) && from_rank.is_none_or(|y| m.from.y == y))
+            // a missing promotion piece is read as a queen
+            .filter(|m| m.promote_to.is_none() || m.promote_to == Some(promotion.unwrap_or(PieceType::Queen)))
+            .collect();
+
+        if promotion.is_some() && candidates.iter().any(|m| m.promote_to.is_none()) {
+            return Err(SanError::Illegal(san.to_string()));
+        }
+
+        match candidates.len() {
+            0 => Err(SanError::Illegal(san.to_string())),
+            1 => Ok(candidates.into_iter().next().unwrap()),
+            _ => Err(SanError::Ambiguous(san.to_string()))
+        }
+    }
+
     pub fn gen_hash(&mut self) {
         let mut hash_array = Vec::with_capacity(782);
         let mut hash = i64::MAX;
+        let mut pawn_hash = i64::MAX;
 
         let mut rng = StdRng::seed_from_u64(9009);
 
@@ -1266,6 +1547,10 @@ impl Board {
             let piece_index = piece.to_piece_index();
 
             hash ^= hash_array[piece_index * 64 + pos.y * 8 + pos.x];
+
+            if piece.piece_type == PieceType::Pawn {
+                pawn_hash ^= hash_array[piece_index * 64 + pos.y * 8 + pos.x];
+            }
         }
 
         if self.castling.white.0 { hash ^= hash_array[12 * 64]; }
@@ -1284,6 +1569,7 @@ impl Board {
         }
 
         self.hash = hash;
+        self.pawn_hash = pawn_hash;
         self.hash_table = hash_array;
     }
 }
@@ -1,35 +1,339 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{self, BufRead};
-use std::path::Path;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use rand::Rng;
 
 use crate::board::Board;
 use crate::moves::Move;
+use crate::piece::PieceColor;
+use crate::pgn::{self, Game};
+use crate::polyglot::{decode_move, encode_move};
+use crate::r#const::{BOOK_LEARNING_DROP, BOOK_MAX_PLIES, DEFAULT_BOOK_DEPTH};
 
-#[derive(Debug, Clone)]
-pub struct OpeningBook {
-    root: BookNode,
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BookSelection {
+    Best,
+    // proportional to how often a move was played
+    Weighted,
+    // proportional to the points scored with a move
+    Score
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BookOptions {
+    pub selection: BookSelection,
+    // moves played in fewer games are ignored
+    pub min_games: usize,
+    // the book is left after this many plies of the game
+    pub max_depth: usize
+}
+
+impl Default for BookOptions {
+    fn default() -> Self {
+        BookOptions {
+            selection: BookSelection::Best,
+            min_games: 1,
+            max_depth: DEFAULT_BOOK_DEPTH
+        }
+    }
+}
+
+// results are from the point of view of the side that played the move
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BookMove {
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize
+}
+
+impl BookMove {
+    // the usual Polyglot weight, two for a win and one for a draw
+    pub fn points(&self) -> usize {
+        2 * self.wins + self.draws
+    }
+
+    fn add(&mut self, other: &BookMove) {
+        self.games += other.games;
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    // a move keeps at least one game so it stays in the book
+    fn scaled(&self, multiplier: usize, divisor: usize) -> BookMove {
+        BookMove {
+            games: (self.games * multiplier / divisor).max(1),
+            wins: self.wins * multiplier / divisor,
+            draws: self.draws * multiplier / divisor,
+            losses: self.losses * multiplier / divisor
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+    Unknown
+}
+
+impl GameResult {
+    fn from_tag(value: &str) -> GameResult {
+        match value {
+            "1-0" => GameResult::WhiteWin,
+            "0-1" => GameResult::BlackWin,
+            "1/2-1/2" => GameResult::Draw,
+            _ => GameResult::Unknown
+        }
+    }
+
+    fn for_side(&self, color: PieceColor) -> BookMove {
+        let (wins, draws, losses) = match (self, color) {
+            (GameResult::WhiteWin, PieceColor::White) | (GameResult::BlackWin, PieceColor::Black) => (1, 0, 0),
+            (GameResult::WhiteWin, PieceColor::Black) | (GameResult::BlackWin, PieceColor::White) => (0, 0, 1),
+            (GameResult::Draw, _) => (0, 1, 0),
+            (GameResult::Unknown, _) => (0, 0, 0)
+        };
+
+        BookMove { games: 1, wins, draws, losses }
+    }
 }
 
+// the games of a file by move sequence, shared openings are only replayed once
 #[derive(Debug, Clone)]
-struct BookNode {
-    moves: HashMap<String, usize>,
-    children: HashMap<String, BookNode>,
+struct GameTree {
+    stats: BookMove,
+    children: HashMap<String, GameTree>,
 }
 
-impl BookNode {
+impl GameTree {
     fn new() -> Self {
-        BookNode {
-            moves: HashMap::new(),
+        GameTree {
+            stats: BookMove::default(),
             children: HashMap::new(),
         }
     }
+
+    fn add_game(&mut self, game: &Game, turn: PieceColor) {
+        let result = GameResult::from_tag(game.header("Result").unwrap_or(&game.result));
+        let mut current = self;
+        let mut color = turn;
+
+        for m in game.moves.iter().take(BOOK_MAX_PLIES) {
+            current = current.children
+                .entry(m.san.clone())
+                .or_insert_with(GameTree::new);
+
+            current.stats.add(&result.for_side(color));
+            color = color.opposite();
+        }
+    }
+}
+
+// picks an item with a probability proportional to its weight
+pub fn weighted_choice<T>(items: Vec<(T, u64)>) -> Option<T> {
+    let total: u64 = items.iter().map(|(_, weight)| weight).sum();
+
+    if total == 0 {
+        return None;
+    }
+
+    let mut pick = rand::rng().random_range(0..total);
+
+    items.into_iter().find(|(_, weight)| {
+        if pick < *weight {
+            return true;
+        }
+
+        pick -= weight;
+        false
+    }).map(|(item, _)| item)
+}
+
+// A compiled book is a header (magic, version, position and entry counts) followed by
+// entries sorted by key and move, all little endian. Entries are read straight from the
+// mapped file, so loading does not depend on the size of the book.
+pub const COMPILED_BOOK_EXTENSION: &str = "mcb";
+const COMPILED_MAGIC: &[u8; 8] = b"MCHSBOOK";
+const COMPILED_VERSION: u32 = 1;
+const HEADER_SIZE: usize = 24;
+// key, move, two unused bytes and the four counters
+const ENTRY_SIZE: usize = 28;
+
+#[derive(Debug)]
+enum Storage {
+    // built from PGN files
+    Positions(HashMap<u64, HashMap<u16, BookMove>>),
+    Compiled(Mmap)
+}
+
+fn read_entry(bytes: &[u8], index: usize) -> (u64, u16, BookMove) {
+    let entry = &bytes[HEADER_SIZE + index * ENTRY_SIZE..HEADER_SIZE + (index + 1) * ENTRY_SIZE];
+    let counter = |offset: usize| u32::from_le_bytes(entry[offset..offset + 4].try_into().unwrap()) as usize;
+
+    let stats = BookMove {
+        games: counter(12),
+        wins: counter(16),
+        draws: counter(20),
+        losses: counter(24)
+    };
+
+    (u64::from_le_bytes(entry[0..8].try_into().unwrap()), u16::from_le_bytes([entry[8], entry[9]]), stats)
+}
+
+pub fn choose_move(moves: Vec<(Move, BookMove)>, options: &BookOptions) -> Option<Move> {
+    let moves: Vec<_> = moves.into_iter()
+        .filter(|(_, stats)| stats.games >= options.min_games.max(1))
+        .collect();
+
+    let best = moves.iter().max_by_key(|(_, stats)| stats.games).map(|(m, _)| m.clone());
+
+    let weighted = match options.selection {
+        BookSelection::Best => return best,
+        BookSelection::Weighted => weighted_choice(moves.into_iter().map(|(m, stats)| (m, stats.games as u64)).collect()),
+        BookSelection::Score => weighted_choice(moves.into_iter().map(|(m, stats)| (m, stats.points() as u64)).collect())
+    };
+
+    // only lost games, play the main line anyway
+    weighted.or(best)
+}
+
+// positions are keyed by their Polyglot hash so transpositions and games started
+// from a FEN share the statistics, moves use the Polyglot encoding
+#[derive(Debug)]
+pub struct OpeningBook {
+    storage: Storage,
 }
 
 impl OpeningBook {
     pub fn new() -> Self {
         OpeningBook {
-            root: BookNode::new(),
+            storage: Storage::Positions(HashMap::new()),
+        }
+    }
+
+    pub fn load_compiled(path: &Path) -> io::Result<OpeningBook> {
+        let file = File::open(path)?;
+
+        // SAFETY: the book is never written while the engine runs, a file changed
+        // underneath the mapping only gives wrong statistics
+        let bytes = unsafe { Mmap::map(&file)? };
+
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        if bytes.len() < HEADER_SIZE || &bytes[0..8] != COMPILED_MAGIC {
+            return Err(invalid(format!("{:?} is not a compiled book", path)));
+        }
+
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        let entries = u64::from_le_bytes(bytes[16..24].try_into().unwrap()) as usize;
+
+        if version != COMPILED_VERSION {
+            return Err(invalid(format!("Unsupported book version {}", version)));
+        }
+
+        if bytes.len() != HEADER_SIZE + entries * ENTRY_SIZE {
+            return Err(invalid(format!("Book size {} does not match {} entries", bytes.len(), entries)));
+        }
+
+        Ok(OpeningBook {
+            storage: Storage::Compiled(bytes),
+        })
+    }
+
+    // returns the number of entries written
+    pub fn save_compiled(&self, path: &Path) -> io::Result<usize> {
+        let mut entries: Vec<(u64, u16, BookMove)> = match &self.storage {
+            Storage::Positions(positions) => positions.iter()
+                .flat_map(|(&key, moves)| moves.iter().map(move |(&m, &stats)| (key, m, stats)))
+                .collect(),
+            Storage::Compiled(bytes) => (0..self.entries()).map(|index| read_entry(bytes, index)).collect()
+        };
+
+        entries.sort_by_key(|&(key, m, _)| (key, m));
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(COMPILED_MAGIC)?;
+        writer.write_all(&COMPILED_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.len() as u32).to_le_bytes())?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+
+        for (key, m, stats) in &entries {
+            writer.write_all(&key.to_le_bytes())?;
+            writer.write_all(&m.to_le_bytes())?;
+            writer.write_all(&[0; 2])?;
+
+            for counter in [stats.games, stats.wins, stats.draws, stats.losses] {
+                writer.write_all(&(counter.min(u32::MAX as usize) as u32).to_le_bytes())?;
+            }
+        }
+
+        writer.flush()?;
+        Ok(entries.len())
+    }
+
+    // a compiled book is read back into memory before games are added to it
+    fn positions_mut(&mut self) -> &mut HashMap<u64, HashMap<u16, BookMove>> {
+        if let Storage::Compiled(bytes) = &self.storage {
+            let mut positions: HashMap<u64, HashMap<u16, BookMove>> = HashMap::new();
+
+            for index in 0..self.entries() {
+                let (key, m, stats) = read_entry(bytes, index);
+                positions.entry(key).or_default().insert(m, stats);
+            }
+
+            self.storage = Storage::Positions(positions);
+        }
+
+        match &mut self.storage {
+            Storage::Positions(positions) => positions,
+            Storage::Compiled(_) => unreachable!()
+        }
+    }
+
+    fn lookup(&self, key: u64) -> Vec<(u16, BookMove)> {
+        match &self.storage {
+            Storage::Positions(positions) => positions.get(&key)
+                .map(|moves| moves.iter().map(|(&m, &stats)| (m, stats)).collect())
+                .unwrap_or_default(),
+            Storage::Compiled(bytes) => {
+                let entries = self.entries();
+                let key_at = |index: usize| read_entry(bytes, index).0;
+
+                // binary search for the first entry of the position
+                let (mut low, mut high) = (0, entries);
+
+                while low < high {
+                    let middle = (low + high) / 2;
+
+                    if key_at(middle) < key {
+                        low = middle + 1;
+                    } else {
+                        high = middle;
+                    }
+                }
+
+                (low..entries)
+                    .map(|index| read_entry(bytes, index))
+                    .take_while(|&(entry_key, _, _)| entry_key == key)
+                    .map(|(_, m, stats)| (m, stats))
+                    .collect()
+            }
+        }
+    }
+
+    // the number of moves stored over all positions
+    fn entries(&self) -> usize {
+        match &self.storage {
+            Storage::Positions(positions) => positions.values().map(|moves| moves.len()).sum(),
+            Storage::Compiled(bytes) => (bytes.len() - HEADER_SIZE) / ENTRY_SIZE
         }
     }
 
@@ -69,189 +373,203 @@ impl OpeningBook {
         Ok(total_games)
     }
 
+    // games with syntax errors are skipped, a game stops at its first illegal move
     pub fn load_pgn_file<P: AsRef<Path>>(&mut self, file_path: P) -> io::Result<usize> {
-        let file = File::open(file_path)?;
-        let reader = io::BufReader::new(file);
-        
-        let mut in_game = false;
-        let mut moves = Vec::new();
+        let text = fs::read_to_string(&file_path)?;
+
         let mut loaded_games = 0;
-        
-        for line in reader.lines() {
-            let line = line?;
-            let trimmed = line.trim();
-            
-            if trimmed.is_empty() {
-                continue;
-            }
-            
-            if trimmed.starts_with('[') {
-                if in_game && !moves.is_empty() {
-                    self.add_game(&moves);
-                    moves.clear();
-                    loaded_games += 1;
+        let mut games = GameTree::new();
+        // games set up from a position are replayed from there
+        let mut setups: HashMap<String, GameTree> = HashMap::new();
+
+        for game in pgn::games(&text) {
+            let game = match game {
+                Ok(game) => game,
+                Err(e) => {
+                    eprintln!("Skipping game in {:?}: {}", file_path.as_ref(), e);
+                    continue;
                 }
-                in_game = false;
+            };
+
+            if game.moves.is_empty() {
                 continue;
             }
-            
-            in_game = true;
-            
-            for token in trimmed.split_whitespace() {
-                if token.contains('.') && !token.starts_with('.') {
-                    let parts: Vec<&str> = token.split('.').collect();
-                    if parts.len() > 1 && !parts[1].is_empty() {
-                        let m = parts[1];
-                        self.process_token(m, &mut moves)?;
-                    }
-                } else {
-                    self.process_token(token, &mut moves)?;
-                }
+
+            match game.header("FEN") {
+                Some(fen) => {
+                    let turn = game.start_position().turn;
+                    setups.entry(fen.to_string()).or_insert_with(GameTree::new).add_game(&game, turn);
+                },
+                None => games.add_game(&game, PieceColor::White)
             }
-        }
-        
-        if !moves.is_empty() {
-            self.add_game(&moves);
+
             loaded_games += 1;
         }
-        
+
+        self.add_tree(&mut Board::startpos(), &games);
+
+        for (fen, games) in &setups {
+            self.add_tree(&mut Board::from_fen(fen), games);
+        }
+
         Ok(loaded_games)
     }
-    
-    fn process_token(&mut self, token: &str, moves: &mut Vec<String>) -> io::Result<()> {
-        if token.parse::<u32>().is_ok() {
-            return Ok(());
-        }
-        
-        if token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*" {
-            return Ok(());
-        }
-        
-        let m = token.trim_end_matches(&['+', '#', '!', '?'][..])
-            .split(';').next().unwrap()
-            .split('{').next().unwrap()
-            .to_string();
-        
-        if m.is_empty() || m == "0-0" || m == "0-0-0" {
-            return Ok(());
+
+    // replays every line of the tree once, a line stops at the first move that is not legal
+    fn add_tree(&mut self, board: &mut Board, node: &GameTree) {
+        let key = board.polyglot_hash();
+
+        for (san, child) in &node.children {
+            let Ok(m) = board.parse_san(san) else {
+                continue;
+            };
+
+            self.positions_mut().entry(key)
+                .or_default()
+                .entry(encode_move(&m))
+                .or_default()
+                .add(&child.stats);
+
+            let history = board.make_move(&m);
+            self.add_tree(board, child);
+            board.unmake_move(&m, &history);
         }
+    }
 
-        moves.push(m);
-        
-        Ok(())
+    pub fn get_best_move(&self, board: &mut Board) -> Option<Move> {
+        self.pick_move(board, &BookOptions::default())
     }
 
-    fn add_game(&mut self, moves: &[String]) {
-        if moves.is_empty() {
-            return;
-        }
-    
-        let mut current = &mut self.root;
-    
-        for m in moves {
-            *current.moves.entry(m.clone()).or_insert(0) += 1;
-            
-            current = current.children
-                .entry(m.clone())
-                .or_insert_with(BookNode::new);
-        }
+    pub fn pick_move(&self, board: &mut Board, options: &BookOptions) -> Option<Move> {
+        choose_move(self.moves(board), options)
     }
-    
-    pub fn get_best_move(&self, moves: &[String]) -> Option<String> {
-        let mut current = &self.root;
-        
-        for mv in moves {
-            match current.children.get(mv) {
-                Some(child) => current = child,
-                None => return None,
-            }
-        }
-        
-        current.moves.iter()
-            .max_by_key(|&(_, count)| count)
-            .map(|(mv, _)| mv.clone())
+
+    // the statistics of each move played from the position
+    pub fn moves(&self, board: &mut Board) -> Vec<(Move, BookMove)> {
+        self.lookup(board.polyglot_hash()).into_iter()
+            .filter_map(|(m, stats)| decode_move(board, m).map(|m| (m, stats)))
+            .collect()
     }
 
-    pub fn to_move(&self, san: &str, board: &mut Board) -> Option<Move> {
-        let mut found = None;
-        for m in board.get_total_legal_moves(None) {
-            if m.to_san(board) == san {
-                found = Some(m);
-            }
+    // the number of positions
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Positions(positions) => positions.len(),
+            Storage::Compiled(bytes) => u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize
         }
-        
-        found
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries() == 0
     }
 
     pub fn print_statistics(&self) {
-        let mut first_moves: Vec<_> = self.root.moves.iter().collect();
+        let mut board = Board::startpos();
+        let mut first_moves = self.moves(&mut board);
         
-        first_moves.sort_by(|&(_, count1), &(_, count2)| count2.cmp(count1));
+        first_moves.sort_by_key(|(_, stats)| Reverse(stats.games));
         
         if first_moves.is_empty() {
             println!("info string book: no moves in book");
             return;
         }
         
-        println!("info string book: first branches");
+        println!("info string book: first moves");
         
-        for (mv, count) in first_moves {
-            match self.root.children.get(mv) {
-                Some(node) => {
-                    let total_branch_moves = self.count_all_branch_moves(node);
-                    
-                    println!("info string book: - {} - played {} times, {} total sub mov stored", mv, count, total_branch_moves);
-                },
-                None => {
-                    println!("info string book: - {} - played {} times", mv, count);
-                }
-            }
+        for (m, stats) in first_moves {
+            println!("info string book: - {} - played {} times, +{} ={} -{}", m.to_san(&board), stats.games, stats.wins, stats.draws, stats.losses);
         }
         
-        let total_positions = self.count_total_positions();
-        let total_moves = self.count_total_moves();
-        
-        println!("\ninfo string book: total pos: {}", total_positions);
-        println!("info string book: total mov: {}", total_moves);
+        println!("\ninfo string book: total pos: {}", self.len());
+        println!("info string book: total mov: {}", self.entries());
     }
+}
+// the engine's own results with the book moves it played, kept in a compiled book of
+// their own so the source games stay untouched
+#[derive(Debug)]
+pub struct BookLearning {
+    results: OpeningBook,
+    path: PathBuf
+}
 
-    fn count_all_branch_moves(&self, node: &BookNode) -> usize {
-        let mut count = node.moves.len();
-        
-        for (_, child) in &node.children {
-            count += self.count_all_branch_moves(child);
-        }
-        
-        count
+impl BookLearning {
+    // a missing file starts an empty one
+    pub fn open(path: &Path) -> io::Result<BookLearning> {
+        let mut results = if path.exists() {
+            OpeningBook::load_compiled(path)?
+        } else {
+            OpeningBook::new()
+        };
+
+        // read into memory so the file can be replaced
+        results.positions_mut();
+
+        Ok(BookLearning { results, path: path.to_path_buf() })
     }
 
-    fn count_total_positions(&self) -> usize {
-        let mut count = 1;
-        
-        for (_, child) in &self.root.children {
-            count += self.count_positions_recursive(child);
-        }
-        
-        count
+    pub fn len(&self) -> usize {
+        self.results.entries()
     }
 
-    fn count_positions_recursive(&self, node: &BookNode) -> usize {
-        let mut count = 1;
-        
-        for (_, child) in &node.children {
-            count += self.count_positions_recursive(child);
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+
+    // adds the game result for every (key, move, side) the engine played from the book and
+    // saves the file, games without a result are not learned from
+    pub fn learn(&mut self, line: &[(u64, u16, PieceColor)], result: &str) -> io::Result<usize> {
+        let result = GameResult::from_tag(result);
+
+        if result == GameResult::Unknown || line.is_empty() {
+            return Ok(0);
         }
-        
-        count
+
+        for &(key, m, color) in line {
+            self.results.positions_mut()
+                .entry(key)
+                .or_default()
+                .entry(m)
+                .or_default()
+                .add(&result.for_side(color));
+        }
+
+        // written next to the file first so a reader never sees half of it
+        let temporary = self.path.with_extension("tmp");
+        self.results.save_compiled(&temporary)?;
+        fs::rename(&temporary, &self.path)?;
+
+        Ok(line.len())
     }
 
-    fn count_total_moves(&self) -> usize {
-        let mut count = self.root.moves.len();
-        
-        for (_, child) in &self.root.children {
-            count += self.count_all_branch_moves(child);
+    // every win more than losses multiplies the statistics of a move, every loss more divides
+    // them, a move that lost BOOK_LEARNING_DROP games more than it won is not played anymore
+    pub fn adjust(&self, board: &Board, moves: Vec<(Move, BookMove)>) -> Vec<(Move, BookMove)> {
+        let learned: HashMap<u16, BookMove> = self.results.lookup(board.polyglot_hash()).into_iter().collect();
+
+        if learned.is_empty() {
+            return moves;
         }
-        
-        count
+
+        moves.into_iter()
+            .filter_map(|(m, stats)| {
+                let Some(result) = learned.get(&encode_move(&m)) else {
+                    return Some((m, stats));
+                };
+
+                let net = result.wins as i64 - result.losses as i64;
+
+                if net <= -BOOK_LEARNING_DROP {
+                    return None;
+                }
+
+                let stats = if net >= 0 {
+                    stats.scaled(1 + net as usize, 1)
+                } else {
+                    stats.scaled(1, 1 + net.unsigned_abs() as usize)
+                };
+
+                Some((m, stats))
+            })
+            .collect()
     }
-}
\ No newline at end of file
+}
@@ -20,14 +20,99 @@ pub const MOBILITY_VALUE: f64 = 0.05;
 pub const NO_SAFETY_PENALTY: f64 = 0.8;
 pub const LOW_SAFETY_PENALTY: f64 = 0.5; 
 
+// indexed by relative rank, 0 being the pawn's own back rank
+pub const PASSED_PAWN_BONUS: [f64; 8] = [0.0, 0.1, 0.15, 0.25, 0.45, 0.75, 1.2, 0.0];
+pub const CANDIDATE_PASSER_BONUS: [f64; 8] = [0.0, 0.05, 0.05, 0.1, 0.2, 0.35, 0.0, 0.0];
+pub const CONNECTED_PAWN_BONUS: [f64; 8] = [0.0, 0.02, 0.04, 0.06, 0.1, 0.2, 0.35, 0.0];
+pub const PASSED_PAWN_ENDGAME_FACTOR: f64 = 1.5;
+pub const PASSED_PAWN_FREE_PATH_BONUS: f64 = 0.4;
+pub const PASSED_PAWN_KING_PROXIMITY: f64 = 0.1;
+pub const UNSTOPPABLE_PASSER_BONUS: f64 = 6.0;
+pub const PAWN_CHAIN_BONUS: f64 = 0.05;
+pub const DOUBLED_PAWN_PENALTY: f64 = 0.25;
+pub const BACKWARD_PAWN_PENALTY: f64 = 0.15;
+pub const PAWN_TABLE_SIZE_MB: usize = 2;
+
+pub const BISHOP_PAIR_BONUS: f64 = 0.5;
+pub const ROOK_OPEN_FILE_BONUS: f64 = 0.25;
+pub const ROOK_SEMI_OPEN_FILE_BONUS: f64 = 0.12;
+pub const ROOK_SEVENTH_RANK_BONUS: f64 = 0.3;
+pub const KNIGHT_OUTPOST_BONUS: f64 = 0.35;
+pub const BISHOP_OUTPOST_BONUS: f64 = 0.15;
+pub const TRAPPED_BISHOP_PENALTY: f64 = 1.5;
+pub const TRAPPED_ROOK_PENALTY: f64 = 0.5;
+pub const QUEEN_EARLY_DEVELOPMENT_PENALTY: f64 = 0.15;
+
+pub const KNOWN_WIN_VALUE: f64 = 20.0;
+pub const ENDGAME_EDGE_WEIGHT: f64 = 1.0;
+pub const ENDGAME_CORNER_WEIGHT: f64 = 0.5;
+pub const ENDGAME_PROXIMITY_WEIGHT: f64 = 0.3;
+pub const OPPOSITE_BISHOPS_SCALE: f64 = 0.5;
+pub const NO_PAWNS_SCALE: f64 = 0.2;
+
+pub const TB_WIN_VALUE: f64 = 10000.0;
+pub const TB_CURSED_WIN_VALUE: f64 = 0.1;
+
 pub const MOVE_PREALLOC: usize = 30;
 pub const MAX_PLIES: u8 = 50;
 pub const MAX_WINDOW_WIDTH: f64 = 5000.0;
 pub const DEFAULT_MARGIN: f64 = 200.0;
 
+pub const TIME_CHECK_INTERVAL: u64 = 1024;
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 10;
+pub const DEFAULT_MOVES_TO_GO: u64 = 30;
+pub const BEST_MOVE_CHANGE_FACTOR: f64 = 1.4;
+pub const STABILITY_FACTOR: f64 = 0.8;
+pub const STABLE_ITERATIONS: u32 = 4;
+pub const SCORE_DROP_THRESHOLD: f64 = 0.3;
+pub const SCORE_DROP_FACTOR: f64 = 1.5;
+pub const MIN_TIME_SCALE: f64 = 0.5;
+pub const MAX_TIME_SCALE: f64 = 2.5;
+
 pub const MAX_PHASE: i32 = 24;
 
+pub const MAX_THREADS: usize = 64;
+pub const DEFAULT_HASH_SIZE: usize = 64;
+pub const MAX_HASH_SIZE: usize = 4096;
+
+pub const DEFAULT_MAX_SESSIONS: usize = 32;
+pub const DEFAULT_SESSIONS_PER_OWNER: usize = 4;
+pub const DEFAULT_SESSION_TTL: u64 = 1800;
+pub const DEFAULT_SESSION_HASH_SIZE: usize = 16;
+pub const DEFAULT_SESSION_MCTS_NODES: usize = 1000000;
+pub const DEFAULT_SESSION_THREADS: usize = 1;
+pub const SESSION_EVICTION_INTERVAL: u64 = 60;
+pub const COMMAND_TIMEOUT: u64 = 30;
+pub const SEARCH_LIMIT_WINDOW: u64 = 60;
+pub const CPU_LIMIT_WINDOW: u64 = 3600;
+
+pub const BOOK_MAX_PLIES: usize = 20;
+pub const DEFAULT_BOOK_DEPTH: usize = 100;
+pub const BOOK_LEARNING_DROP: i64 = 3;
+pub const SELF_PLAY_MAX_PLIES: usize = 200;
+
+pub const API_DEFAULT_DEPTH: u8 = 5;
+pub const API_MAX_MOVE_TIME: u64 = 10000;
+pub const API_MAX_MULTIPV: usize = 10;
+
+pub const MAX_SKILL_LEVEL: u8 = 20;
+pub const SKILL_MIN_ELO: u32 = 800;
+pub const SKILL_MAX_ELO: u32 = 2400;
+pub const SKILL_BASE_NODES: u64 = 500;
+pub const SKILL_TIME_STEP: u64 = 100;
+pub const SKILL_MULTIPV: usize = 4;
+
 pub const MCTS_MAX_PLIES: usize = 100;
+pub const MCTS_MAX_NODES: usize = 200000;
+pub const MCTS_REUSE_DEPTH: u8 = 2;
+pub const MCTS_CPUCT: f64 = 1.0;
+pub const MCTS_FPU_REDUCTION: f64 = 0.1;
+pub const MCTS_PRIOR_TEMPERATURE: f64 = 50.0;
+pub const MCTS_EVAL_PRIOR_TEMPERATURE: f64 = 1.0;
+pub const MCTS_WIN_PROBABILITY_SCALE: f64 = 400.0;
+pub const MCTS_QUIESCENCE_DEPTH: i8 = 8;
+pub const MCTS_PROBE_DEPTH: u8 = 2;
+pub const MCTS_LEAF_HASH_SIZE: usize = 4;
 
 pub const PAWN_VALUE: f64 = 100.0;
 pub const KNIGHT_VALUE: f64 = 320.0;
@@ -0,0 +1,393 @@
+use std::sync::OnceLock;
+
+use crate::{board::Board, evaluation::EvaluationResult, moves::Position, piece::PieceColor, pieces::bitboard::{A_FILE, COLOR_MASK, H_FILE}, r#const::{ENDGAME_CORNER_WEIGHT, ENDGAME_EDGE_WEIGHT, ENDGAME_PROXIMITY_WEIGHT, KNOWN_WIN_VALUE, NO_PAWNS_SCALE, OPPOSITE_BISHOPS_SCALE}};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Endgame {
+    // replaces the general evaluation entirely
+    Value(EvaluationResult),
+    // multiplies the general evaluation
+    Scale(f64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideMaterial {
+    pub pawns: u32,
+    pub knights: u32,
+    pub bishops: u32,
+    pub rooks: u32,
+    pub queens: u32,
+    pub bishop_squares: u64
+}
+
+impl SideMaterial {
+    pub fn non_pawn_material(&self) -> u32 {
+        self.knights * 3 + self.bishops * 3 + self.rooks * 5 + self.queens * 9
+    }
+
+    pub fn total(&self) -> u32 {
+        self.non_pawn_material() + self.pawns
+    }
+
+    pub fn is_bare(&self) -> bool {
+        self.total() == 0
+    }
+
+    pub fn has_only(&self, pawns: u32, knights: u32, bishops: u32, rooks: u32, queens: u32) -> bool {
+        self.pawns == pawns && self.knights == knights && self.bishops == bishops && self.rooks == rooks && self.queens == queens
+    }
+
+    fn has_bishops_on_both_colors(&self) -> bool {
+        self.bishop_squares & COLOR_MASK != 0 && self.bishop_squares & !COLOR_MASK != 0
+    }
+
+    fn can_force_mate(&self) -> bool {
+        self.queens > 0 || self.rooks > 0 || self.has_bishops_on_both_colors() || (self.bishops > 0 && self.knights > 0) || self.knights >= 3
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaterialSignature {
+    pub white: SideMaterial,
+    pub black: SideMaterial
+}
+
+impl MaterialSignature {
+    pub fn from_board(board: &Board) -> Self {
+        let bb = &board.bb;
+
+        MaterialSignature {
+            white: SideMaterial {
+                pawns: bb.white_pawns.count_ones(),
+                knights: bb.white_knights.count_ones(),
+                bishops: bb.white_bishops.count_ones(),
+                rooks: bb.white_rooks.count_ones(),
+                queens: bb.white_queens.count_ones(),
+                bishop_squares: bb.white_bishops
+            },
+            black: SideMaterial {
+                pawns: bb.black_pawns.count_ones(),
+                knights: bb.black_knights.count_ones(),
+                bishops: bb.black_bishops.count_ones(),
+                rooks: bb.black_rooks.count_ones(),
+                queens: bb.black_queens.count_ones(),
+                bishop_squares: bb.black_bishops
+            }
+        }
+    }
+
+    pub fn get(&self, color: PieceColor) -> &SideMaterial {
+        match color {
+            PieceColor::White => &self.white,
+            PieceColor::Black => &self.black
+        }
+    }
+}
+
+fn for_side(color: PieceColor, value: f64) -> EvaluationResult {
+    match color {
+        PieceColor::White => EvaluationResult { white: value, black: 0.0 },
+        PieceColor::Black => EvaluationResult { white: 0.0, black: value }
+    }
+}
+
+fn draw() -> Endgame {
+    Endgame::Value(EvaluationResult::default())
+}
+
+fn center_distance(pos: Position) -> f64 {
+    f64::max((pos.x as f64 - 3.5).abs(), (pos.y as f64 - 3.5).abs())
+}
+
+pub fn probe(board: &Board) -> Option<Endgame> {
+    let signature = MaterialSignature::from_board(board);
+
+    for color in [PieceColor::White, PieceColor::Black] {
+        let strong = signature.get(color);
+        let weak = signature.get(color.opposite());
+
+        if !weak.is_bare() || strong.is_bare() {
+            continue;
+        }
+
+        if strong.has_only(1, 0, 0, 0, 0) {
+            return Some(evaluate_kpk(board, color));
+        }
+
+        if strong.has_only(0, 2, 0, 0, 0) {
+            return Some(draw());
+        }
+
+        if strong.has_only(0, 1, 1, 0, 0) {
+            return Some(evaluate_kbnk(board, color, strong));
+        }
+
+        if strong.pawns == 0 && strong.can_force_mate() {
+            return Some(evaluate_kxk(board, color, strong));
+        }
+
+        if strong.pawns > 0 && strong.has_only(strong.pawns, 0, 1, 0, 0) && is_wrong_rook_pawn(board, color) {
+            return Some(draw());
+        }
+    }
+
+    let white = &signature.white;
+    let black = &signature.black;
+
+    if white.has_only(white.pawns, 0, 1, 0, 0) && black.has_only(black.pawns, 0, 1, 0, 0) && (white.bishop_squares & COLOR_MASK == 0) != (black.bishop_squares & COLOR_MASK == 0) {
+        return Some(Endgame::Scale(OPPOSITE_BISHOPS_SCALE));
+    }
+
+    if white.pawns == 0 && black.pawns == 0 && white.non_pawn_material().abs_diff(black.non_pawn_material()) <= 3 {
+        return Some(Endgame::Scale(NO_PAWNS_SCALE));
+    }
+
+    None
+}
+
+// drives the lone king to the edge and brings the strong king closer
+fn evaluate_kxk(board: &Board, color: PieceColor, strong: &SideMaterial) -> Endgame {
+    let strong_king = board.get_king_pos(color);
+    let weak_king = board.get_king_pos(color.opposite());
+
+    let value = strong.total() as f64 +
+        KNOWN_WIN_VALUE +
+        center_distance(weak_king) * ENDGAME_EDGE_WEIGHT +
+        (7 - strong_king.distance(weak_king)) as f64 * ENDGAME_PROXIMITY_WEIGHT;
+
+    Endgame::Value(for_side(color, value))
+}
+
+// mate can only be forced in a corner of the bishop's colour
+fn evaluate_kbnk(board: &Board, color: PieceColor, strong: &SideMaterial) -> Endgame {
+    let strong_king = board.get_king_pos(color);
+    let weak_king = board.get_king_pos(color.opposite());
+
+    let corners = if strong.bishop_squares & COLOR_MASK != 0 {
+        [Position { x: 0, y: 0 }, Position { x: 7, y: 7 }]
+    } else {
+        [Position { x: 7, y: 0 }, Position { x: 0, y: 7 }]
+    };
+
+    let corner_distance = corners.iter()
+        .map(|corner| weak_king.x.abs_diff(corner.x) + weak_king.y.abs_diff(corner.y))
+        .min()
+        .unwrap_or(0);
+
+    let value = strong.total() as f64 +
+        KNOWN_WIN_VALUE +
+        (14 - corner_distance) as f64 * ENDGAME_CORNER_WEIGHT +
+        center_distance(weak_king) * ENDGAME_EDGE_WEIGHT +
+        (7 - strong_king.distance(weak_king)) as f64 * ENDGAME_PROXIMITY_WEIGHT;
+
+    Endgame::Value(for_side(color, value))
+}
+
+fn evaluate_kpk(board: &Board, color: PieceColor) -> Endgame {
+    let pawns = if color == PieceColor::White { board.bb.white_pawns } else { board.bb.black_pawns };
+    let pawn = Position::from_bitboard(pawns);
+
+    let strong_king = board.get_king_pos(color);
+    let weak_king = board.get_king_pos(color.opposite());
+
+    if kpk_probe(color, strong_king, pawn, weak_king, board.turn == color) {
+        let rank = if color == PieceColor::White { 7 - pawn.y } else { pawn.y };
+        Endgame::Value(for_side(color, KNOWN_WIN_VALUE + 1.0 + rank as f64 * 0.1))
+    } else {
+        draw()
+    }
+}
+
+fn is_wrong_rook_pawn(board: &Board, color: PieceColor) -> bool {
+    let (pawns, bishops, promotion_y) = if color == PieceColor::White {
+        (board.bb.white_pawns, board.bb.white_bishops, 0)
+    } else {
+        (board.bb.black_pawns, board.bb.black_bishops, 7)
+    };
+
+    let file = if pawns & !A_FILE == 0 {
+        0
+    } else if pawns & !H_FILE == 0 {
+        7
+    } else {
+        return false;
+    };
+
+    let promotion = Position { x: file, y: promotion_y };
+    let promotion_light = promotion.to_bitboard() & COLOR_MASK != 0;
+    let bishop_light = bishops & COLOR_MASK != 0;
+
+    promotion_light != bishop_light && board.get_king_pos(color.opposite()).distance(promotion) <= 1
+}
+
+// KPK bitbase, built by retrograde analysis the first time it is needed.
+// Squares are indexed from the strong side's point of view: rank 0 is its back rank
+// and the pawn is always mirrored onto files a-d.
+
+const KPK_SIZE: usize = 2 * 64 * 64 * 4 * 6;
+
+const KPK_INVALID: u8 = 0;
+const KPK_UNKNOWN: u8 = 1;
+const KPK_DRAW: u8 = 2;
+const KPK_WIN: u8 = 4;
+
+static KPK_BITBASE: OnceLock<Vec<bool>> = OnceLock::new();
+
+fn kpk_index(strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize) -> usize {
+    let side = if strong_to_move { 0 } else { 1 };
+    (((side * 64 + strong_king) * 64 + weak_king) * 4 + pawn % 8) * 6 + (pawn / 8 - 1)
+}
+
+fn square_distance(a: usize, b: usize) -> usize {
+    let file = (a % 8).abs_diff(b % 8);
+    let rank = (a / 8).abs_diff(b / 8);
+    file.max(rank)
+}
+
+fn king_attacks(square: usize) -> u64 {
+    let mut attacks = 0u64;
+    for target in 0..64 {
+        if square_distance(square, target) == 1 {
+            attacks |= 1u64 << target;
+        }
+    }
+    attacks
+}
+
+fn kpk_pawn_attacks(pawn: usize) -> u64 {
+    let mut attacks = 0u64;
+    let file = pawn % 8;
+    if pawn / 8 < 7 {
+        if file > 0 { attacks |= 1u64 << (pawn + 7); }
+        if file < 7 { attacks |= 1u64 << (pawn + 9); }
+    }
+    attacks
+}
+
+fn kpk_initial(strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize, king_moves: &[u64; 64]) -> u8 {
+    if square_distance(strong_king, weak_king) <= 1 || strong_king == pawn || weak_king == pawn {
+        return KPK_INVALID;
+    }
+
+    if strong_to_move && kpk_pawn_attacks(pawn) & (1u64 << weak_king) != 0 {
+        return KPK_INVALID;
+    }
+
+    let push = pawn + 8;
+
+    if strong_to_move && pawn / 8 == 6 && strong_king != push &&
+        (square_distance(weak_king, push) > 1 || square_distance(strong_king, push) == 1) {
+        return KPK_WIN;
+    }
+
+    if !strong_to_move {
+        let guarded = king_moves[strong_king] | kpk_pawn_attacks(pawn);
+
+        if king_moves[weak_king] & !guarded == 0 {
+            return KPK_DRAW;
+        }
+
+        if king_moves[weak_king] & (1u64 << pawn) & !king_moves[strong_king] != 0 {
+            return KPK_DRAW;
+        }
+    }
+
+    KPK_UNKNOWN
+}
+
+fn kpk_classify(db: &[u8], strong_to_move: bool, strong_king: usize, weak_king: usize, pawn: usize, king_moves: &[u64; 64]) -> u8 {
+    let (good, bad) = if strong_to_move { (KPK_WIN, KPK_DRAW) } else { (KPK_DRAW, KPK_WIN) };
+
+    let mut result = KPK_INVALID;
+
+    let mut rem = if strong_to_move { king_moves[strong_king] } else { king_moves[weak_king] };
+    while rem != 0 {
+        let square = rem.trailing_zeros() as usize;
+
+        result |= if strong_to_move {
+            db[kpk_index(false, square, weak_king, pawn)]
+        } else {
+            db[kpk_index(true, strong_king, square, pawn)]
+        };
+
+        rem &= rem - 1;
+    }
+
+    if strong_to_move {
+        let push = pawn + 8;
+
+        if pawn / 8 < 6 {
+            result |= db[kpk_index(false, strong_king, weak_king, push)];
+        }
+
+        if pawn / 8 == 1 && push != strong_king && push != weak_king {
+            result |= db[kpk_index(false, strong_king, weak_king, push + 8)];
+        }
+    }
+
+    if result & good != 0 {
+        good
+    } else if result & KPK_UNKNOWN != 0 {
+        KPK_UNKNOWN
+    } else {
+        bad
+    }
+}
+
+fn generate_kpk() -> Vec<bool> {
+    let mut king_moves = [0u64; 64];
+    for (square, moves) in king_moves.iter_mut().enumerate() {
+        *moves = king_attacks(square);
+    }
+
+    let mut positions = Vec::with_capacity(KPK_SIZE);
+    for strong_to_move in [true, false] {
+        for strong_king in 0..64 {
+            for weak_king in 0..64 {
+                for file in 0..4 {
+                    for rank in 1..7 {
+                        positions.push((strong_to_move, strong_king, weak_king, rank * 8 + file));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut db = vec![KPK_INVALID; KPK_SIZE];
+    for &(strong_to_move, strong_king, weak_king, pawn) in &positions {
+        db[kpk_index(strong_to_move, strong_king, weak_king, pawn)] = kpk_initial(strong_to_move, strong_king, weak_king, pawn, &king_moves);
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        for &(strong_to_move, strong_king, weak_king, pawn) in &positions {
+            let index = kpk_index(strong_to_move, strong_king, weak_king, pawn);
+
+            if db[index] == KPK_UNKNOWN {
+                let result = kpk_classify(&db, strong_to_move, strong_king, weak_king, pawn, &king_moves);
+
+                if result != KPK_UNKNOWN {
+                    db[index] = result;
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    db.into_iter().map(|result| result == KPK_WIN).collect()
+}
+
+pub fn kpk_probe(strong_color: PieceColor, strong_king: Position, pawn: Position, weak_king: Position, strong_to_move: bool) -> bool {
+    let bitbase = KPK_BITBASE.get_or_init(generate_kpk);
+
+    let mirror = pawn.x > 3;
+    let normalize = |pos: Position| {
+        let file = if mirror { 7 - pos.x } else { pos.x };
+        let rank = if strong_color == PieceColor::White { 7 - pos.y } else { pos.y };
+        rank * 8 + file
+    };
+
+    bitbase[kpk_index(strong_to_move, normalize(strong_king), normalize(weak_king), normalize(pawn))]
+}
\ No newline at end of file
@@ -1,6 +1,8 @@
-use std::path::Path;
+use std::{collections::HashSet, path::Path, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
 
-use crate::{board::Board, book::OpeningBook, mcts::Mcts, moves::Move, search::Minimax};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::{board::{Board, ResultType}, book::{choose_move, BookLearning, BookOptions, OpeningBook, COMPILED_BOOK_EXTENSION}, evaluation::{Evaluator, HandcraftedEvaluator}, mcts::{Mcts, MctsOptions, MctsStats}, moves::Move, nnue::{Network, NnueEvaluator}, pgn::Game, piece::PieceColor, polyglot::{encode_move, PolyglotBook}, r#const::DEFAULT_HASH_SIZE, search::{to_centipawns, Minimax, SearchInfo}, skill::Skill, syzygy::Tablebase, time_manager::TimeManager};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EngineType {
@@ -8,32 +10,312 @@ pub enum EngineType {
     MCTS
 }
 
+// a root move with its score in centipawns for the side to move and the moves expected to follow
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub m: Move,
+    pub score: i32,
+    pub pv: Vec<Move>
+}
+
 pub struct Engine {
     engine_type: EngineType,
     minimax: Option<Minimax>,
     mcts: Option<Mcts>,
-    pub book: Option<OpeningBook>,
-    pub enable_book: bool
+    pub book: Option<Arc<OpeningBook>>,
+    pub polyglot: Option<Arc<PolyglotBook>>,
+    pub book_options: BookOptions,
+    pub enable_book: bool,
+    // shared by every engine that plays from the book
+    pub learning: Option<Arc<Mutex<BookLearning>>>,
+    // whether finished games change the learned results or only read them
+    pub record_learning: bool,
+    // the book moves played in the current game as (key, move, side)
+    book_line: Vec<(u64, u16, PieceColor)>,
+    pub tablebase: Option<Arc<Tablebase>>,
+    pub network: Option<Arc<Network>>,
+    pub use_nnue: bool,
+    pub mcts_options: MctsOptions,
+    pub threads: usize,
+    // megabytes for each of the minimax tables
+    pub hash_size: usize,
+    // below the top level the engine deliberately plays weaker
+    pub skill: Skill,
+    // centipawns from white's point of view, None when the last move came from a book or tablebase
+    pub last_score: Option<i32>,
+    // where a move from a book or tablebase came from, the protocol writes it as an info string
+    pub last_info: Option<String>,
+    info: Option<UnboundedSender<SearchInfo>>,
+    evaluator: Arc<dyn Evaluator>,
+    stop: Arc<AtomicBool>
 }
 
 impl Engine {
     pub fn new(engine_type: EngineType, enable_book: bool) -> Engine {
-        Engine {
+        let mut engine = Engine {
             engine_type,
-            minimax: if engine_type == EngineType::Minimax { Some(Minimax::new()) } else { None },
+            minimax: if engine_type == EngineType::Minimax { Some(Minimax::with_hash_size(DEFAULT_HASH_SIZE)) } else { None },
             mcts: if engine_type == EngineType::MCTS { Some(Mcts::new()) } else { None },
             enable_book,
-            book: None
-        }
+            book: None,
+            polyglot: None,
+            book_options: BookOptions::default(),
+            learning: None,
+            record_learning: true,
+            book_line: Vec::new(),
+            tablebase: None,
+            network: None,
+            use_nnue: false,
+            mcts_options: MctsOptions::default(),
+            threads: 1,
+            hash_size: DEFAULT_HASH_SIZE,
+            skill: Skill::default(),
+            last_score: None,
+            last_info: None,
+            info: None,
+            evaluator: Arc::new(HandcraftedEvaluator),
+            stop: Arc::new(AtomicBool::new(false))
+        };
+
+        engine.set_stop_signal(engine.stop.clone());
+        engine
     }
 
     pub fn switch_to(&mut self, engine_type: EngineType) {
         self.engine_type = engine_type;
-        self.minimax = if engine_type == EngineType::Minimax { Some(Minimax::new()) } else { None };
+        self.minimax = if engine_type == EngineType::Minimax { Some(Minimax::with_hash_size(self.hash_size)) } else { None };
         self.mcts = if engine_type == EngineType::MCTS { Some(Mcts::new()) } else { None };
+
+        self.set_tablebase(self.tablebase.clone());
+        self.set_evaluator(self.evaluator.clone());
+        self.set_mcts_options(self.mcts_options);
+        self.set_threads(self.threads);
+        self.set_info_sender(self.info.clone());
+        self.set_stop_signal(self.stop.clone());
+    }
+
+    // setting the flag stops the running search, which clears it when it returns
+    pub fn stop_signal(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn set_stop_signal(&mut self, stop: Arc<AtomicBool>) {
+        if let Some(minimax) = self.minimax.as_mut() {
+            minimax.set_stop_signal(stop.clone());
+        }
+
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_stop_signal(stop.clone());
+        }
+
+        self.stop = stop;
+    }
+
+    // search progress goes to the channel instead of only being printed
+    pub fn set_info_sender(&mut self, sender: Option<UnboundedSender<SearchInfo>>) {
+        if let Some(minimax) = self.minimax.as_mut() {
+            minimax.set_info_sender(sender.clone());
+        }
+
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_info_sender(sender.clone());
+        }
+
+        self.info = sender;
+    }
+
+    // the tables are allocated again, which also clears them
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.hash_size = size_mb;
+
+        if self.engine_type == EngineType::Minimax {
+            self.switch_to(EngineType::Minimax);
+        }
+    }
+
+    // only the MCTS search runs on several threads for now
+    pub fn set_threads(&mut self, threads: usize) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_threads(threads);
+        }
+
+        self.threads = threads;
+    }
+
+    // the threads a search of the current engine type runs on
+    pub fn search_threads(&self) -> usize {
+        match self.engine_type {
+            EngineType::Minimax => 1,
+            EngineType::MCTS => self.threads
+        }
+    }
+
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+
+    pub fn mcts_stats(&self) -> Option<MctsStats> {
+        self.mcts.as_ref()?.stats()
+    }
+
+    pub fn set_mcts_options(&mut self, options: MctsOptions) {
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_options(options);
+        }
+
+        self.mcts_options = options;
+    }
+
+    fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        if let Some(minimax) = self.minimax.as_mut() {
+            minimax.set_evaluator(evaluator.clone());
+        }
+
+        if let Some(mcts) = self.mcts.as_mut() {
+            mcts.set_evaluator(evaluator.clone());
+        }
+
+        self.evaluator = evaluator;
+    }
+
+    fn update_evaluator(&mut self) {
+        let evaluator: Arc<dyn Evaluator> = match (&self.network, self.use_nnue) {
+            (Some(network), true) => Arc::new(NnueEvaluator::new(network.clone())),
+            _ => Arc::new(HandcraftedEvaluator)
+        };
+
+        self.set_evaluator(evaluator);
+    }
+
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network;
+        self.update_evaluator();
+    }
+
+    pub fn load_network(&mut self, path: &Path) -> std::io::Result<usize> {
+        let network = Network::load(path)?;
+        let hidden = network.hidden_size();
+
+        self.set_network(Some(Arc::new(network)));
+        Ok(hidden)
+    }
+
+    // returns whether the network is actually in use
+    pub fn set_use_nnue(&mut self, enabled: bool) -> bool {
+        self.use_nnue = enabled;
+        self.update_evaluator();
+
+        enabled && self.network.is_some()
+    }
+
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        if let Some(minimax) = self.minimax.as_mut() {
+            minimax.set_tablebase(tablebase.clone());
+        }
+
+        self.tablebase = tablebase;
+    }
+
+    pub fn load_tablebase(&mut self, paths: &str) -> std::io::Result<usize> {
+        let tablebase = Tablebase::load(paths)?;
+        let tables = tablebase.len();
+
+        self.set_tablebase(if tablebase.is_empty() { None } else { Some(Arc::new(tablebase)) });
+        Ok(tables)
+    }
+
+    fn probe_book(&mut self, board: &mut Board) -> Option<Move> {
+        let ply = 2 * (board.moves.max(1) as usize - 1) + if board.turn == PieceColor::Black { 1 } else { 0 };
+
+        if !self.enable_book || ply >= self.book_options.max_depth {
+            return None;
+        }
+
+        if let Some(m) = self.polyglot.as_ref().and_then(|book| book.pick_move(board, self.book_options.selection)) {
+            self.last_info = Some(format!("polyglot book move {:?}", m));
+            return Some(m);
+        }
+
+        let mut moves = self.book.as_ref()?.moves(board);
+
+        if let Some(learning) = self.learning.as_ref() {
+            moves = learning.lock().unwrap_or_else(|e| e.into_inner()).adjust(board, moves);
+        }
+
+        let m = choose_move(moves, &self.book_options)?;
+        let key = board.polyglot_hash();
+
+        // searching the same position again does not count twice
+        if !self.book_line.iter().any(|&(line_key, _, _)| line_key == key) {
+            self.book_line.push((key, encode_move(&m), board.turn));
+        }
+
+        self.last_info = Some(format!("book move {:?}", m));
+        Some(m)
+    }
+
+    pub fn set_learning(&mut self, learning: Option<Arc<Mutex<BookLearning>>>) {
+        self.learning = learning;
+    }
+
+    pub fn set_record_learning(&mut self, enabled: bool) {
+        self.record_learning = enabled;
+    }
+
+    pub fn new_game(&mut self) {
+        self.book_line.clear();
+    }
+
+    // learns from the book moves the finished game played, returns how many were learned
+    pub fn learn(&mut self, game: &Game) -> std::io::Result<usize> {
+        let line = std::mem::take(&mut self.book_line);
+
+        let Some(learning) = self.learning.as_ref().filter(|_| self.record_learning) else {
+            return Ok(0);
+        };
+
+        // a book move the game took back or never reached is not the game's
+        let mut board = game.start_position();
+        let mut played = HashSet::new();
+
+        for m in game.mainline().unwrap_or_default() {
+            played.insert((board.polyglot_hash(), encode_move(&m)));
+            board.make_move(&m);
+        }
+
+        let line: Vec<(u64, u16, PieceColor)> = line.into_iter()
+            .filter(|&(key, m, _)| played.contains(&(key, m)))
+            .collect();
+
+        learning.lock().unwrap_or_else(|e| e.into_inner()).learn(&line, &game.result)
+    }
+
+    fn probe_tablebase(&mut self, board: &mut Board) -> Option<Move> {
+        let tablebase = self.tablebase.as_ref()?;
+        let probe = tablebase.probe_root(board)?;
+
+        self.last_info = Some(format!("tablebase move {:?} wdl {:?} dtz {} tbhits 1", probe.m, probe.wdl, probe.dtz));
+        Some(probe.m)
     }
 
+    // Polyglot and compiled books are recognised by their extension, returns the number of entries for them
     pub fn load_book(&mut self, path: &Path) -> std::io::Result<usize> {
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("bin")) {
+            let book = PolyglotBook::load(path)?;
+            let entries = book.len();
+
+            self.polyglot = Some(Arc::new(book));
+            return Ok(entries);
+        }
+
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(COMPILED_BOOK_EXTENSION)) {
+            let book = OpeningBook::load_compiled(path)?;
+            let positions = book.len();
+
+            self.book = Some(Arc::new(book));
+            return Ok(positions);
+        }
+
         let mut book = OpeningBook::new();
 
         let loaded_games = if path.is_dir() {
@@ -42,68 +324,235 @@ impl Engine {
             book.load_pgn_file(path)?
         };
         
-        self.book = Some(book);
+        self.book = Some(Arc::new(book));
         Ok(loaded_games)
     }
 
-    pub fn search(&mut self, board: &mut Board, depth: Option<u8>, time_limit: Option<u64>, move_history: &Vec<String>) -> Option<Move> {
-        if self.enable_book {
-            if let Some(book) = &self.book {
-                if let Some(book_move) = book.get_best_move(&move_history) {
-                    println!("book move found {book_move}");
-                    return book.to_move(&book_move, board);
-                }
-            }
+    pub fn search(&mut self, board: &mut Board, depth: Option<u8>, time_limit: Option<u64>) -> Option<Move> {
+        self.last_score = None;
+        self.last_info = None;
+
+        if let Some(m) = self.probe_book(board) {
+            return Some(m);
         }
 
+        if let Some(m) = self.probe_tablebase(board) {
+            return Some(m);
+        }
+
+        self.evaluator.prepare(board);
+
         match self.engine_type {
             EngineType::Minimax => {
                 let engine = self.minimax.as_mut().unwrap();
-                engine.search(board, depth.unwrap_or(7), f64::NEG_INFINITY, f64::INFINITY, true).moves.first().cloned()
+                let result = engine.search(board, depth.unwrap_or(7), f64::NEG_INFINITY, f64::INFINITY, board.turn == PieceColor::White);
+
+                self.last_score = Some(to_centipawns(result.value));
+                result.moves.first().cloned()
             },
             EngineType::MCTS => {
                 let engine = self.mcts.as_mut().unwrap();
-                engine.search(board, time_limit.unwrap_or(10000))
+                let m = engine.search(board, time_limit.unwrap_or(10000));
+
+                self.last_score = self.mcts_score(board);
+                m
             }
         }
     }
 
-    pub fn iterative_deepening(&mut self, board: &mut Board, depth: u8, time_limit: u64, move_history: &Vec<String>) -> Option<Move> {
-        if self.enable_book {
-            if let Some(book) = &self.book {
-                if let Some(book_move) = book.get_best_move(&move_history) {
-                    println!("book move found {book_move}");
-                    return book.to_move(&book_move, board);
-                }
-            }
+    pub fn iterative_deepening(&mut self, board: &mut Board, depth: u8, time_manager: TimeManager) -> Option<Move> {
+        self.last_score = None;
+        self.last_info = None;
+
+        if let Some(m) = self.probe_book(board) {
+            return Some(m);
         }
 
+        if let Some(m) = self.probe_tablebase(board) {
+            return Some(m);
+        }
+
+        self.evaluator.prepare(board);
+
+        if self.skill.enabled() {
+            return self.weakened_search(board, depth, time_manager);
+        }
+
+        self.search_root(board, depth, time_manager)
+    }
+
+    fn search_root(&mut self, board: &mut Board, depth: u8, time_manager: TimeManager) -> Option<Move> {
         match self.engine_type {
             EngineType::Minimax => {
                 let engine = self.minimax.as_mut().unwrap();
-                engine.iterative_deepening(board, depth, time_limit).moves.first().cloned()
+                let result = engine.iterative_deepening_timed(board, depth, time_manager);
+
+                self.last_score = Some(to_centipawns(result.value));
+                result.moves.first().cloned()
             },
             EngineType::MCTS => {
                 let engine = self.mcts.as_mut().unwrap();
-                engine.iterative_deepening(board, depth as u32, time_limit)
+                let m = engine.iterative_deepening(board, depth as u32, time_manager.optimum());
+
+                self.last_score = self.mcts_score(board);
+                m
             }
         }
     }
 
-    pub fn stop(&mut self) {
-        match self.engine_type {
-            EngineType::Minimax => {
-                let engine = self.minimax.as_mut().unwrap();
-                engine.stop();
-            },
-            EngineType::MCTS => {
-                let engine = self.mcts.as_mut().unwrap();
-                engine.stop();
+    // a shallower and shorter search, then a pick among the best lines
+    fn weakened_search(&mut self, board: &mut Board, depth: u8, time_manager: TimeManager) -> Option<Move> {
+        let skill = self.skill;
+        let limits = TimeManager::fixed(time_manager.optimum().min(skill.move_time()));
+        let depth = depth.min(skill.depth());
+
+        let (mut lines, depth, nodes) = self.lines(board, depth, &limits, skill.nodes());
+
+        // not a single line in the time of the level, a slow machine still gets a move
+        if lines.is_empty() {
+            return self.search_root(board, 1, time_manager);
+        }
+
+        lines.truncate(skill.lines());
+
+        let scores: Vec<i32> = lines.iter().map(|line| line.score).collect();
+        let Line { m, score, .. } = lines.swap_remove(skill.pick(&scores, &mut rand::rng()));
+
+        self.last_score = Some(if board.turn == PieceColor::White { score } else { -score });
+
+        if let Some(sender) = self.info.as_ref() {
+            let _ = sender.send(SearchInfo { depth: depth as u32, score, nodes, time: std::time::Duration::from_millis(limits.elapsed()), pv: vec![m.clone()] });
+        }
+
+        Some(m)
+    }
+
+    // every root move scored so the scores compare, best first, with the depth and the nodes it took.
+    // Once the lines have taken max_nodes they are not searched deeper, minimax only
+    pub fn lines(&mut self, board: &mut Board, depth: u8, limits: &TimeManager, max_nodes: u64) -> (Vec<Line>, u8, u64) {
+        self.evaluator.prepare(board);
+
+        let (mut lines, depth, nodes) = match self.engine_type {
+            EngineType::Minimax => self.minimax_lines(board, depth, limits, max_nodes),
+            EngineType::MCTS => self.mcts_lines(board, depth, limits)
+        };
+
+        lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+        (lines, depth, nodes)
+    }
+
+    // every root move is searched to the same depth so the scores compare, one ply deeper each
+    // round while time and nodes last. A round counts once it is finished, the first one even
+    // when it is not, so it may hold fewer moves. Returns the lines, the depth and the nodes
+    fn minimax_lines(&mut self, board: &mut Board, depth: u8, limits: &TimeManager, max_nodes: u64) -> (Vec<Line>, u8, u64) {
+        let turn = board.turn;
+        let to_move = |score: i32| if turn == PieceColor::White { score } else { -score };
+
+        // captures first, the first round may not get through all the moves
+        let legal_moves = board.get_total_legal_moves(None);
+        let mut moves = self.minimax.as_mut().unwrap().sort(legal_moves, board, 0);
+        let mut lines = Vec::new();
+        let mut reached = 0;
+        let mut nodes = 0;
+
+        // the progress of these searches is not the engine's answer, only their lines are kept
+        let info = self.info.clone();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        self.set_info_sender(Some(sender));
+
+        'rounds: for round in 1..=depth {
+            let mut scored = Vec::new();
+
+            for m in &moves {
+                let remaining = limits.maximum().saturating_sub(limits.elapsed());
+
+                let history = board.make_move(m);
+                let replies = board.get_total_legal_moves(None).len();
+                let over = board.get_result() != ResultType::None;
+
+                if remaining > 0 {
+                    self.search_root(board, round, TimeManager::fixed(remaining));
+                }
+
+                let mut pv = vec![m.clone()];
+
+                while let Ok(reply) = receiver.try_recv() {
+                    pv.truncate(1);
+                    pv.extend(reply.pv);
+                }
+
+                board.unmake_move(m, &history);
+
+                let minimax = self.minimax.as_ref().unwrap();
+                nodes += minimax.nodes;
+
+                // a search cut short ends the rounds, a finished game or a forced reply needs no depth
+                let completed = remaining > 0 && (over || replies <= 1 || minimax.depth >= round);
+
+                match self.last_score.filter(|_| completed) {
+                    Some(score) => scored.push(Line { m: m.clone(), score: to_move(score), pv }),
+                    None => {
+                        if lines.is_empty() {
+                            lines = scored;
+                            reached = round;
+                        }
+
+                        break 'rounds;
+                    }
+                }
+            }
+
+            scored.sort_by_key(|line| std::cmp::Reverse(line.score));
+            moves = scored.iter().map(|line| line.m.clone()).collect();
+            lines = scored;
+            reached = round;
+
+            if nodes >= max_nodes {
+                break;
             }
         }
+
+        self.set_info_sender(info);
+        (lines, reached + 1, nodes)
+    }
+
+    // the tree scores every root move it visited
+    fn mcts_lines(&mut self, board: &mut Board, depth: u8, limits: &TimeManager) -> (Vec<Line>, u8, u64) {
+        let Some(best) = self.search_root(board, depth, limits.clone()) else {
+            return (Vec::new(), 0, 0);
+        };
+
+        let stats = self.mcts_stats();
+        let nodes = stats.as_ref().map_or(0, |stats| stats.iterations as u64);
+
+        let mut lines: Vec<Line> = stats.map(|stats| stats.root_moves).unwrap_or_default().into_iter()
+            .filter_map(|root| Some(Line { m: root.m.clone(), score: root.centipawns()?, pv: vec![root.m.clone()] }))
+            .collect();
+
+        if !lines.iter().any(|line| line.m == best) {
+            let score = self.last_score.unwrap_or(0);
+            lines.push(Line { m: best.clone(), score: if board.turn == PieceColor::White { score } else { -score }, pv: vec![best] });
+        }
+
+        (lines, depth, nodes)
+    }
+
+    // the tree scores for the side to move
+    fn mcts_score(&self, board: &Board) -> Option<i32> {
+        let score = self.mcts_stats()?.centipawns();
+
+        Some(if board.turn == PieceColor::White { score } else { -score })
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
     }
 
     pub fn set_book_enabled(&mut self, enabled: bool) {
         self.enable_book = enabled;
     }
+
+    pub fn set_book_options(&mut self, options: BookOptions) {
+        self.book_options = options;
+    }
 }
\ No newline at end of file
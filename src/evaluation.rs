@@ -1,6 +1,6 @@
-use std::usize;
+use std::cell::RefCell;
 
-use crate::{board::{Board, ResultType}, r#const::*, piece::{PartialPiece, PieceColor, PieceType}, pieces::{bitboard::{A_FILE_INV, H_FILE_INV}, queen::get_controlled_squares_queen}};
+use crate::{board::{Board, ResultType}, endgame::{probe as probe_endgame, Endgame}, moves::Position, pawn_structure::{adjacent_files_mask, file_mask, forward_ranks_mask, front_span, pawn_attacks, relative_rank, PawnEntry, PawnTable}, r#const::*, piece::{PartialPiece, PieceColor, PieceType}, pieces::{bitboard::{A_FILE_INV, COLOR_MASK, H_FILE_INV, RANK_1, RANK_2, RANK_7, RANK_8}, queen::get_controlled_squares_queen}};
 
 #[derive(Debug, Clone, Copy)]
 pub struct EvaluationResult {
@@ -16,6 +16,13 @@ impl EvaluationResult {
         }
     }
 
+    pub fn scale(&self, factor: f64) -> Self {
+        EvaluationResult {
+            white: self.white * factor,
+            black: self.black * factor
+        }
+    }
+
     pub fn to_value(&self) -> f64 {
         self.white - self.black
     }
@@ -28,24 +35,89 @@ impl EvaluationResult {
     }
 }
 
-pub fn evaluate(board: &mut Board) -> EvaluationResult {
-    let checkmate = board.get_result();
-    match checkmate {
-        ResultType::WhiteCheckmate => return EvaluationResult {
+#[derive(Debug, Clone)]
+pub struct EvaluationBreakdown {
+    pub terms: Vec<(&'static str, EvaluationResult)>
+}
+
+impl EvaluationBreakdown {
+    pub fn total(&self) -> EvaluationResult {
+        self.terms.iter()
+            .fold(EvaluationResult::default(), |acc, (_, term)| acc.combine(*term))
+    }
+
+    pub fn get(&self, name: &str) -> Option<EvaluationResult> {
+        self.terms.iter()
+            .find(|(term_name, _)| *term_name == name)
+            .map(|(_, term)| *term)
+    }
+}
+
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: &mut Board) -> EvaluationResult;
+
+    // sets up any incremental state on the root position before a search
+    fn prepare(&self, _board: &mut Board) {}
+}
+
+pub struct HandcraftedEvaluator;
+
+impl Evaluator for HandcraftedEvaluator {
+    fn evaluate(&self, board: &mut Board) -> EvaluationResult {
+        evaluate(board)
+    }
+}
+
+pub fn evaluate_terminal(board: &mut Board) -> Option<EvaluationResult> {
+    match board.get_result() {
+        ResultType::WhiteCheckmate => Some(EvaluationResult {
             white: 10000000000.0,
             black: 0.0
-        },
-        ResultType::BlackCheckmate => return EvaluationResult {
+        }),
+        ResultType::BlackCheckmate => Some(EvaluationResult {
             white: 0.0,
             black: 10000000000.0
-        },
-        ResultType::Draw | ResultType::Stalemate => return EvaluationResult {
+        }),
+        ResultType::Draw | ResultType::Stalemate => Some(EvaluationResult {
             white: 0.0,
             black: 0.0
-        },
-        _ => ()
+        }),
+        _ => None
+    }
+}
+
+pub fn evaluate(board: &mut Board) -> EvaluationResult {
+    if let Some(result) = evaluate_terminal(board) {
+        return result;
+    }
+
+    match probe_endgame(board) {
+        Some(Endgame::Value(value)) => value,
+        Some(Endgame::Scale(factor)) => evaluate_breakdown(board).total().scale(factor),
+        None => evaluate_breakdown(board).total()
     }
+}
+
+pub fn evaluate_breakdown(board: &mut Board) -> EvaluationBreakdown {
+    EvaluationBreakdown {
+        terms: vec![
+            ("material", evaluate_material(board)),
+            ("pawns", evaluate_pawns(board)),
+            ("mobility", evaluate_mobility(board)),
+            ("piece_safety", evaluate_piece_safety(board)),
+            ("positions", evaluate_positions(board)),
+            ("king_safety", evaluate_kings_safety(board)),
+            ("bishop_pair", evaluate_bishop_pair(board)),
+            ("rook_files", evaluate_rook_files(board)),
+            ("rook_seventh", evaluate_rook_seventh(board)),
+            ("outposts", evaluate_outposts(board)),
+            ("trapped_pieces", evaluate_trapped_pieces(board)),
+            ("queen_development", evaluate_queen_development(board))
+        ]
+    }
+}
 
+pub fn evaluate_material(board: &Board) -> EvaluationResult {
     let mut value = EvaluationResult::default();
 
     for piece in board.pieces.values() {
@@ -56,61 +128,90 @@ pub fn evaluate(board: &mut Board) -> EvaluationResult {
         }
     }
 
-    let pawns = evaluate_pawns(board);
-    let mobility = evaluate_mobility(board);
-    let piece_safety = evaluate_piece_safety(board);
-    let positions = evaluate_positions(board);
-    let king_safety = evaluate_kings_safety(board);
-
-    value.combine(pawns)
-         .combine(mobility)
-         .combine(piece_safety)
-         .combine(positions)
-         .combine(king_safety)
+    value
+}
+
+thread_local! {
+    static PAWN_HASH_TABLE: RefCell<PawnTable> = RefCell::new(PawnTable::new(PAWN_TABLE_SIZE_MB));
+}
+
+pub fn probe_pawn_structure(board: &Board) -> PawnEntry {
+    PAWN_HASH_TABLE.with(|table| table.borrow_mut().probe(board.pawn_hash, board.bb.white_pawns, board.bb.black_pawns))
 }
 
 pub fn evaluate_pawns(board: &mut Board) -> EvaluationResult {
-    let mut files_white: Vec<usize> = vec![0; 8];
-    let mut files_black: Vec<usize> = vec![0; 8];
+    let entry = probe_pawn_structure(board);
 
-    let mut values = EvaluationResult::default();
+    entry.score.combine(evaluate_passed_pawns(board, &entry))
+}
 
-    for pawn in board.pieces.values().filter(|p| p.piece_type == PieceType::Pawn) {
-        match pawn.color {
-            PieceColor::White => files_white[pawn.pos.x] += 1,
-            PieceColor::Black => files_black[pawn.pos.x] += 1
-        };
-    }
+pub fn evaluate_passed_pawns(board: &Board, entry: &PawnEntry) -> EvaluationResult {
+    let phase = board.calculate_phase();
 
-    for i in 0..8 {
-        let file_value = (-0.1 * f64::abs(i as f64 - 3.5).powf(1.75)) + 1.05;
+    EvaluationResult {
+        white: evaluate_passed_pawns_color(board, PieceColor::White, entry.white.passed, phase),
+        black: evaluate_passed_pawns_color(board, PieceColor::Black, entry.black.passed, phase)
+    }
+}
 
-        let file_white = files_white[i] as f64 * file_value;
+fn evaluate_passed_pawns_color(board: &Board, color: PieceColor, passed: u64, phase: f64) -> f64 {
+    let own_king = board.get_king_pos(color);
+    let enemy_king = board.get_king_pos(color.opposite());
 
-        let last_file_white = if i == 0 { 0 } else { files_white[i - 1] };
-        let next_file_white = if i == 7 { 0 } else { files_white[i + 1] };
+    let enemy_pieces = if color == PieceColor::White {
+        board.bb.black_knights | board.bb.black_bishops | board.bb.black_rooks | board.bb.black_queens
+    } else {
+        board.bb.white_knights | board.bb.white_bishops | board.bb.white_rooks | board.bb.white_queens
+    };
 
-        let mut penalty_white = 0.0;
+    let mut value = 0.0;
 
-        if last_file_white == 0 { penalty_white += PAWN_ISOLATION_PENALTY; }
-        if next_file_white == 0 { penalty_white += PAWN_ISOLATION_PENALTY; }
+    let mut rem = passed;
+    while rem != 0 {
+        let index = rem.trailing_zeros() as usize;
+        let pos = Position::from_bitboard(1u64 << index);
+        let rank = relative_rank(color, pos.y);
 
-        values.white += f64::min(file_white, (1.0 - penalty_white) * (1.0 / file_white));
+        // 0 on the starting rank, 1 on the seventh
+        let weight = (rank as f64 - 1.0) / 5.0;
 
-        let file_black = files_black[i] as f64 * file_value;
+        let mut bonus = PASSED_PAWN_BONUS[rank];
 
-        let last_file_black = if i == 0 { 0 } else { files_black[i - 1] };
-        let next_file_black = if i == 7 { 0 } else { files_black[i + 1] };
+        let free_path = front_span(color, pos) & board.bb.all_pieces == 0;
+        if free_path {
+            bonus += PASSED_PAWN_FREE_PATH_BONUS * weight;
+        }
 
-        let mut penalty_black = 0.0;
+        let stop = Position {
+            x: pos.x,
+            y: if color == PieceColor::White { pos.y - 1 } else { pos.y + 1 }
+        };
+        let proximity = (enemy_king.distance(stop) * 2) as f64 - own_king.distance(stop) as f64;
+        bonus += PASSED_PAWN_KING_PROXIMITY * proximity * weight * phase;
+
+        bonus *= 1.0 + phase * PASSED_PAWN_ENDGAME_FACTOR;
+
+        // rule of the square, only meaningful once the defender has no pieces left
+        if enemy_pieces == 0 && free_path {
+            let promotion = Position {
+                x: pos.x,
+                y: if color == PieceColor::White { 0 } else { 7 }
+            };
+            let pawn_distance = (7 - rank).min(5) as isize;
+            // a defender to move gets one step closer before the pawn does
+            let tempo = if board.turn == color { 0 } else { 1 };
+
+            if enemy_king.distance(promotion) as isize > pawn_distance + tempo {
+                bonus += UNSTOPPABLE_PASSER_BONUS;
+            }
+        }
 
-        if last_file_black == 0 { penalty_black += PAWN_ISOLATION_PENALTY; }
-        if next_file_black == 0 { penalty_black += PAWN_ISOLATION_PENALTY; }
+        value += bonus;
 
-        values.black += f64::min(file_black, (1.0 - penalty_black) * (1.0 / file_black));
+        rem &= rem - 1;
     }
 
-    values
+    value
 }
 
 pub fn evaluate_mobility(board: &mut Board) -> EvaluationResult {
@@ -334,4 +435,185 @@ pub fn evaluate_kings_safety(board: &Board) -> EvaluationResult {
         white, 
         black 
     }
+}
+
+pub fn evaluate_bishop_pair(board: &Board) -> EvaluationResult {
+    let bonus = |bishops: u64| {
+        if bishops & COLOR_MASK != 0 && bishops & !COLOR_MASK != 0 {
+            BISHOP_PAIR_BONUS
+        } else {
+            0.0
+        }
+    };
+
+    EvaluationResult {
+        white: bonus(board.bb.white_bishops),
+        black: bonus(board.bb.black_bishops)
+    }
+}
+
+fn evaluate_rook_files_color(rooks: u64, own_pawns: u64, enemy_pawns: u64) -> f64 {
+    let mut value = 0.0;
+
+    let mut rem = rooks;
+    while rem != 0 {
+        let index = rem.trailing_zeros() as usize;
+        let file = file_mask(index % 8);
+
+        if file & own_pawns == 0 {
+            value += if file & enemy_pawns == 0 {
+                ROOK_OPEN_FILE_BONUS
+            } else {
+                ROOK_SEMI_OPEN_FILE_BONUS
+            };
+        }
+
+        rem &= rem - 1;
+    }
+
+    value
+}
+
+pub fn evaluate_rook_files(board: &Board) -> EvaluationResult {
+    EvaluationResult {
+        white: evaluate_rook_files_color(board.bb.white_rooks, board.bb.white_pawns, board.bb.black_pawns),
+        black: evaluate_rook_files_color(board.bb.black_rooks, board.bb.black_pawns, board.bb.white_pawns)
+    }
+}
+
+pub fn evaluate_rook_seventh(board: &Board) -> EvaluationResult {
+    // the seventh rank only matters while it holds enemy pawns or cuts off the enemy king
+    let white = if (board.bb.black_pawns & RANK_7 != 0 || board.bb.black_king & RANK_8 != 0) && board.bb.white_rooks & RANK_7 != 0 {
+        (board.bb.white_rooks & RANK_7).count_ones() as f64 * ROOK_SEVENTH_RANK_BONUS
+    } else {
+        0.0
+    };
+
+    let black = if (board.bb.white_pawns & RANK_2 != 0 || board.bb.white_king & RANK_1 != 0) && board.bb.black_rooks & RANK_2 != 0 {
+        (board.bb.black_rooks & RANK_2).count_ones() as f64 * ROOK_SEVENTH_RANK_BONUS
+    } else {
+        0.0
+    };
+
+    EvaluationResult { white, black }
+}
+
+fn evaluate_outposts_color(color: PieceColor, pieces: u64, bonus: f64, own_pawns: u64, enemy_pawns: u64) -> f64 {
+    let mut value = 0.0;
+
+    let mut rem = pieces;
+    while rem != 0 {
+        let index = rem.trailing_zeros() as usize;
+        let square = 1u64 << index;
+        let pos = Position::from_bitboard(square);
+        let rank = relative_rank(color, pos.y);
+
+        let protected = own_pawns & pawn_attacks(color.opposite(), square) != 0;
+        let attackable = enemy_pawns & adjacent_files_mask(pos.x) & forward_ranks_mask(color, pos.y) != 0;
+
+        if (3..=5).contains(&rank) && protected && !attackable {
+            value += bonus;
+        }
+
+        rem &= rem - 1;
+    }
+
+    value
+}
+
+pub fn evaluate_outposts(board: &Board) -> EvaluationResult {
+    let bb = &board.bb;
+
+    EvaluationResult {
+        white: evaluate_outposts_color(PieceColor::White, bb.white_knights, KNIGHT_OUTPOST_BONUS, bb.white_pawns, bb.black_pawns) +
+               evaluate_outposts_color(PieceColor::White, bb.white_bishops, BISHOP_OUTPOST_BONUS, bb.white_pawns, bb.black_pawns),
+        black: evaluate_outposts_color(PieceColor::Black, bb.black_knights, KNIGHT_OUTPOST_BONUS, bb.black_pawns, bb.white_pawns) +
+               evaluate_outposts_color(PieceColor::Black, bb.black_bishops, BISHOP_OUTPOST_BONUS, bb.black_pawns, bb.white_pawns)
+    }
+}
+
+fn square(notation: &str) -> u64 {
+    let bytes = notation.as_bytes();
+    let x = (bytes[0] - b'a') as usize;
+    let y = 8 - (bytes[1] - b'0') as usize;
+
+    Position { x, y }.to_bitboard()
+}
+
+fn evaluate_trapped_pieces_color(board: &Board, color: PieceColor) -> f64 {
+    let (bishops, rooks, king, enemy_pawns, back_rank, castling) = if color == PieceColor::White {
+        (board.bb.white_bishops, board.bb.white_rooks, board.bb.white_king, board.bb.black_pawns, RANK_1, board.castling.white)
+    } else {
+        (board.bb.black_bishops, board.bb.black_rooks, board.bb.black_king, board.bb.white_pawns, RANK_8, board.castling.black)
+    };
+
+    let mut penalty = 0.0;
+
+    // a bishop that took the rook pawn and got shut in by the knight pawn
+    let traps = if color == PieceColor::White {
+        [("a7", "b6"), ("h7", "g6"), ("a6", "b5"), ("h6", "g5")]
+    } else {
+        [("a2", "b3"), ("h2", "g3"), ("a3", "b4"), ("h3", "g4")]
+    };
+
+    for (bishop, pawn) in traps {
+        if bishops & square(bishop) != 0 && enemy_pawns & square(pawn) != 0 {
+            penalty += TRAPPED_BISHOP_PENALTY;
+        }
+    }
+
+    // a rook stuck in the corner behind a king that can no longer castle
+    if king & back_rank != 0 {
+        let king_file = king.trailing_zeros() as usize % 8;
+        let mut rem = rooks & back_rank;
+
+        while rem != 0 {
+            let rook_file = rem.trailing_zeros() as usize % 8;
+
+            let kingside = (5..=6).contains(&king_file) && rook_file > king_file && !castling.0;
+            let queenside = (1..=3).contains(&king_file) && rook_file < king_file && !castling.1;
+
+            if kingside || queenside {
+                penalty += TRAPPED_ROOK_PENALTY;
+            }
+
+            rem &= rem - 1;
+        }
+    }
+
+    -penalty
+}
+
+pub fn evaluate_trapped_pieces(board: &Board) -> EvaluationResult {
+    EvaluationResult {
+        white: evaluate_trapped_pieces_color(board, PieceColor::White),
+        black: evaluate_trapped_pieces_color(board, PieceColor::Black)
+    }
+}
+
+fn evaluate_queen_development_color(queens: u64, minors: u64, queen_start: u64, minor_start: u64) -> f64 {
+    if queens == 0 || queens & queen_start != 0 {
+        return 0.0;
+    }
+
+    -((minors & minor_start).count_ones() as f64 * QUEEN_EARLY_DEVELOPMENT_PENALTY)
+}
+
+pub fn evaluate_queen_development(board: &Board) -> EvaluationResult {
+    let bb = &board.bb;
+
+    EvaluationResult {
+        white: evaluate_queen_development_color(
+            bb.white_queens,
+            bb.white_knights | bb.white_bishops,
+            square("d1"),
+            square("b1") | square("c1") | square("f1") | square("g1")
+        ),
+        black: evaluate_queen_development_color(
+            bb.black_queens,
+            bb.black_knights | bb.black_bishops,
+            square("d8"),
+            square("b8") | square("c8") | square("f8") | square("g8")
+        )
+    }
 }
\ No newline at end of file
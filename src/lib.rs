@@ -3,10 +3,21 @@ pub mod moves;
 pub mod piece;
 pub mod pieces;
 pub mod evaluation;
+pub mod pawn_structure;
+pub mod endgame;
+pub mod syzygy;
+pub mod nnue;
 pub mod r#const;
 pub mod search;
+pub mod time_manager;
 pub mod protocol;
 pub mod mcts;
 pub mod engine;
 pub mod book;
+pub mod polyglot;
+pub mod pgn;
+pub mod api;
+pub mod session;
+pub mod auth;
+pub mod skill;
 pub mod server;
\ No newline at end of file
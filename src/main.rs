@@ -1,13 +1,83 @@
-use std::io;
+use std::{env, io, path::Path, sync::{Arc, Mutex}};
 
-use mchess::server;
+use mchess::{board::Board, book::{BookLearning, BookOptions, BookSelection, OpeningBook}, engine::{Engine, EngineType}, pgn::Game, r#const::{MAX_PLIES, SELF_PLAY_MAX_PLIES}, server, time_manager::TimeManager};
+
+const USAGE: &str = "usage: mchess [book build <output.mcb> <pgn file or directory>... | book selfplay <book> <learning file> <games> [movetime]]";
+
+// compiles PGN files and directories into one book file for the server to map
+fn build_book(output: &Path, inputs: &[String]) -> io::Result<()> {
+    let mut book = OpeningBook::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        if path.is_dir() {
+            book.load_book_directory(path)?;
+        } else {
+            let games = book.load_pgn_file(path)?;
+            println!("Loaded {} games from {:?}", games, path);
+        }
+    }
+
+    let entries = book.save_compiled(output)?;
+    println!("Wrote {} positions and {} moves to {:?}", book.len(), entries, output);
+
+    Ok(())
+}
+
+// plays the engine against itself from the book so the learning file sees results
+fn self_play(book: &Path, learning: &Path, games: usize, move_time: u64) -> io::Result<()> {
+    let mut engine = Engine::new(EngineType::Minimax, true);
+    engine.load_book(book)?;
+    engine.set_learning(Some(Arc::new(Mutex::new(BookLearning::open(learning)?))));
+    // varied openings, every line gets tried
+    engine.set_book_options(BookOptions { selection: BookSelection::Weighted, ..BookOptions::default() });
+
+    for index in 0..games {
+        let mut board = Board::startpos();
+        let mut game = Game::new(None);
+        engine.new_game();
+
+        while game.result == "*" && game.moves.len() < SELF_PLAY_MAX_PLIES {
+            let Some(m) = engine.iterative_deepening(&mut board, MAX_PLIES, TimeManager::from_budget(move_time)) else {
+                break;
+            };
+
+            game.play(&mut board, &m, None);
+        }
+
+        let learned = engine.learn(&game)?;
+        println!("Game {}: {} after {} plies, learned {} book moves", index + 1, game.result, game.moves.len(), learned);
+    }
+
+    Ok(())
+}
 
 #[tokio::main]
 async fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.iter().map(|arg| arg.as_str()).collect::<Vec<_>>().as_slice() {
+        [] => {},
+        ["book", "build", output, inputs @ ..] if !inputs.is_empty() => {
+            return build_book(Path::new(output), &args[3..]);
+        },
+        ["book", "selfplay", book, learning, games, rest @ ..] if rest.len() <= 1 => {
+            let games = games.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid number of games"))?;
+            let move_time = rest.first().map_or(Ok(100), |time| time.parse()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid move time"))?;
+
+            return self_play(Path::new(book), Path::new(learning), games, move_time);
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid arguments"));
+        }
+    }
+
     if let Err(e) = server::run_server().await {
         eprintln!("Server error: {}", e);
         return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
     }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,44 +1,230 @@
-use std::time::{Duration, Instant};
+use std::{sync::{atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering}, Arc, OnceLock}, thread, time::{Duration, Instant}};
 use rand::Rng;
 
-use crate::{board::{Board, ResultType}, r#const::MCTS_MAX_PLIES, evaluation::evaluate, moves::{Move, MoveType}, piece::PieceColor, search::Minimax};
+use crate::{board::{Board, ResultType}, r#const::{MCTS_CPUCT, MCTS_EVAL_PRIOR_TEMPERATURE, MCTS_FPU_REDUCTION, MCTS_LEAF_HASH_SIZE, MCTS_MAX_NODES, MCTS_MAX_PLIES, MCTS_PRIOR_TEMPERATURE, MCTS_PROBE_DEPTH, MCTS_QUIESCENCE_DEPTH, MCTS_REUSE_DEPTH, MCTS_WIN_PROBABILITY_SCALE}, evaluation::{Evaluator, HandcraftedEvaluator}, moves::{Move, MoveType}, piece::PieceColor, search::{Minimax, SearchInfo}};
+use tokio::sync::mpsc::UnboundedSender;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    Ucb1,
+    Puct
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Prior {
+    MoveOrdering,
+    Evaluation
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Leaf {
+    Rollout,
+    Quiescence,
+    Minimax
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MctsOptions {
+    pub max_nodes: usize,
+    pub selection: Selection,
+    pub prior: Prior,
+    pub leaf: Leaf
+}
+
+impl Default for MctsOptions {
+    fn default() -> Self {
+        MctsOptions {
+            max_nodes: MCTS_MAX_NODES,
+            selection: Selection::Ucb1,
+            prior: Prior::MoveOrdering,
+            leaf: Leaf::Quiescence
+        }
+    }
+}
+
+// score is from the point of view of the side that played `m`, the counters are
+// shared between the search threads and children are only ever added once
 #[derive(Debug)]
 struct Node {
     pub m: Option<Move>,
-    pub visits: u32,
-    pub score: f64,
-    pub children: Vec<Node>,
-    pub expanded: bool
+    pub prior: f64,
+    pub visits: AtomicU32,
+    pub virtual_loss: AtomicU32,
+    pub score: AtomicU64,
+    pub children: OnceLock<Vec<Node>>
 }
 
 impl Node {
-    fn new(m: Option<Move>) -> Self {
+    fn new(m: Option<Move>, prior: f64) -> Self {
         Node {
             m,
-            visits: 0,
-            score: 0.0,
-            children: Vec::new(),
-            expanded: false
+            prior,
+            visits: AtomicU32::new(0),
+            virtual_loss: AtomicU32::new(0),
+            score: AtomicU64::new(0f64.to_bits()),
+            children: OnceLock::new()
         }
     }
 
+    fn visits(&self) -> u32 {
+        self.visits.load(Ordering::Relaxed)
+    }
+
+    fn score(&self) -> f64 {
+        f64::from_bits(self.score.load(Ordering::Relaxed))
+    }
+
+    fn children(&self) -> &[Node] {
+        self.children.get().map_or(&[], |children| children.as_slice())
+    }
+
+    // visits still running in the tree count as losses
+    fn effective_visits(&self) -> u32 {
+        self.visits() + self.virtual_loss.load(Ordering::Relaxed)
+    }
+
+    fn add_virtual_loss(&self) {
+        self.virtual_loss.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn update(&self, value: f64) {
+        self.score.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| Some((f64::from_bits(bits) + value).to_bits())).ok();
+        self.visits.fetch_add(1, Ordering::Relaxed);
+        self.virtual_loss.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    fn size(&self) -> usize {
+        1 + self.children().iter().map(|child| child.size()).sum::<usize>()
+    }
+
+    // the subtree for the position with `hash`, looking at most `depth` plies down from `board`
+    fn find(self, board: &mut Board, hash: i64, depth: u8) -> Option<Node> {
+        if board.hash == hash {
+            return Some(self);
+        }
+
+        if depth == 0 {
+            return None;
+        }
+
+        self.children.into_inner()?.into_iter().find_map(|child| {
+            let m = child.m.clone()?;
+
+            let history = board.make_move(&m);
+            let found = child.find(board, hash, depth - 1);
+            board.unmake_move(&m, &history);
+
+            found
+        })
+    }
+
     fn get_uct(&self, parent_visits: u32, exp: f64) -> f64 {
-        if self.visits == 0 { return f64::INFINITY; }
+        let visits = self.effective_visits();
+        if visits == 0 { return f64::INFINITY; }
+
+        let exploitation = self.score() / visits as f64;
+        let exploration = exp * ((parent_visits as f64).ln() / visits as f64).sqrt();
 
-        let exploitation = self.score / self.visits as f64;
-        let exploration = exp * ((parent_visits as f64).ln() / self.visits as f64).sqrt();
+        exploitation + exploration
+    }
+
+    // unvisited children get the first play urgency value instead of a mean
+    fn get_puct(&self, parent_visits: u32, fpu: f64, cpuct: f64) -> f64 {
+        let visits = self.effective_visits();
+        let exploitation = if visits == 0 { fpu } else { self.score() / visits as f64 };
+        let exploration = cpuct * self.prior * (parent_visits.max(1) as f64).sqrt() / (1 + visits) as f64;
 
         exploitation + exploration
     }
 }
 
+// logistic curve on the centipawn score, 400 centipawns is 10 to 1 odds
+pub fn win_probability(pawns: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-pawns * 100.0 / MCTS_WIN_PROBABILITY_SCALE))
+}
+
+// inverse of win_probability, clamped so certain results stay finite
+pub fn centipawns(probability: f64) -> i32 {
+    let probability = probability.clamp(1e-6, 1.0 - 1e-6);
+
+    (-MCTS_WIN_PROBABILITY_SCALE * (1.0 / probability - 1.0).log10()).round() as i32
+}
+
+#[derive(Debug, Clone)]
+pub struct RootMove {
+    pub m: Move,
+    pub visits: u32,
+    // win probability for the side to move, None until the move was visited
+    pub value: Option<f64>,
+    pub prior: f64
+}
+
+impl RootMove {
+    pub fn centipawns(&self) -> Option<i32> {
+        self.value.map(centipawns)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MctsStats {
+    pub iterations: usize,
+    pub nodes_visited: usize,
+    pub tree_size: usize,
+    pub time: Duration,
+    pub visits: u32,
+    pub value: f64,
+    // most visited first
+    pub root_moves: Vec<RootMove>,
+    pub pv: Vec<Move>
+}
+
+impl MctsStats {
+    pub fn centipawns(&self) -> i32 {
+        centipawns(self.value)
+    }
+
+    pub fn info_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.root_moves.iter()
+            .map(|root_move| match root_move.value {
+                Some(value) => format!("info string move {:?} visits {} value {:.3} cp {} prior {:.3}",
+                    root_move.m, root_move.visits, value, centipawns(value), root_move.prior),
+                None => format!("info string move {:?} visits 0 prior {:.3}", root_move.m, root_move.prior)
+            })
+            .collect();
+
+        let time = self.time.as_millis().max(1);
+        let pv: Vec<String> = self.pv.iter().map(|m| format!("{:?}", m)).collect();
+
+        lines.push(format!("info depth {} score cp {} nodes {} nps {} time {} pv {}",
+            self.pv.len(), self.centipawns(), self.iterations, self.iterations as u128 * 1000 / time, time, pv.join(" ")));
+
+        lines
+    }
+}
+
+fn softmax(values: &[f64], temperature: f64) -> Vec<f64> {
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = values.iter().map(|value| ((value - max) / temperature).exp()).collect();
+    let total: f64 = weights.iter().sum();
+
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
 pub struct Mcts {
     pub time_limit: u64,
     pub exp: f64,
     pub max_iterations: usize,
     pub nodes_visited: usize,
-    is_stopping: bool
+    pub iterations: usize,
+    pub search_time: Duration,
+    pub options: MctsOptions,
+    pub threads: usize,
+    root: Option<(Node, Board)>,
+    tree_size: AtomicUsize,
+    leaf_searches: Vec<Option<Minimax>>,
+    evaluator: Arc<dyn Evaluator>,
+    info: Option<UnboundedSender<SearchInfo>>,
+    stop: Arc<AtomicBool>
 }
 
 impl Mcts {
@@ -48,7 +234,130 @@ impl Mcts {
             exp: 1.414,
             max_iterations: 10000,
             nodes_visited: 0,
-            is_stopping: false
+            iterations: 0,
+            search_time: Duration::ZERO,
+            options: MctsOptions::default(),
+            threads: 1,
+            root: None,
+            tree_size: AtomicUsize::new(0),
+            leaf_searches: Vec::new(),
+            evaluator: Arc::new(HandcraftedEvaluator),
+            info: None,
+            stop: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    // receives a SearchInfo for every chunk of iterative_deepening
+    pub fn set_info_sender(&mut self, sender: Option<UnboundedSender<SearchInfo>>) {
+        self.info = sender;
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.leaf_searches.clear();
+        self.clear_tree();
+    }
+
+    pub fn set_max_nodes(&mut self, max_nodes: usize) {
+        self.options.max_nodes = max_nodes.max(1);
+    }
+
+    // the stored statistics depend on the priors and leaf values, so they are dropped when those change
+    pub fn set_options(&mut self, options: MctsOptions) {
+        if options.selection != self.options.selection || options.prior != self.options.prior || options.leaf != self.options.leaf {
+            self.clear_tree();
+        }
+
+        self.options = options;
+        self.set_max_nodes(options.max_nodes);
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn tree_size(&self) -> usize {
+        self.tree_size.load(Ordering::Relaxed)
+    }
+
+    pub fn root_visits(&self) -> u32 {
+        self.root.as_ref().map_or(0, |(root, _)| root.visits())
+    }
+
+    // follows the most visited children of the stored tree
+    pub fn principal_variation(&self) -> Vec<Move> {
+        let mut pv = Vec::new();
+        let mut node = self.root.as_ref().map(|(root, _)| root);
+
+        while let Some(best) = node.and_then(|node| node.children().iter().filter(|child| child.visits() > 0).max_by_key(|child| child.visits())) {
+            match &best.m {
+                Some(m) => pv.push(m.clone()),
+                None => break
+            }
+
+            node = Some(best);
+        }
+
+        pv
+    }
+
+    // statistics of the stored tree, the counters are from the last search call
+    pub fn stats(&self) -> Option<MctsStats> {
+        let (root, _) = self.root.as_ref()?;
+
+        let mut root_moves: Vec<RootMove> = root.children().iter()
+            .filter_map(|child| Some(RootMove {
+                m: child.m.clone()?,
+                visits: child.visits(),
+                value: (child.visits() > 0).then(|| child.score() / child.visits() as f64),
+                prior: child.prior
+            }))
+            .collect();
+
+        root_moves.sort_by_key(|root_move| std::cmp::Reverse(root_move.visits));
+
+        // the root stores the value for the side that moved into it
+        let value = match root.visits() {
+            0 => 0.5,
+            visits => 1.0 - root.score() / visits as f64
+        };
+
+        Some(MctsStats {
+            iterations: self.iterations,
+            nodes_visited: self.nodes_visited,
+            tree_size: self.tree_size(),
+            time: self.search_time,
+            visits: root.visits(),
+            value,
+            root_moves,
+            pv: self.principal_variation()
+        })
+    }
+
+    pub fn clear_tree(&mut self) {
+        self.root = None;
+        self.tree_size.store(0, Ordering::Relaxed);
+    }
+
+    // keeps the statistics of the previous search if the position is the same or was
+    // reached from it by our move and the opponent's reply
+    fn take_root(&mut self, board: &Board) -> Node {
+        let root = self.root.take()
+            .and_then(|(root, mut root_board)| root.find(&mut root_board, board.hash, MCTS_REUSE_DEPTH))
+            .filter(|root| root.size() <= self.options.max_nodes);
+
+        match root {
+            Some(mut root) => {
+                root.m = None;
+                self.tree_size.store(root.size(), Ordering::Relaxed);
+
+                println!("info string MCTS reusing tree with {} nodes and {} visits", self.tree_size(), root.visits());
+                root
+            },
+            None => {
+                self.tree_size.store(1, Ordering::Relaxed);
+                Node::new(None, 1.0)
+            }
         }
     }
 
@@ -58,96 +367,226 @@ impl Mcts {
         let start_time = Instant::now();
         let time_limit = Duration::from_millis(time_limit_ms);
 
-        let mut root = Node::new(None);
+        let root = self.take_root(board);
+
+        let threads = self.threads.max(1);
+        self.leaf_searches.resize_with(threads, || None);
+        let mut leaf_searches = std::mem::take(&mut self.leaf_searches);
+
+        // a single thread runs on the caller's thread and stays reproducible
+        let (iterations, nodes_visited) = if threads == 1 {
+            self.run(&root, board, &mut leaf_searches[0], start_time, time_limit)
+        } else {
+            let this = &*self;
+            let root = &root;
+
+            thread::scope(|scope| {
+                let handles: Vec<_> = leaf_searches.iter_mut()
+                    .map(|leaf_search| {
+                        let board = board.clone();
+                        scope.spawn(move || this.run(root, &board, leaf_search, start_time, time_limit))
+                    })
+                    .collect();
+
+                handles.into_iter()
+                    .map(|handle| handle.join().expect("MCTS thread panicked"))
+                    .fold((0, 0), |total, counts| (total.0 + counts.0, total.1 + counts.1))
+            })
+        };
+
+        self.leaf_searches = leaf_searches;
+        self.nodes_visited = nodes_visited;
+        self.iterations = iterations;
+        self.search_time = start_time.elapsed();
+
+        let best_move = root.children().iter()
+            .max_by_key(|child| child.visits())
+            .and_then(|child| child.m.clone());
+
+        println!("info string MCTS completed {} iterations on {} threads in {:?}", iterations, threads, self.search_time);
+        println!("info string Nodes visited: {} tree size: {}", self.nodes_visited, self.tree_size());
+
+        self.root = Some((root, board.clone()));
+
+        if let Some(stats) = self.stats() {
+            for line in stats.info_lines() {
+                println!("{}", line);
+            }
+        }
+
+        best_move
+    }
+
+    // returns the iterations and nodes visited by one search thread
+    fn run(&self, root: &Node, board: &Board, leaf_search: &mut Option<Minimax>, start_time: Instant, time_limit: Duration) -> (usize, usize) {
         let mut iterations = 0;
+        let mut nodes_visited = 0;
 
-        while start_time.elapsed() < time_limit && !self.is_stopping {
+        // always run one iteration so there is a move to return
+        while (iterations == 0 || start_time.elapsed() < time_limit) && !self.is_stopping() {
             let mut board_clone = board.clone();
-            let path = self.select_and_expand(&mut root, &mut board_clone);
-            let result = self.simulate(&mut board_clone);
-            self.backpropagate(&mut root, &path, result);
+            let path = self.select_and_expand(root, &mut board_clone, &mut nodes_visited);
+            let result = self.simulate(&mut board_clone, leaf_search);
+            self.backpropagate(&path, result);
 
             iterations += 1;
         }
 
-        let best_child = root.children.iter()
-            .max_by_key(|child| child.visits)
-            .expect("No moves found");
+        (iterations, nodes_visited)
+    }
+
+    // the first child with the highest value, so unvisited children are tried in move ordering
+    fn select_child(&self, node: &Node) -> usize {
+        let parent_visits = node.effective_visits();
+
+        // the parent stores the value for the side that moved into it
+        let fpu = match node.visits() {
+            0 => 0.5,
+            visits => 1.0 - node.score() / visits as f64 - MCTS_FPU_REDUCTION
+        };
+
+        let mut best_index = 0;
+        let mut best_value = f64::NEG_INFINITY;
+
+        for (index, child) in node.children().iter().enumerate() {
+            let value = match self.options.selection {
+                Selection::Ucb1 => child.get_uct(parent_visits, self.exp),
+                Selection::Puct => child.get_puct(parent_visits, fpu, MCTS_CPUCT)
+            };
+
+            if value > best_value {
+                best_index = index;
+                best_value = value;
+            }
+        }
 
-        println!("info string MCTS completed {} iterations in {:?}", iterations, start_time.elapsed());
-        println!("info string Nodes visited: {}", self.nodes_visited);
-        
-        best_child.m.clone()
+        best_index
     }
 
-    fn select_and_expand(&mut self, node: &mut Node, board: &mut Board) -> Vec<usize> {
-        let mut path = Vec::new();
-        let mut current_node = node;
+    fn select_and_expand<'a>(&self, root: &'a Node, board: &mut Board, nodes_visited: &mut usize) -> Vec<&'a Node> {
+        root.add_virtual_loss();
 
-        while !current_node.children.is_empty() && current_node.expanded && !self.is_stopping {
-            let parent_visits = current_node.visits;
-            let best_child_index = current_node.children.iter()
-                .enumerate()
-                .max_by(|(_, a), (_, b)| a.get_uct(parent_visits, self.exp).partial_cmp(&b.get_uct(parent_visits, self.exp)).unwrap_or(std::cmp::Ordering::Equal))
-                .map(|(index, _)| index)
-                .unwrap();
+        let mut path = vec![root];
+        let mut current_node = root;
 
-            path.push(best_child_index);
+        while !current_node.children().is_empty() && !self.is_stopping() {
+            let child = &current_node.children()[self.select_child(current_node)];
+            child.add_virtual_loss();
 
-            if let Some(m) = &current_node.children[best_child_index].m {
+            if let Some(m) = &child.m {
                 board.make_move(m);
             }
 
-            current_node = &mut current_node.children[best_child_index];
-            self.nodes_visited += 1;
+            path.push(child);
+            current_node = child;
+            *nodes_visited += 1;
         }
 
-        if board.get_result().is_end() {
-            return path;
+        if !board.get_result().is_end() {
+            self.expand(current_node, board);
         }
 
-        if !current_node.expanded {
-            let legal_moves = board.get_total_legal_moves(None);
+        path
+    }
+
+    // adds every child at once, the leaf itself is evaluated
+    fn expand(&self, node: &Node, board: &mut Board) {
+        if node.children.get().is_some() {
+            return;
+        }
 
-            let mut scores: Vec<(Move, f64)> = legal_moves.into_iter()
-                .map(|m| (m.clone(), Minimax::evaluate_move_base(&m, board)))
-                .collect();
+        // the tree is full, keep evaluating from the existing leaves
+        let legal_moves = board.get_total_legal_moves(None);
+        if self.tree_size() + legal_moves.len() > self.options.max_nodes {
+            return;
+        }
 
-            scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        node.children.get_or_init(|| {
+            let children = self.create_children(legal_moves, board);
+            self.tree_size.fetch_add(children.len(), Ordering::Relaxed);
 
-            if current_node.children.len() >= scores.len() {
-                current_node.expanded = true;
-                return path;
-            }
+            children
+        });
+    }
 
-            let tried_moves: Vec<Move> = current_node.children.iter()
-                .filter_map(|child| child.m.clone())
-                .collect();
+    fn create_children(&self, legal_moves: Vec<Move>, board: &mut Board) -> Vec<Node> {
+        let mut children = Vec::with_capacity(legal_moves.len());
+        let mut ordering = Vec::with_capacity(legal_moves.len());
+        let mut values = Vec::with_capacity(legal_moves.len());
 
-            for (m, _) in scores {
-                if !tried_moves.contains(&m) {
-                    let child = Node::new(Some(m.clone()));
+        for m in legal_moves {
+            let score = Minimax::evaluate_move_base(&m, board);
 
-                    board.make_move(&m);
-                    
-                    current_node.children.push(child);
-                    path.push(current_node.children.len() - 1);
+            values.push(match (self.options.selection, self.options.prior) {
+                (Selection::Ucb1, _) => 0.0,
+                (Selection::Puct, Prior::MoveOrdering) => score / MCTS_PRIOR_TEMPERATURE,
+                (Selection::Puct, Prior::Evaluation) => {
+                    let history = board.make_move(&m);
+                    let eval = self.evaluator.evaluate(board).to_value();
+                    board.unmake_move(&m, &history);
 
-                    self.nodes_visited += 1;
-                    break;
+                    let eval = if m.piece_color == PieceColor::White { eval } else { -eval };
+                    eval / MCTS_EVAL_PRIOR_TEMPERATURE
                 }
-            }
+            });
+
+            ordering.push(score);
+            children.push(Node::new(Some(m), 0.0));
         }
-        
-        path
+
+        for (child, prior) in children.iter_mut().zip(softmax(&values, 1.0)) {
+            child.prior = prior;
+        }
+
+        let mut ordered: Vec<(f64, Node)> = ordering.into_iter().zip(children).collect();
+        ordered.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        ordered.into_iter().map(|(_, child)| child).collect()
+    }
+
+    fn leaf_search<'a>(&self, leaf_search: &'a mut Option<Minimax>) -> &'a mut Minimax {
+        leaf_search.get_or_insert_with(|| {
+            let mut minimax = Minimax::with_hash_size(MCTS_LEAF_HASH_SIZE);
+            minimax.set_evaluator(self.evaluator.clone());
+            minimax
+        })
     }
 
-    fn simulate(&mut self, board: &mut Board) -> f64 {
+    // returns the win probability for the side to move at the leaf
+    fn simulate(&self, board: &mut Board, leaf_search: &mut Option<Minimax>) -> f64 {
         let turn = board.turn;
+
+        if self.options.leaf == Leaf::Rollout {
+            self.rollout(board);
+        }
+
+        let value = match board.get_result() {
+            ResultType::WhiteCheckmate => {
+                return if turn == PieceColor::White { 1.0 } else { 0.0 };
+            },
+            ResultType::BlackCheckmate => {
+                return if turn == PieceColor::Black { 1.0 } else { 0.0 };
+            },
+            ResultType::Draw | ResultType::Stalemate => return 0.5,
+            ResultType::None | ResultType::NotCached => {
+                let maximizer = board.turn == PieceColor::White;
+
+                match self.options.leaf {
+                    Leaf::Rollout => self.evaluator.evaluate(board).to_value(),
+                    Leaf::Quiescence => self.leaf_search(leaf_search).quiescence(board, f64::NEG_INFINITY, f64::INFINITY, maximizer, MCTS_QUIESCENCE_DEPTH),
+                    Leaf::Minimax => self.leaf_search(leaf_search).search(board, MCTS_PROBE_DEPTH, f64::NEG_INFINITY, f64::INFINITY, maximizer).value
+                }
+            }
+        };
+
+        win_probability(if turn == PieceColor::White { value } else { -value })
+    }
+
+    fn rollout(&self, board: &mut Board) {
         let mut rng = rand::rng();
         let mut plies = 0;
 
-        while !board.get_result().is_end() && plies < MCTS_MAX_PLIES && !self.is_stopping {
+        while !board.get_result().is_end() && plies < MCTS_MAX_PLIES && !self.is_stopping() {
             let legal_moves = board.get_total_legal_moves(None);
             if legal_moves.is_empty() {
                 break;
@@ -195,49 +634,30 @@ impl Mcts {
             board.make_move(&m);
             plies += 1;
         }
-
-        match board.get_result() {
-            ResultType::WhiteCheckmate => {
-                if turn == PieceColor::White { 1.0 } else { 0.0 }
-            },
-            ResultType::BlackCheckmate => {
-                if turn == PieceColor::Black { 1.0 } else { 0.0 }
-            },
-            ResultType::Draw | ResultType::Stalemate => 0.5,
-            ResultType::None | ResultType::NotCached => {
-                let eval = evaluate(board);
-                let score = match turn {
-                    PieceColor::White => eval.white - eval.black,
-                    PieceColor::Black => eval.black - eval.white
-                };
-
-                0.5 + (score.tanh() / 2.0)
-            }
-        }
     }
 
-    fn backpropagate(&mut self, root: &mut Node, path: &Vec<usize>, result: f64) {
-        root.visits += 1;
-        root.score += result;
+    // result is for the side to move at the leaf, the value flips at every ply on the way up
+    fn backpropagate(&self, path: &[&Node], result: f64) {
+        let mut value = 1.0 - result;
 
-        let mut current = root;
-        for &index in path {
-            current = &mut current.children[index];
-            current.visits += 1;
-            current.score += result;
+        for node in path.iter().rev() {
+            node.update(value);
+            value = 1.0 - value;
         }
     }
 
     pub fn iterative_deepening(&mut self, board: &mut Board, time_chunks: u32, max_time_ms: u64) -> Option<Move> {
-        let base_time = max_time_ms / time_chunks as u64;
+        let start_time = Instant::now();
+        let base_time = max_time_ms / time_chunks.max(1) as u64;
 
         let mut best_move = None;
         let mut total_time_used = 0;
+        let mut total_iterations = 0;
 
         for i in 1..=time_chunks {
             total_time_used += base_time;
 
-            if self.is_stopping {
+            if self.is_stopping() {
                 break;
             }
 
@@ -249,12 +669,24 @@ impl Mcts {
             println!("info string MCTS iteration {}/{}: time used {}ms, total {}ms", 
                 i, time_chunks, base_time, total_time_used);
 
-            if total_time_used > max_time_ms * 9/10 {
+            total_iterations += self.iterations;
+
+            if let (Some(sender), Some(stats)) = (self.info.as_ref(), self.stats()) {
+                let _ = sender.send(SearchInfo {
+                    depth: stats.pv.len() as u32,
+                    score: stats.centipawns(),
+                    nodes: total_iterations as u64,
+                    time: start_time.elapsed(),
+                    pv: stats.pv
+                });
+            }
+
+            if total_time_used > max_time_ms * 9/10 || start_time.elapsed().as_millis() as u64 >= max_time_ms {
                 break;
             }
         }
 
-        if self.is_stopping {
+        if self.is_stopping() {
             self.reset_stop();
         }
 
@@ -262,11 +694,19 @@ impl Mcts {
     }
 
     pub fn stop(&mut self) {
-        self.is_stopping = true;
+        self.stop.store(true, Ordering::Relaxed);
     }
 
     pub fn reset_stop(&mut self) {
-        self.is_stopping = false;
+        self.stop.store(false, Ordering::Relaxed);
+    }
+
+    pub fn set_stop_signal(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    fn is_stopping(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
     }
 }
 
@@ -69,6 +69,10 @@ impl Position {
     pub fn shift(&self, vec: Vector) -> Position {
         Position::from(self.x as isize + vec.x, self.y as isize + vec.y)
     }
+
+    pub fn distance(&self, other: Position) -> usize {
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+    }
 }
 
 impl fmt::Debug for Position {
@@ -257,9 +261,10 @@ impl Move {
                     board.pieces[&index].pos.y == from_rank
                 });
                 
-                if !need_rank {
+                // the file is preferred whenever it is enough
+                if !need_file {
                     san.push("abcdefgh".chars().nth(from_file).unwrap());
-                } else if !need_file {
+                } else if !need_rank {
                     san.push(char::from_digit(8 - from_rank as u32, 10).unwrap());
                 } else {
                     san.push("abcdefgh".chars().nth(from_file).unwrap());
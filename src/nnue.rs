@@ -0,0 +1,370 @@
+use std::{fs, io, path::Path, sync::Arc};
+
+use crate::{board::Board, endgame::{probe as probe_endgame, Endgame}, evaluation::{evaluate_terminal, EvaluationResult, Evaluator}, moves::Position, piece::{PieceColor, PieceType}};
+
+// (768 -> N)x2 -> 1 perspective network. Each side keeps its own accumulator of the
+// first layer, updated incrementally as pieces move, the side to move's half comes
+// first in the output layer.
+
+pub const NNUE_INPUTS: usize = 768;
+
+const NNUE_MAGIC: &[u8; 4] = b"MCNN";
+const NNUE_VERSION: u32 = 1;
+
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+const CHUNK: usize = 16;
+
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator {
+    pub white: Vec<i16>,
+    pub black: Vec<i16>
+}
+
+impl Accumulator {
+    pub fn get(&self, color: PieceColor) -> &Vec<i16> {
+        match color {
+            PieceColor::White => &self.white,
+            PieceColor::Black => &self.black
+        }
+    }
+}
+
+// one accumulator per ply, make_move pushes and unmake_move pops
+#[derive(Clone)]
+pub struct NnueState {
+    pub network: Arc<Network>,
+    stack: Vec<Accumulator>
+}
+
+pub type Feature = (PieceType, PieceColor, Position);
+
+pub fn feature_index(perspective: PieceColor, (piece_type, color, pos): Feature) -> usize {
+    let piece = match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5
+    };
+
+    // a1 = 0 for white, mirrored vertically for black
+    let (side, square) = match perspective {
+        PieceColor::White => (if color == PieceColor::White { 0 } else { 6 }, (7 - pos.y) * 8 + pos.x),
+        PieceColor::Black => (if color == PieceColor::Black { 0 } else { 6 }, pos.y * 8 + pos.x)
+    };
+
+    (side + piece) * 64 + square
+}
+
+fn read_i16s(bytes: &[u8], offset: &mut usize, count: usize) -> io::Result<Vec<i16>> {
+    let end = *offset + count * 2;
+    let slice = bytes.get(*offset..end).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Network file is truncated"))?;
+    *offset = end;
+
+    Ok(slice.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect())
+}
+
+fn read_u32(bytes: &[u8], offset: &mut usize) -> io::Result<u32> {
+    let slice = bytes.get(*offset..*offset + 4).ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "Network file is truncated"))?;
+    *offset += 4;
+
+    Ok(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+impl Network {
+    pub fn new(hidden: usize, feature_weights: Vec<i16>, feature_bias: Vec<i16>, output_weights: Vec<i16>, output_bias: i32) -> io::Result<Network> {
+        if hidden == 0 || !hidden.is_multiple_of(CHUNK) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Hidden layer size {} is not a multiple of {}", hidden, CHUNK)));
+        }
+
+        if feature_weights.len() != NNUE_INPUTS * hidden || feature_bias.len() != hidden || output_weights.len() != 2 * hidden {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Layer sizes do not match the hidden layer size"));
+        }
+
+        Ok(Network {
+            hidden,
+            feature_weights,
+            feature_bias,
+            output_weights,
+            output_bias
+        })
+    }
+
+    // small deterministic network, only useful for testing the inference path
+    pub fn random(hidden: usize, seed: u64) -> io::Result<Network> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let mut next = |range: i16| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            ((state % (2 * range as u64 + 1)) as i64 - range as i64) as i16
+        };
+
+        let feature_weights = (0..NNUE_INPUTS * hidden).map(|_| next(64)).collect();
+        let feature_bias = (0..hidden).map(|_| next(32)).collect();
+        let output_weights = (0..2 * hidden).map(|_| next(64)).collect();
+
+        Network::new(hidden, feature_weights, feature_bias, output_weights, next(1000) as i32)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Network> {
+        let bytes = fs::read(path)?;
+
+        if bytes.len() < 12 || &bytes[0..4] != NNUE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a network file"));
+        }
+
+        let mut offset = 4;
+        let version = read_u32(&bytes, &mut offset)?;
+        if version != NNUE_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unsupported network version {}", version)));
+        }
+
+        let hidden = read_u32(&bytes, &mut offset)? as usize;
+
+        let feature_weights = read_i16s(&bytes, &mut offset, NNUE_INPUTS * hidden)?;
+        let feature_bias = read_i16s(&bytes, &mut offset, hidden)?;
+        let output_weights = read_i16s(&bytes, &mut offset, 2 * hidden)?;
+        let output_bias = read_u32(&bytes, &mut offset)? as i32;
+
+        Network::new(hidden, feature_weights, feature_bias, output_weights, output_bias)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(16 + 2 * (self.feature_weights.len() + self.feature_bias.len() + self.output_weights.len()));
+
+        bytes.extend_from_slice(NNUE_MAGIC);
+        bytes.extend_from_slice(&NNUE_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+
+        for value in self.feature_weights.iter().chain(&self.feature_bias).chain(&self.output_weights) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+
+        fs::write(path, bytes)
+    }
+
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+
+    pub fn refresh(&self, board: &Board) -> Accumulator {
+        let mut accumulator = Accumulator {
+            white: self.feature_bias.clone(),
+            black: self.feature_bias.clone()
+        };
+
+        for piece in board.pieces.values() {
+            let feature = (piece.piece_type, piece.color, piece.pos);
+
+            simd::add(&mut accumulator.white, self.weights(feature_index(PieceColor::White, feature)));
+            simd::add(&mut accumulator.black, self.weights(feature_index(PieceColor::Black, feature)));
+        }
+
+        accumulator
+    }
+
+    pub fn update(&self, accumulator: &mut Accumulator, removed: &[Feature], added: &[Feature]) {
+        for &feature in removed {
+            simd::sub(&mut accumulator.white, self.weights(feature_index(PieceColor::White, feature)));
+            simd::sub(&mut accumulator.black, self.weights(feature_index(PieceColor::Black, feature)));
+        }
+
+        for &feature in added {
+            simd::add(&mut accumulator.white, self.weights(feature_index(PieceColor::White, feature)));
+            simd::add(&mut accumulator.black, self.weights(feature_index(PieceColor::Black, feature)));
+        }
+    }
+
+    // score in pawns from the point of view of `turn`
+    pub fn evaluate(&self, accumulator: &Accumulator, turn: PieceColor) -> f64 {
+        let us = accumulator.get(turn);
+        let them = accumulator.get(turn.opposite());
+
+        let output = simd::crelu_dot(us, &self.output_weights[..self.hidden]) as i64 +
+            simd::crelu_dot(them, &self.output_weights[self.hidden..]) as i64 +
+            self.output_bias as i64;
+
+        (output * SCALE as i64 / (QA * QB) as i64) as f64 / 100.0
+    }
+}
+
+impl NnueState {
+    pub fn new(network: Arc<Network>, board: &Board) -> Self {
+        let accumulator = network.refresh(board);
+
+        NnueState {
+            network,
+            stack: vec![accumulator]
+        }
+    }
+
+    pub fn current(&self) -> &Accumulator {
+        self.stack.last().unwrap()
+    }
+
+    pub fn push(&mut self, removed: &[Feature], added: &[Feature]) {
+        let mut accumulator = self.current().clone();
+        self.network.update(&mut accumulator, removed, added);
+
+        self.stack.push(accumulator);
+    }
+
+    // false once the state was attached below the position being restored
+    pub fn pop(&mut self) -> bool {
+        if self.stack.len() > 1 {
+            self.stack.pop();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn evaluate(&self, turn: PieceColor) -> f64 {
+        self.network.evaluate(self.current(), turn)
+    }
+}
+
+pub struct NnueEvaluator {
+    network: Arc<Network>
+}
+
+impl NnueEvaluator {
+    pub fn new(network: Arc<Network>) -> Self {
+        NnueEvaluator { network }
+    }
+
+    fn attach(&self, board: &mut Board) {
+        let attached = board.nnue.as_ref().is_some_and(|state| Arc::ptr_eq(&state.network, &self.network));
+
+        if !attached {
+            board.nnue = Some(NnueState::new(self.network.clone(), board));
+        }
+    }
+}
+
+impl Evaluator for NnueEvaluator {
+    fn evaluate(&self, board: &mut Board) -> EvaluationResult {
+        if let Some(result) = evaluate_terminal(board) {
+            return result;
+        }
+
+        if let Some(Endgame::Value(value)) = probe_endgame(board) {
+            return value;
+        }
+
+        self.attach(board);
+
+        let value = board.nnue.as_ref().unwrap().evaluate(board.turn);
+        let result = match board.turn {
+            PieceColor::White => EvaluationResult { white: value, black: 0.0 },
+            PieceColor::Black => EvaluationResult { white: 0.0, black: value }
+        };
+
+        match probe_endgame(board) {
+            Some(Endgame::Scale(factor)) => result.scale(factor),
+            _ => result
+        }
+    }
+
+    fn prepare(&self, board: &mut Board) {
+        self.attach(board);
+    }
+}
+
+mod simd {
+    use super::QA;
+
+    pub fn add(accumulator: &mut [i16], weights: &[i16]) {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::add(accumulator, weights) };
+        }
+
+        for (a, w) in accumulator.iter_mut().zip(weights) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    pub fn sub(accumulator: &mut [i16], weights: &[i16]) {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::sub(accumulator, weights) };
+        }
+
+        for (a, w) in accumulator.iter_mut().zip(weights) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+
+    pub fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") {
+            return unsafe { avx2::crelu_dot(values, weights) };
+        }
+
+        values.iter().zip(weights)
+            .fold(0i32, |acc, (&v, &w)| acc.wrapping_add((v as i32).clamp(0, QA) * w as i32))
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    mod avx2 {
+        use std::arch::x86_64::*;
+
+        use crate::nnue::{CHUNK, QA};
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn add(accumulator: &mut [i16], weights: &[i16]) {
+            for (a, w) in accumulator.chunks_exact_mut(CHUNK).zip(weights.chunks_exact(CHUNK)) {
+                let a_ptr = a.as_mut_ptr() as *mut __m256i;
+                let sum = _mm256_add_epi16(_mm256_loadu_si256(a_ptr), _mm256_loadu_si256(w.as_ptr() as *const __m256i));
+                _mm256_storeu_si256(a_ptr, sum);
+            }
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn sub(accumulator: &mut [i16], weights: &[i16]) {
+            for (a, w) in accumulator.chunks_exact_mut(CHUNK).zip(weights.chunks_exact(CHUNK)) {
+                let a_ptr = a.as_mut_ptr() as *mut __m256i;
+                let diff = _mm256_sub_epi16(_mm256_loadu_si256(a_ptr), _mm256_loadu_si256(w.as_ptr() as *const __m256i));
+                _mm256_storeu_si256(a_ptr, diff);
+            }
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn crelu_dot(values: &[i16], weights: &[i16]) -> i32 {
+            let zero = _mm256_setzero_si256();
+            let max = _mm256_set1_epi16(QA as i16);
+            let mut sum = _mm256_setzero_si256();
+
+            for (v, w) in values.chunks_exact(CHUNK).zip(weights.chunks_exact(CHUNK)) {
+                let clamped = _mm256_min_epi16(_mm256_max_epi16(_mm256_loadu_si256(v.as_ptr() as *const __m256i), zero), max);
+                let products = _mm256_madd_epi16(clamped, _mm256_loadu_si256(w.as_ptr() as *const __m256i));
+                sum = _mm256_add_epi32(sum, products);
+            }
+
+            let mut lanes = [0i32; 8];
+            _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, sum);
+
+            lanes.iter().fold(0i32, |acc, &lane| acc.wrapping_add(lane))
+        }
+    }
+}
\ No newline at end of file
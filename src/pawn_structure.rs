@@ -0,0 +1,215 @@
+use crate::{evaluation::EvaluationResult, moves::Position, piece::PieceColor, pieces::bitboard::{A_FILE, A_FILE_INV, H_FILE_INV}, r#const::{BACKWARD_PAWN_PENALTY, CANDIDATE_PASSER_BONUS, CONNECTED_PAWN_BONUS, DOUBLED_PAWN_PENALTY, PAWN_CHAIN_BONUS, PAWN_ISOLATION_PENALTY}};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PawnInfo {
+    pub passed: u64,
+    pub candidates: u64,
+    pub backward: u64,
+    pub doubled: u64,
+    pub connected: u64
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PawnEntry {
+    pub key: i64,
+    pub score: EvaluationResult,
+    pub white: PawnInfo,
+    pub black: PawnInfo
+}
+
+impl PawnEntry {
+    pub fn get_info(&self, color: PieceColor) -> &PawnInfo {
+        match color {
+            PieceColor::White => &self.white,
+            PieceColor::Black => &self.black
+        }
+    }
+}
+
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+    mask: usize
+}
+
+impl PawnTable {
+    pub fn new(size_mb: usize) -> Self {
+        let num_entries = (size_mb * 1024 * 1024) / std::mem::size_of::<PawnEntry>();
+        let size = num_entries.next_power_of_two();
+        PawnTable {
+            entries: vec![PawnEntry {
+                key: 0,
+                score: EvaluationResult::default(),
+                white: PawnInfo::default(),
+                black: PawnInfo::default()
+            }; size],
+            mask: size - 1
+        }
+    }
+
+    pub fn get(&self, hash: i64) -> Option<&PawnEntry> {
+        let entry = &self.entries[(hash as usize) & self.mask];
+        if entry.key == hash {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        let index = (entry.key as usize) & self.mask;
+        self.entries[index] = entry;
+    }
+
+    pub fn probe(&mut self, hash: i64, white_pawns: u64, black_pawns: u64) -> PawnEntry {
+        if let Some(entry) = self.get(hash) {
+            return *entry;
+        }
+
+        let entry = analyse_pawns(hash, white_pawns, black_pawns);
+        self.store(entry);
+
+        entry
+    }
+}
+
+pub fn file_mask(file: usize) -> u64 {
+    A_FILE << file
+}
+
+pub fn adjacent_files_mask(file: usize) -> u64 {
+    let left = if file > 0 { file_mask(file - 1) } else { 0 };
+    let right = if file < 7 { file_mask(file + 1) } else { 0 };
+
+    left | right
+}
+
+// every rank strictly in front of `y` from the point of view of `color`
+pub fn forward_ranks_mask(color: PieceColor, y: usize) -> u64 {
+    match color {
+        PieceColor::White => if y == 0 { 0 } else { u64::MAX >> (64 - 8 * y) },
+        PieceColor::Black => if y == 7 { 0 } else { u64::MAX << (8 * (y + 1)) }
+    }
+}
+
+pub fn front_span(color: PieceColor, pos: Position) -> u64 {
+    file_mask(pos.x) & forward_ranks_mask(color, pos.y)
+}
+
+pub fn pawn_attacks(color: PieceColor, pawns: u64) -> u64 {
+    match color {
+        PieceColor::White => ((pawns >> 7) & A_FILE_INV) | ((pawns >> 9) & H_FILE_INV),
+        PieceColor::Black => ((pawns << 9) & A_FILE_INV) | ((pawns << 7) & H_FILE_INV)
+    }
+}
+
+pub fn relative_rank(color: PieceColor, y: usize) -> usize {
+    match color {
+        PieceColor::White => 7 - y,
+        PieceColor::Black => y
+    }
+}
+
+fn evaluate_files(pawns: u64) -> f64 {
+    let mut value = 0.0;
+
+    for i in 0..8 {
+        let file_value = (-0.1 * f64::abs(i as f64 - 3.5).powf(1.75)) + 1.05;
+
+        let count = (pawns & file_mask(i)).count_ones();
+        let file = count as f64 * file_value;
+
+        let last_file = if i == 0 { 0 } else { (pawns & file_mask(i - 1)).count_ones() };
+        let next_file = if i == 7 { 0 } else { (pawns & file_mask(i + 1)).count_ones() };
+
+        let mut penalty = 0.0;
+
+        if last_file == 0 { penalty += PAWN_ISOLATION_PENALTY; }
+        if next_file == 0 { penalty += PAWN_ISOLATION_PENALTY; }
+
+        value += f64::min(file, (1.0 - penalty) * (1.0 / file));
+    }
+
+    value
+}
+
+fn analyse_color(color: PieceColor, own: u64, enemy: u64) -> (f64, PawnInfo) {
+    let mut info = PawnInfo::default();
+    let mut value = evaluate_files(own);
+
+    let enemy_attacks = pawn_attacks(color.opposite(), enemy);
+
+    let mut rem = own;
+    while rem != 0 {
+        let index = rem.trailing_zeros() as usize;
+        let square = 1u64 << index;
+        let pos = Position::from_bitboard(square);
+        let rank = relative_rank(color, pos.y);
+
+        let forward = forward_ranks_mask(color, pos.y);
+        let file = file_mask(pos.x);
+        let adjacent = adjacent_files_mask(pos.x);
+
+        let stop = match color {
+            PieceColor::White => square >> 8,
+            PieceColor::Black => square << 8
+        };
+
+        let doubled = own & file & forward != 0;
+        let passed = !doubled && enemy & (file | adjacent) & forward == 0;
+
+        let phalanx = own & (((square << 1) & A_FILE_INV) | ((square >> 1) & H_FILE_INV));
+        let supporters = own & pawn_attacks(color.opposite(), square);
+
+        if doubled {
+            info.doubled |= square;
+            value -= DOUBLED_PAWN_PENALTY;
+        }
+
+        if passed {
+            info.passed |= square;
+        }
+
+        if phalanx != 0 || supporters != 0 {
+            info.connected |= square;
+            value += CONNECTED_PAWN_BONUS[rank] + supporters.count_ones() as f64 * PAWN_CHAIN_BONUS;
+        }
+
+        if !passed {
+            let behind = own & adjacent & !forward;
+
+            if behind == 0 && stop & enemy_attacks != 0 {
+                info.backward |= square;
+                value -= BACKWARD_PAWN_PENALTY;
+            }
+
+            if !doubled && enemy & file & forward == 0 {
+                let sentries = (enemy & adjacent & forward).count_ones();
+                let helpers = behind.count_ones();
+
+                if helpers >= sentries {
+                    info.candidates |= square;
+                    value += CANDIDATE_PASSER_BONUS[rank];
+                }
+            }
+        }
+
+        rem &= rem - 1;
+    }
+
+    (value, info)
+}
+
+pub fn analyse_pawns(key: i64, white_pawns: u64, black_pawns: u64) -> PawnEntry {
+    let (white_value, white) = analyse_color(PieceColor::White, white_pawns, black_pawns);
+    let (black_value, black) = analyse_color(PieceColor::Black, black_pawns, white_pawns);
+
+    PawnEntry {
+        key,
+        score: EvaluationResult {
+            white: white_value,
+            black: black_value
+        },
+        white,
+        black
+    }
+}
\ No newline at end of file
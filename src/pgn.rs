@@ -0,0 +1,628 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::board::{Board, ResultType};
+use crate::moves::Move;
+use crate::piece::PieceColor;
+
+// PGN import following the export format of the standard: tag pairs, movetext with
+// move numbers, comments, NAGs, recursive variations and a game termination marker.
+// Lines and columns are counted from 1. Games are exported in the same format.
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl PgnError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        PgnError { line, column, message: message.into() }
+    }
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    pub san: String,
+    pub line: usize,
+    pub column: usize,
+    pub nags: Vec<u8>,
+    // comments that follow the move
+    pub comments: Vec<String>,
+    // alternatives to this move, each starting from the position before it
+    pub variations: Vec<Vec<PgnMove>>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    pub headers: Vec<(String, String)>,
+    // comments before the first move
+    pub comments: Vec<String>,
+    pub moves: Vec<PgnMove>,
+    pub result: String
+}
+
+impl Game {
+    // an empty game record dated today, set up from the position if one is given
+    pub fn new(fen: Option<&str>) -> Self {
+        let mut game = Game {
+            headers: Vec::new(),
+            comments: Vec::new(),
+            moves: Vec::new(),
+            result: "*".to_string()
+        };
+
+        for tag in SEVEN_TAG_ROSTER {
+            game.set_header(tag, "?");
+        }
+
+        game.set_header("Date", today());
+        game.set_header("Result", "*");
+
+        if let Some(fen) = fen {
+            game.set_header("SetUp", "1");
+            game.set_header("FEN", fen);
+        }
+
+        game
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: impl Into<String>) {
+        let value = value.into();
+
+        match self.headers.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, current)) => *current = value,
+            None => self.headers.push((name.to_string(), value))
+        }
+    }
+
+    // plays the move on the board and records it, the result follows the new position
+    pub fn play(&mut self, board: &mut Board, m: &Move, comment: Option<String>) {
+        let san = san(board, m);
+        board.make_move(m);
+        let result = game_result(board);
+
+        self.moves.push(PgnMove {
+            san,
+            line: 0,
+            column: 0,
+            nags: Vec::new(),
+            comments: comment.into_iter().collect(),
+            variations: Vec::new()
+        });
+
+        self.result = result.to_string();
+        self.set_header("Result", result);
+    }
+
+    pub fn start_position(&self) -> Board {
+        match self.header("FEN") {
+            Some(fen) => Board::from_fen(fen),
+            None => Board::startpos()
+        }
+    }
+
+    // the mainline as moves, checked against the legal moves of each position
+    pub fn mainline(&self) -> Result<Vec<Move>, PgnError> {
+        let mut board = self.start_position();
+        let mut moves = Vec::with_capacity(self.moves.len());
+
+        for pgn_move in &self.moves {
+            let m = pgn_move.to_move(&mut board)?;
+            board.make_move(&m);
+            moves.push(m);
+        }
+
+        Ok(moves)
+    }
+
+    // checks the mainline and every variation
+    pub fn validate(&self) -> Result<(), PgnError> {
+        validate_line(&mut self.start_position(), &self.moves)
+    }
+}
+
+impl PgnMove {
+    pub fn to_move(&self, board: &mut Board) -> Result<Move, PgnError> {
+        board.parse_san(&self.san).map_err(|e| PgnError::new(self.line, self.column, e.to_string()))
+    }
+}
+
+// the seven tag roster comes first, missing tags are exported as unknown
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for tag in SEVEN_TAG_ROSTER {
+            let value = match tag {
+                "Result" => &self.result,
+                "Date" => self.header(tag).unwrap_or("????.??.??"),
+                _ => self.header(tag).unwrap_or("?")
+            };
+
+            writeln!(f, "[{} \"{}\"]", tag, escape(value))?;
+        }
+
+        for (tag, value) in &self.headers {
+            if !SEVEN_TAG_ROSTER.contains(&tag.as_str()) {
+                writeln!(f, "[{} \"{}\"]", tag, escape(value))?;
+            }
+        }
+
+        let mut tokens: Vec<String> = self.comments.iter().map(|comment| format!("{{{}}}", comment.replace('}', ""))).collect();
+
+        let start = self.start_position();
+        let ply = 2 * (start.moves.max(1) as usize - 1) + if start.turn == PieceColor::Black { 1 } else { 0 };
+
+        write_line(&mut tokens, &self.moves, ply);
+        tokens.push(self.result.clone());
+
+        // comments may be broken over several lines as well
+        let mut line = String::new();
+        writeln!(f)?;
+
+        for word in tokens.iter().flat_map(|token| token.split_whitespace()) {
+            if !line.is_empty() && line.len() + 1 + word.len() > MAX_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.push_str(word);
+        }
+
+        writeln!(f, "{}", line)
+    }
+}
+
+// the result of the position as a game termination marker
+pub fn game_result(board: &mut Board) -> &'static str {
+    match board.get_result() {
+        ResultType::WhiteCheckmate => "1-0",
+        ResultType::BlackCheckmate => "0-1",
+        ResultType::Draw | ResultType::Stalemate => "1/2-1/2",
+        // stalemate is not reported by the board
+        _ if board.get_check(board.turn).checked == 0 && board.get_total_legal_moves(None).is_empty() => "1/2-1/2",
+        _ => "*"
+    }
+}
+
+// SAN with the check or mate suffix, the board is left as it was
+pub fn san(board: &mut Board, m: &Move) -> String {
+    let mut san = m.to_san(board);
+    let history = board.make_move(m);
+
+    match game_result(board) {
+        "1-0" | "0-1" => san.push('#'),
+        _ if board.get_check(board.turn).checked != 0 => san.push('+'),
+        _ => {}
+    }
+
+    board.unmake_move(m, &history);
+    san
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// ply counts from the start of the game, black moves only get a number where the line resumes
+fn write_line(tokens: &mut Vec<String>, moves: &[PgnMove], start: usize) {
+    let mut resumed = true;
+
+    for (i, pgn_move) in moves.iter().enumerate() {
+        let ply = start + i;
+
+        if ply.is_multiple_of(2) {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if resumed {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+
+        tokens.push(pgn_move.san.clone());
+        tokens.extend(pgn_move.nags.iter().map(|nag| format!("${}", nag)));
+        tokens.extend(pgn_move.comments.iter().map(|comment| format!("{{{}}}", comment.replace('}', ""))));
+
+        for variation in &pgn_move.variations {
+            let mut line = Vec::new();
+            write_line(&mut line, variation, ply);
+
+            if line.is_empty() {
+                continue;
+            }
+
+            line[0].insert(0, '(');
+            line.last_mut().unwrap().push(')');
+            tokens.append(&mut line);
+        }
+
+        resumed = !pgn_move.comments.is_empty() || !pgn_move.variations.is_empty();
+    }
+}
+
+// the current UTC date, days are converted to the civil calendar
+fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs() / 86400).unwrap_or(0) as i64 + 719468;
+
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+fn validate_line(board: &mut Board, moves: &[PgnMove]) -> Result<(), PgnError> {
+    for pgn_move in moves {
+        for variation in &pgn_move.variations {
+            validate_line(&mut board.clone(), variation)?;
+        }
+
+        let m = pgn_move.to_move(board)?;
+        board.make_move(&m);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    MoveNumber,
+    San(String),
+    Open,
+    Close,
+    Result(String)
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    line_start: bool
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Self {
+        Lexer { chars: text.chars().peekable(), line: 1, column: 1, line_start: true }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+            self.line_start = true;
+        } else {
+            self.column += 1;
+            self.line_start = false;
+        }
+
+        Some(c)
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut text = String::new();
+
+        while let Some(&c) = self.chars.peek() {
+            if !predicate(c) {
+                break;
+            }
+
+            text.push(c);
+            self.bump();
+        }
+
+        text
+    }
+
+    // skips to the end of the line, used for `;` comments and `%` escapes
+    fn rest_of_line(&mut self) -> String {
+        self.take_while(|c| c != '\n')
+    }
+
+    fn tag(&mut self, line: usize, column: usize) -> Result<Token, PgnError> {
+        self.take_while(char::is_whitespace);
+        let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+
+        if name.is_empty() {
+            return Err(PgnError::new(self.line, self.column, "expected a tag name"));
+        }
+
+        self.take_while(char::is_whitespace);
+        if self.bump() != Some('"') {
+            return Err(PgnError::new(self.line, self.column, format!("expected a quoted value for tag {}", name)));
+        }
+
+        let mut value = String::new();
+
+        loop {
+            match self.bump() {
+                Some('\\') => value.extend(self.bump()),
+                Some('"') => break,
+                Some('\n') | None => return Err(PgnError::new(line, column, format!("unterminated value for tag {}", name))),
+                Some(c) => value.push(c)
+            }
+        }
+
+        self.take_while(|c| c != ']' && c != '\n' && c.is_whitespace());
+        if self.bump() != Some(']') {
+            return Err(PgnError::new(self.line, self.column, format!("expected ] after tag {}", name)));
+        }
+
+        Ok(Token::Tag(name, value))
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        loop {
+            let line_start = self.line_start;
+            let Some(&c) = self.chars.peek() else {
+                return Ok(None);
+            };
+
+            let (line, column) = (self.line, self.column);
+
+            if c.is_whitespace() {
+                self.bump();
+                continue;
+            }
+
+            if c == '%' && line_start {
+                self.rest_of_line();
+                continue;
+            }
+
+            self.bump();
+
+            let token = match c {
+                '[' => self.tag(line, column)?,
+                '{' => {
+                    let comment = self.take_while(|c| c != '}');
+
+                    if self.bump().is_none() {
+                        return Err(PgnError::new(line, column, "unterminated comment"));
+                    }
+
+                    Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" "))
+                },
+                ';' => Token::Comment(self.rest_of_line().trim().to_string()),
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '*' => Token::Result("*".to_string()),
+                '$' => {
+                    let digits = self.take_while(|c| c.is_ascii_digit());
+                    let nag = digits.parse::<u8>().map_err(|_| PgnError::new(line, column, format!("invalid NAG ${}", digits)))?;
+
+                    Token::Nag(nag)
+                },
+                '!' | '?' => {
+                    let suffix = format!("{}{}", c, self.take_while(|c| c == '!' || c == '?'));
+                    Token::Nag(suffix_nag(&suffix).ok_or_else(|| PgnError::new(line, column, format!("invalid annotation {}", suffix)))?)
+                },
+                '.' => {
+                    self.take_while(|c| c == '.');
+                    Token::MoveNumber
+                },
+                // move numbers start with a digit, the only dots in a move are in `e.p.`
+                c if c.is_ascii_digit() => {
+                    let word = format!("{}{}", c, self.take_while(|c| c.is_ascii_alphanumeric() || "=+#-/".contains(c)));
+
+                    match word.as_str() {
+                        "1-0" | "0-1" | "1/2-1/2" => Token::Result(word),
+                        word if word.chars().all(|c| c.is_ascii_digit()) => {
+                            self.take_while(|c| c == '.');
+                            Token::MoveNumber
+                        },
+                        // castling written with zeros
+                        _ => Token::San(word)
+                    }
+                },
+                c if c.is_ascii_alphabetic() => Token::San(format!("{}{}", c, self.take_while(|c| c.is_ascii_alphanumeric() || "=+#-.".contains(c)))),
+                c => return Err(PgnError::new(line, column, format!("unexpected character {:?}", c)))
+            };
+
+            return Ok(Some((token, line, column)));
+        }
+    }
+}
+
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None
+    }
+}
+
+pub struct Games<'a> {
+    lexer: Lexer<'a>,
+    peeked: Option<(Token, usize, usize)>,
+    in_movetext: bool,
+    failed: bool
+}
+
+// parses the games one by one, after an error the rest of that game is skipped
+pub fn games(text: &str) -> Games<'_> {
+    Games { lexer: Lexer::new(text), peeked: None, in_movetext: false, failed: false }
+}
+
+pub fn parse(text: &str) -> Result<Vec<Game>, PgnError> {
+    games(text).collect()
+}
+
+impl Games<'_> {
+    fn peek(&mut self) -> Result<Option<&(Token, usize, usize)>, PgnError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next_token()?;
+        }
+
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        self.peek()?;
+        Ok(self.peeked.take())
+    }
+
+    // drops everything up to the tag section of the next game
+    fn recover(&mut self) {
+        let mut past_tags = self.in_movetext;
+        self.peeked = None;
+
+        loop {
+            self.lexer.rest_of_line();
+            self.lexer.bump();
+
+            match self.lexer.chars.peek() {
+                Some('[') if past_tags => return,
+                Some('[') => {},
+                None => return,
+                _ => past_tags = true
+            }
+        }
+    }
+
+    fn game(&mut self) -> Result<Option<Game>, PgnError> {
+        let mut headers = Vec::new();
+        self.in_movetext = false;
+
+        while let Some((Token::Tag(..), _, _)) = self.peek()? {
+            if let Some((Token::Tag(name, value), _, _)) = self.next_token()? {
+                headers.push((name, value));
+            }
+        }
+
+        if headers.is_empty() && self.peek()?.is_none() {
+            return Ok(None);
+        }
+
+        self.in_movetext = true;
+
+        let mut comments = Vec::new();
+        let mut result = None;
+        let moves = self.moves(false, &mut comments, &mut result)?;
+
+        Ok(Some(Game {
+            headers,
+            comments,
+            moves,
+            result: result.unwrap_or_else(|| "*".to_string())
+        }))
+    }
+
+    fn moves(&mut self, nested: bool, comments: &mut Vec<String>, result: &mut Option<String>) -> Result<Vec<PgnMove>, PgnError> {
+        let mut moves: Vec<PgnMove> = Vec::new();
+
+        loop {
+            let Some((token, line, column)) = self.peek()?.cloned() else {
+                if nested {
+                    return Err(PgnError::new(self.lexer.line, self.lexer.column, "unterminated variation"));
+                }
+
+                return Ok(moves);
+            };
+
+            match token {
+                // a new game without a termination marker for this one
+                Token::Tag(..) if !nested => return Ok(moves),
+                Token::Tag(..) => return Err(PgnError::new(line, column, "tag inside a variation")),
+                _ => {}
+            }
+
+            self.next_token()?;
+
+            match token {
+                Token::San(san) => {
+                    moves.push(PgnMove {
+                        san,
+                        line,
+                        column,
+                        nags: vec![],
+                        comments: vec![],
+                        variations: vec![]
+                    });
+                },
+                Token::Nag(nag) => match moves.last_mut() {
+                    Some(last) => last.nags.push(nag),
+                    None => return Err(PgnError::new(line, column, "annotation before the first move"))
+                },
+                Token::Comment(comment) => match moves.last_mut() {
+                    Some(last) => last.comments.push(comment),
+                    None => comments.push(comment)
+                },
+                Token::Open => {
+                    if moves.is_empty() {
+                        return Err(PgnError::new(line, column, "variation before the first move"));
+                    }
+
+                    let mut variation_comments = Vec::new();
+                    let variation = self.moves(true, &mut variation_comments, result)?;
+
+                    if variation.is_empty() {
+                        return Err(PgnError::new(line, column, "empty variation"));
+                    }
+
+                    moves.last_mut().unwrap().variations.push(variation);
+                },
+                Token::Close if nested => return Ok(moves),
+                Token::Close => return Err(PgnError::new(line, column, "unexpected )")),
+                Token::Result(_) if nested => return Err(PgnError::new(line, column, "game result inside a variation")),
+                Token::Result(value) => {
+                    *result = Some(value);
+                    return Ok(moves);
+                },
+                Token::MoveNumber | Token::Tag(..) => {}
+            }
+        }
+    }
+}
+
+impl Iterator for Games<'_> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            self.failed = false;
+            self.recover();
+        }
+
+        match self.game() {
+            Ok(game) => game.map(Ok),
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
\ No newline at end of file
@@ -1,13 +1,27 @@
-use std::{io::{self, Write}, path::Path};
+use std::{collections::HashMap, io::{self, Write}, path::Path, sync::{Arc, Mutex}, thread};
 
-use crate::{board::Board, engine::{Engine, EngineType}, moves::{Move, MoveType}, piece::{PieceColor, PieceType}};
+use crate::{board::Board, book::{BookLearning, BookOptions, BookSelection}, engine::{Engine, EngineType}, mcts::{Leaf, MctsOptions, Prior, Selection}, moves::{Move, MoveType}, pgn::Game, piece::{PieceColor, PieceType}, search::SearchInfo, skill::Skill, r#const::{DEFAULT_BOOK_DEPTH, DEFAULT_MOVE_OVERHEAD, MAX_HASH_SIZE, MAX_PLIES, MAX_SKILL_LEVEL, MAX_THREADS, SKILL_MAX_ELO, SKILL_MIN_ELO}, time_manager::{TimeControl, TimeManager}};
 
 pub struct UciProtocol {
     pub engine: Engine,
     board: Board,
     engine_type: EngineType,
     enable_book: bool,
-    move_history: Vec<String>
+    pub move_overhead: u64,
+    // rebuilt from every position command
+    game: Game,
+    // the engine's moves by ply with the score it expected
+    evaluations: HashMap<usize, (Move, i32)>,
+    // name and rating from UCI_Opponent
+    opponent: Option<(String, Option<u32>)>,
+    // UCI_LimitStrength plays at UCI_Elo instead of the skill level
+    pub skill_level: u8,
+    pub limit_strength: bool,
+    pub elo: u32,
+    // upper bounds for the options that decide how much memory and cpu the engine takes
+    pub max_hash_size: usize,
+    pub max_mcts_nodes: usize,
+    pub max_threads: usize
 }
 
 impl UciProtocol {
@@ -17,10 +31,44 @@ impl UciProtocol {
             board: Board::startpos(),
             engine_type: EngineType::Minimax, // default
             enable_book: false,
-            move_history: vec![]
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            game: Game::new(None),
+            evaluations: HashMap::new(),
+            opponent: None,
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: SKILL_MAX_ELO,
+            max_hash_size: MAX_HASH_SIZE,
+            max_mcts_nodes: 10000000,
+            max_threads: MAX_THREADS
         }
     }
 
+    // a preset from the server, the client can still change it with the options
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill_level = skill.level;
+        self.limit_strength = false;
+        self.update_skill();
+    }
+
+    pub fn update_skill(&mut self) {
+        let skill = if self.limit_strength { Skill::from_elo(self.elo) } else { Skill::new(self.skill_level) };
+        self.engine.set_skill(skill);
+    }
+
+    // the game so far, in PGN
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    // the options and the search tables stay, the position, the game and its book moves start over
+    pub fn new_game(&mut self) {
+        self.board = Board::startpos();
+        self.game = Game::new(None);
+        self.evaluations.clear();
+        self.engine.new_game();
+    }
+
     pub fn run_command(&mut self, command: &str) -> io::Result<()> {
         match command {
             "uci" => self.identify(),
@@ -28,8 +76,9 @@ impl UciProtocol {
             cmd if cmd.starts_with("position") => self.handle_position(cmd, &mut io::stdout())?,
             cmd if cmd.starts_with("go") => self.handle_go(cmd, &mut io::stdout())?,
             cmd if cmd.starts_with("setoption") => self.set_option(cmd, &mut io::stdout())?,
+            "pgn" => print!("{}", self.game),
             "ucinewgame" => {
-                self.board = Board::startpos();
+                self.new_game();
                 self.engine.switch_to(self.engine_type);
                 self.engine.set_book_enabled(self.enable_book);
             },
@@ -46,6 +95,16 @@ impl UciProtocol {
     pub fn run(&mut self) -> io::Result<()> {
         self.identify();
 
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<SearchInfo>();
+        self.engine.set_info_sender(Some(sender));
+
+        // the search is blocking, its progress is printed from here while it runs
+        thread::spawn(move || {
+            while let Some(info) = receiver.blocking_recv() {
+                println!("{}", info.to_uci());
+            }
+        });
+
         let stdin = io::stdin();
         let mut input = String::new();
 
@@ -77,21 +136,45 @@ impl UciProtocol {
         println!("id author ggod");
         println!("option name EngineType type combo default Minimax var Minimax var MCTS");
         println!("option name EnableBook type check default false");
+        println!("option name BookFile type string default <empty>");
+        println!("option name BookVariety type combo default Best var Best var Weighted var Score");
+        println!("option name BookDepth type spin default {} min 0 max 1000", DEFAULT_BOOK_DEPTH);
+        println!("option name BookMinGames type spin default 1 min 1 max 10000");
+        println!("option name BookLearningFile type string default <empty>");
+        println!("option name SyzygyPath type string default <empty>");
+        println!("option name EvalFile type string default <empty>");
+        println!("option name UseNNUE type check default false");
+        println!("option name Move Overhead type spin default {} min 0 max 5000", DEFAULT_MOVE_OVERHEAD);
+        println!("option name Hash type spin default {} min 1 max {}", self.engine.hash_size, self.max_hash_size);
+        println!("option name Threads type spin default 1 min 1 max {}", self.max_threads);
+        println!("option name MCTS Node Limit type spin default {} min 1000 max {}", self.engine.mcts_options.max_nodes, self.max_mcts_nodes);
+        println!("option name MCTS Selection type combo default UCB1 var UCB1 var PUCT");
+        println!("option name MCTS Prior type combo default MoveOrdering var MoveOrdering var Evaluation");
+        println!("option name MCTS Leaf type combo default Quiescence var Quiescence var Minimax var Rollout");
+        println!("option name Skill Level type spin default {} min 0 max {}", self.skill_level, MAX_SKILL_LEVEL);
+        println!("option name UCI_LimitStrength type check default {}", self.limit_strength);
+        println!("option name UCI_Elo type spin default {} min {} max {}", self.elo, SKILL_MIN_ELO, SKILL_MAX_ELO);
+        println!("option name UCI_Opponent type string default <empty>");
         println!("uciok");
     }
 
+    // the lowercase option name of a setoption command
+    pub fn option_name(command: &str) -> Option<String> {
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        let name_index = parts.iter().position(|&p| p.to_lowercase() == "name")?;
+        let value_index = parts.iter().position(|&p| p.to_lowercase() == "value");
+
+        let name_end = value_index.unwrap_or(parts.len()).max(name_index + 1);
+        Some(parts[(name_index + 1)..name_end].join(" ").to_lowercase())
+    }
+
     pub fn set_option<T: Write>(&mut self, command: &str, writer: &mut T) -> io::Result<()> {
         let parts: Vec<&str> = command.split_whitespace().collect();
-        let name_index = parts.iter().position(|&p| p.to_lowercase() == "name");
         let value_index = parts.iter().position(|&p| p.to_lowercase() == "value");
 
-        if name_index.is_none() {
+        let Some(name) = UciProtocol::option_name(command) else {
             return Ok(());
-        }
-
-        let name_start = name_index.unwrap() + 1;
-        let name_end = value_index.unwrap_or(parts.len());
-        let name = parts[name_start..name_end].join(" ").to_lowercase();
+        };
 
         let value = if let Some(index) = value_index {
             if index + 1 < parts.len() {
@@ -136,6 +219,248 @@ impl UciProtocol {
                     a => writeln!(writer, "info string Unknown enable book option: {}, current: {:?}", a, self.engine_type)?
                 }
             },
+            "bookfile" | "book file" => {
+                let path = value_index.map(|index| parts[(index + 1)..].join(" ")).unwrap_or_default();
+
+                if path.is_empty() || path == "<empty>" {
+                    writeln!(writer, "info string Unloading books")?;
+                    self.engine.book = None;
+                    self.engine.polyglot = None;
+                } else {
+                    match self.engine.load_book(Path::new(&path)) {
+                        Ok(loaded) => writeln!(writer, "info string Loaded book {} with {} entries", path, loaded)?,
+                        Err(e) => writeln!(writer, "info string Failed to load book from {}: {}", path, e)?
+                    }
+                }
+            },
+            "bookvariety" | "book variety" | "bookselection" | "book selection" => {
+                let selection = match value.to_lowercase().as_str() {
+                    "best" => Some(BookSelection::Best),
+                    "weighted" | "random" => Some(BookSelection::Weighted),
+                    "score" => Some(BookSelection::Score),
+                    _ => None
+                };
+
+                match selection {
+                    Some(selection) => {
+                        writeln!(writer, "info string Setting book variety to {:?}", selection)?;
+                        self.engine.set_book_options(BookOptions { selection, ..self.engine.book_options });
+                    },
+                    None => writeln!(writer, "info string Unknown book variety: {}, current: {:?}", value, self.engine.book_options.selection)?
+                }
+            },
+            "bookdepth" | "book depth" => {
+                match value.parse::<usize>() {
+                    Ok(max_depth) => {
+                        let max_depth = max_depth.min(1000);
+                        writeln!(writer, "info string Setting book depth to {}", max_depth)?;
+                        self.engine.set_book_options(BookOptions { max_depth, ..self.engine.book_options });
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid book depth: {}, current: {}", value, self.engine.book_options.max_depth)?
+                }
+            },
+            "bookmingames" | "book min games" => {
+                match value.parse::<usize>() {
+                    Ok(min_games) => {
+                        let min_games = min_games.clamp(1, 10000);
+                        writeln!(writer, "info string Setting book minimum games to {}", min_games)?;
+                        self.engine.set_book_options(BookOptions { min_games, ..self.engine.book_options });
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid book minimum games: {}, current: {}", value, self.engine.book_options.min_games)?
+                }
+            },
+            "booklearningfile" | "book learning file" => {
+                let path = value_index.map(|index| parts[(index + 1)..].join(" ")).unwrap_or_default();
+
+                if path.is_empty() || path == "<empty>" {
+                    writeln!(writer, "info string Disabling book learning")?;
+                    self.engine.set_learning(None);
+                } else {
+                    match BookLearning::open(Path::new(&path)) {
+                        Ok(learning) => {
+                            writeln!(writer, "info string Learning book moves in {} with {} entries", path, learning.len())?;
+                            self.engine.set_learning(Some(Arc::new(Mutex::new(learning))));
+                        },
+                        Err(e) => writeln!(writer, "info string Failed to open book learning file {}: {}", path, e)?
+                    }
+                }
+            },
+            "syzygypath" => {
+                let path = value_index.map(|index| parts[(index + 1)..].join(" ")).unwrap_or_default();
+
+                if path.is_empty() || path == "<empty>" {
+                    writeln!(writer, "info string Disabling tablebases")?;
+                    self.engine.set_tablebase(None);
+                } else {
+                    match self.engine.load_tablebase(&path) {
+                        Ok(tables) => writeln!(writer, "info string Found {} tablebases in {}", tables, path)?,
+                        Err(e) => writeln!(writer, "info string Failed to load tablebases from {}: {}", path, e)?
+                    }
+                }
+            },
+            "evalfile" => {
+                let path = value_index.map(|index| parts[(index + 1)..].join(" ")).unwrap_or_default();
+
+                if path.is_empty() || path == "<empty>" {
+                    writeln!(writer, "info string Unloading network")?;
+                    self.engine.set_network(None);
+                } else {
+                    match self.engine.load_network(Path::new(&path)) {
+                        Ok(hidden) => writeln!(writer, "info string Loaded network {} with {} hidden neurons", path, hidden)?,
+                        Err(e) => writeln!(writer, "info string Failed to load network from {}: {}", path, e)?
+                    }
+                }
+            },
+            "move overhead" | "moveoverhead" => {
+                match value.parse::<u64>() {
+                    Ok(overhead) => {
+                        writeln!(writer, "info string Setting move overhead to {}", overhead)?;
+                        self.move_overhead = overhead.min(5000);
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid move overhead: {}, current: {}", value, self.move_overhead)?
+                }
+            },
+            "hash" => {
+                match value.parse::<usize>() {
+                    Ok(size) => {
+                        let size = size.clamp(1, self.max_hash_size.max(1));
+                        writeln!(writer, "info string Setting hash to {} MB", size)?;
+                        self.engine.set_hash_size(size);
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid hash: {}, current: {}", value, self.engine.hash_size)?
+                }
+            },
+            "threads" => {
+                match value.parse::<usize>() {
+                    Ok(threads) => {
+                        let threads = threads.clamp(1, self.max_threads.max(1));
+                        writeln!(writer, "info string Setting threads to {}", threads)?;
+                        self.engine.set_threads(threads);
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid threads: {}, current: {}", value, self.engine.threads)?
+                }
+            },
+            "mcts node limit" | "mctsnodelimit" => {
+                match value.parse::<usize>() {
+                    Ok(max_nodes) => {
+                        let max_nodes = max_nodes.clamp(1000, self.max_mcts_nodes.max(1000));
+                        writeln!(writer, "info string Setting MCTS node limit to {}", max_nodes)?;
+                        self.engine.set_mcts_options(MctsOptions { max_nodes, ..self.engine.mcts_options });
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid MCTS node limit: {}, current: {}", value, self.engine.mcts_options.max_nodes)?
+                }
+            },
+            "mcts selection" | "mctsselection" => {
+                let selection = match value.to_lowercase().as_str() {
+                    "ucb1" | "uct" => Some(Selection::Ucb1),
+                    "puct" => Some(Selection::Puct),
+                    _ => None
+                };
+
+                match selection {
+                    Some(selection) => {
+                        writeln!(writer, "info string Setting MCTS selection to {:?}", selection)?;
+                        self.engine.set_mcts_options(MctsOptions { selection, ..self.engine.mcts_options });
+                    },
+                    None => writeln!(writer, "info string Unknown MCTS selection: {}, current: {:?}", value, self.engine.mcts_options.selection)?
+                }
+            },
+            "mcts prior" | "mctsprior" => {
+                let prior = match value.to_lowercase().as_str() {
+                    "moveordering" => Some(Prior::MoveOrdering),
+                    "evaluation" | "eval" => Some(Prior::Evaluation),
+                    _ => None
+                };
+
+                match prior {
+                    Some(prior) => {
+                        writeln!(writer, "info string Setting MCTS prior to {:?}", prior)?;
+                        self.engine.set_mcts_options(MctsOptions { prior, ..self.engine.mcts_options });
+                    },
+                    None => writeln!(writer, "info string Unknown MCTS prior: {}, current: {:?}", value, self.engine.mcts_options.prior)?
+                }
+            },
+            "mcts leaf" | "mctsleaf" => {
+                let leaf = match value.to_lowercase().as_str() {
+                    "quiescence" => Some(Leaf::Quiescence),
+                    "minimax" | "alphabeta" => Some(Leaf::Minimax),
+                    "rollout" => Some(Leaf::Rollout),
+                    _ => None
+                };
+
+                match leaf {
+                    Some(leaf) => {
+                        writeln!(writer, "info string Setting MCTS leaf evaluation to {:?}", leaf)?;
+                        self.engine.set_mcts_options(MctsOptions { leaf, ..self.engine.mcts_options });
+                    },
+                    None => writeln!(writer, "info string Unknown MCTS leaf evaluation: {}, current: {:?}", value, self.engine.mcts_options.leaf)?
+                }
+            },
+            "usennue" => {
+                match value.to_lowercase().as_str() {
+                    "true" => {
+                        if self.engine.set_use_nnue(true) {
+                            writeln!(writer, "info string Setting use NNUE to true")?;
+                        } else {
+                            writeln!(writer, "info string No network loaded, set EvalFile first")?;
+                        }
+                    },
+                    "false" => {
+                        writeln!(writer, "info string Setting use NNUE to false")?;
+                        self.engine.set_use_nnue(false);
+                    },
+                    a => writeln!(writer, "info string Unknown use NNUE option: {}, current: {:?}", a, self.engine.use_nnue)?
+                }
+            },
+            "skill level" | "skilllevel" => {
+                match value.parse::<u8>() {
+                    Ok(level) => {
+                        self.skill_level = level.min(MAX_SKILL_LEVEL);
+                        writeln!(writer, "info string Setting skill level to {}", self.skill_level)?;
+                        self.update_skill();
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid skill level: {}, current: {}", value, self.skill_level)?
+                }
+            },
+            "uci_limitstrength" => {
+                match value.to_lowercase().as_str() {
+                    "true" => {
+                        writeln!(writer, "info string Limiting strength to {} Elo", self.elo)?;
+                        self.limit_strength = true;
+                        self.update_skill();
+                    },
+                    "false" => {
+                        writeln!(writer, "info string Setting limit strength to false")?;
+                        self.limit_strength = false;
+                        self.update_skill();
+                    },
+                    a => writeln!(writer, "info string Unknown limit strength option: {}, current: {:?}", a, self.limit_strength)?
+                }
+            },
+            "uci_elo" => {
+                match value.parse::<u32>() {
+                    Ok(elo) => {
+                        self.elo = elo.clamp(SKILL_MIN_ELO, SKILL_MAX_ELO);
+                        writeln!(writer, "info string Setting Elo to {}", self.elo)?;
+                        self.update_skill();
+                    },
+                    Err(_) => writeln!(writer, "info string Invalid Elo: {}, current: {}", value, self.elo)?
+                }
+            },
+            "uci_opponent" => {
+                // [GM|IM|FM|WGM|WIM|none] [<elo>|none] [computer|human] <name>
+                let opponent = value_index.map(|index| &parts[(index + 1)..]).unwrap_or_default();
+
+                if opponent.len() < 4 {
+                    writeln!(writer, "info string Invalid opponent: {}", opponent.join(" "))?;
+                } else {
+                    let name = opponent[3..].join(" ");
+                    let elo = opponent[1].parse::<u32>().ok();
+
+                    writeln!(writer, "info string Setting opponent to {}", name)?;
+                    self.opponent = Some((name, elo));
+                }
+            },
             a => writeln!(writer, "info string Unknown option: {}", a)?
         }
 
@@ -149,11 +474,9 @@ impl UciProtocol {
         match *pos_type {
             "startpos" => {
                 self.board = Board::startpos();
-                self.engine.switch_to(self.engine_type);
-                self.engine.set_book_enabled(self.enable_book);
+                self.start_game(None);
 
                 if let Some(moves_index) = parts.iter().position(|&p| p == "moves") {
-                    self.move_history.clear();
                     for i in (moves_index + 1)..parts.len() {
                         let uci_move = parts[i];
                         writeln!(writer, "info String {uci_move}")?;
@@ -161,30 +484,62 @@ impl UciProtocol {
                     }
                 }
             },
-            "fen" => {
-                if parts.len() >= 8 {
-                    let fen = parts[2..8].join(" ");
-                    self.board = Board::from_fen(&fen);
-
-                    if let Some(moves_index) = parts.iter().position(|&p| p == "moves") {
-                        self.move_history.clear();
-                        for i in (moves_index + 1)..parts.len() {
-                            let uci_move = parts[i];
-                            self.move_uci(uci_move.trim());
-                        }
+            "fen" if parts.len() >= 8 => {
+                let fen = parts[2..8].join(" ");
+                self.board = Board::from_fen(&fen);
+                self.start_game(Some(&fen));
+
+                if let Some(moves_index) = parts.iter().position(|&p| p == "moves") {
+                    for i in (moves_index + 1)..parts.len() {
+                        let uci_move = parts[i];
+                        self.move_uci(uci_move.trim());
                     }
                 }
             },
             _ => {}
         }
 
+        // the book learns from a finished game once, the engine forgets its book moves then
+        if self.game.result != "*" {
+            match self.engine.learn(&self.game) {
+                Ok(0) => {},
+                Ok(learned) => writeln!(writer, "info string Learned {} book moves from {}", learned, self.game.result)?,
+                Err(e) => writeln!(writer, "info string Failed to save book learning: {}", e)?
+            }
+        }
+
         Ok(())
     }
 
+    // the players and other headers carry over, evaluations only while the start position is the same
+    fn start_game(&mut self, fen: Option<&str>) {
+        if self.game.header("FEN") != fen {
+            self.evaluations.clear();
+        }
+
+        let mut game = Game::new(fen);
+
+        for (tag, value) in &self.game.headers {
+            if !["Result", "SetUp", "FEN"].contains(&tag.as_str()) {
+                game.set_header(tag, value.clone());
+            }
+        }
+
+        self.game = game;
+    }
+
+    fn play(&mut self, m: &Move) {
+        let comment = match self.evaluations.get(&self.game.moves.len()) {
+            Some((engine_move, score)) if engine_move == m => Some(format!("[%eval {:.2}]", *score as f64 / 100.0)),
+            _ => None
+        };
+
+        self.game.play(&mut self.board, m, comment);
+    }
+
     pub fn handle_go<T: Write>(&mut self, command: &str, writer: &mut T) -> io::Result<()> {
         let parts: Vec<&str> = command.split_whitespace().collect();
-        let mut depth = 5;
-        let mut time_limit = 5000;
+        let mut depth = None;
         let mut wtime = None;
         let mut btime = None;
         let mut winc = None;
@@ -192,10 +547,10 @@ impl UciProtocol {
         let mut movestogo = None;
         let mut movetime = None;
 
-        for i in 0..parts.len() - 1 {
+        for i in 0..parts.len().saturating_sub(1) {
             if parts[i] == "depth" {
                 if let Ok(d) = parts[i + 1].parse::<u8>() {
-                    depth = d;
+                    depth = Some(d);
                 }
             } else if parts[i] == "wtime" {
                 if let Ok(t) = parts[i + 1].parse::<u64>() {
@@ -224,27 +579,30 @@ impl UciProtocol {
             }
         }
 
-        if let Some(mt) = movetime {
-            time_limit = mt;
-        } else if wtime.is_some() || btime.is_some() {
-            let is_white = self.board.turn == PieceColor::White;
-            let time = if is_white { wtime } else { btime };
-            let inc = if is_white { winc } else { binc };
+        let is_white = self.board.turn == PieceColor::White;
+        let control = TimeControl {
+            time: if is_white { wtime } else { btime },
+            increment: if is_white { winc } else { binc }.unwrap_or(0),
+            moves_to_go: movestogo,
+            move_time: movetime
+        };
+
+        // without a clock the search is limited by depth and the default budget
+        let (depth, time_manager) = if control.time.is_some() || control.move_time.is_some() {
+            (depth.unwrap_or(MAX_PLIES), TimeManager::new(&control, self.move_overhead))
+        } else {
+            (depth.unwrap_or(5), TimeManager::from_budget(5000))
+        };
 
-            if let Some(remaining) = time {
-                let moves_left = movestogo.unwrap_or(30);
-                let increment = inc.unwrap_or(0);
+        let result = self.engine.iterative_deepening(&mut self.board, depth, time_manager);
 
-                let base_time = remaining / moves_left as u64;
-                let allocated = base_time + increment / 2;
+        if let Some(best_move) = result.as_ref() {
+            self.record_engine_move(best_move, &control);
 
-                time_limit = std::cmp::min(allocated, remaining / 5);
+            if let Some(info) = self.engine.last_info.as_ref() {
+                writeln!(writer, "info string {}", info)?;
             }
-        }
 
-        let result = self.engine.iterative_deepening(&mut self.board, depth, time_limit, &self.move_history);
-
-        if let Some(best_move) = result.as_ref() {
             writeln!(writer, "info string turn {:?} move clr {:?}", self.board.turn, best_move.piece_color)?;
             writeln!(writer, "bestmove {}", self.move_to_uci(best_move))?;
         } else {
@@ -254,6 +612,33 @@ impl UciProtocol {
         Ok(())
     }
 
+    // the engine plays the side to move, its move is recorded once the GUI sends it back
+    fn record_engine_move(&mut self, best_move: &Move, control: &TimeControl) {
+        let (engine, opponent) = match self.board.turn {
+            PieceColor::White => ("White", "Black"),
+            PieceColor::Black => ("Black", "White")
+        };
+
+        self.game.set_header(engine, "mchess");
+
+        if let Some((name, elo)) = self.opponent.clone() {
+            self.game.set_header(opponent, name);
+
+            if let Some(elo) = elo {
+                self.game.set_header(&format!("{}Elo", opponent), elo.to_string());
+            }
+        }
+
+        // the clock is only the base time at the start of the game
+        if let Some(time) = control.time.filter(|_| self.game.moves.len() < 2 && self.game.header("TimeControl").is_none()) {
+            self.game.set_header("TimeControl", format!("{}+{}", time / 1000, control.increment / 1000));
+        }
+
+        if let Some(score) = self.engine.last_score {
+            self.evaluations.insert(self.game.moves.len(), (best_move.clone(), score));
+        }
+    }
+
     fn move_uci(&mut self, uci_move: &str) {
         if uci_move.len() < 4 {
             return;
@@ -273,15 +658,13 @@ impl UciProtocol {
             if m.from.x == from_file && m.from.y == from_rank && m.to.x == to_file && m.to.y == to_rank {
                 if uci_move.len() > 4 {
                     println!("info string > 4 {uci_move}");
-                    if m.move_type.contains(&MoveType::Promotion) {
-                        self.board.make_move(&m);
-                        self.move_history.push(m.to_san(&self.board));
+                    if m.move_type.contains(&MoveType::Promotion) && self.move_to_uci(&m).eq_ignore_ascii_case(uci_move) {
+                        self.play(&m);
                         break;
                     }
                 } else {
                     println!("info string turn bef {:?}", self.board.turn);
-                    self.board.make_move(&m);
-                    self.move_history.push(m.to_san(&self.board));
+                    self.play(&m);
                     println!("info string turn aft {:?}", self.board.turn);
                     break;
                 }
@@ -1,10 +1,16 @@
-use crate::r#const::{CASTLING_VALUE, CHECK_VALUE, DEFAULT_MARGIN, KILLER_MOVE_VALUE, MAX_WINDOW_WIDTH, PAWN_DEVELOPMENT_BONUS, PROMOTION_VALUE, PV_MOVE};
-use crate::evaluation::{evaluate, EvaluationResult};
+use crate::r#const::{CASTLING_VALUE, CHECK_VALUE, DEFAULT_HASH_SIZE, DEFAULT_MARGIN, KILLER_MOVE_VALUE, MAX_WINDOW_WIDTH, PAWN_DEVELOPMENT_BONUS, PAWN_VALUE, PROMOTION_VALUE, PV_MOVE, TB_CURSED_WIN_VALUE, TB_WIN_VALUE};
+use crate::evaluation::{EvaluationResult, Evaluator, HandcraftedEvaluator};
 use crate::board::{Board, ResultType};
 use crate::moves::{Move, MoveType};
-use crate::piece::PieceType;
+use crate::piece::{PieceColor, PieceType};
+use crate::syzygy::{Tablebase, Wdl};
+use crate::time_manager::TimeManager;
 use core::f64;
 use std::collections::HashMap;
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc::UnboundedSender;
 
 pub struct Minimax {
     evaluation_cache: EvalCache,
@@ -12,9 +18,44 @@ pub struct Minimax {
     transposition_table: TranspositionTable,
     killer_moves: Vec<Vec<Option<Move>>>,
     pub nodes: u64,
-    is_stopping: bool,
+    // the deepest iteration the last search completed
+    pub depth: u8,
+    pub tb_hits: u64,
+    tablebase: Option<Arc<Tablebase>>,
+    evaluator: Arc<dyn Evaluator>,
+    time_manager: Option<TimeManager>,
+    info: Option<UnboundedSender<SearchInfo>>,
+    // shared so the search can be stopped from another thread
+    stop: Arc<AtomicBool>,
 }
 
+// progress of a search, sent whenever an iteration completes
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u32,
+    // centipawns from the point of view of the side to move
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    pub pv: Vec<Move>
+}
+
+impl SearchInfo {
+    pub fn to_uci(&self) -> String {
+        let time = self.time.as_millis().max(1);
+        let pv: Vec<String> = self.pv.iter().map(|m| format!("{:?}", m)).collect();
+
+        format!("info depth {} score cp {} nodes {} nps {} time {} pv {}",
+            self.depth, self.score, self.nodes, self.nodes as u128 * 1000 / time, time, pv.join(" "))
+    }
+}
+
+// search values are in pawns, mates are clamped so the result can still be negated
+pub fn to_centipawns(pawns: f64) -> i32 {
+    (pawns * PAWN_VALUE).round().clamp(-(i32::MAX as f64), i32::MAX as f64) as i32
+}
+
+// a Cut score is a lower bound and an All score an upper bound, whichever side moved
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum NodeType {
     PV,
@@ -24,6 +65,7 @@ pub enum NodeType {
 
 #[derive(Debug, Clone)]
 pub struct Node {
+    hash: i64,
     depth: u8,
     node_type: NodeType,
     score: f64,
@@ -54,7 +96,8 @@ impl TranspositionTable {
     pub fn store(&mut self, hash: i64, node: Node) {
         let index = (hash as usize) & self.mask;
         if let Some(entry) = &self.entries[index] {
-            if entry.depth <= node.depth {
+            // another position sharing the slot is replaced as well
+            if entry.hash != hash || entry.depth <= node.depth {
                 self.entries[index] = Some(node);
             }
         } else {
@@ -62,9 +105,9 @@ impl TranspositionTable {
         }
     }
 
-    pub fn get(&self, hash: i64) -> &Option<Node> {
+    pub fn get(&self, hash: i64) -> Option<&Node> {
         let index = (hash as usize) & self.mask;
-        &self.entries[index]
+        self.entries[index].as_ref().filter(|node| node.hash == hash)
     }
 }
 
@@ -106,18 +149,77 @@ impl EvalCache {
 
 impl Minimax {
     pub fn new() -> Self {
+        Minimax::with_hash_size(DEFAULT_HASH_SIZE)
+    }
+
+    pub fn with_hash_size(size_mb: usize) -> Self {
         Minimax {
-            evaluation_cache: EvalCache::new(64),
+            evaluation_cache: EvalCache::new(size_mb),
             move_evaluation_cache: HashMap::new(),
-            transposition_table: TranspositionTable::new(64),
+            transposition_table: TranspositionTable::new(size_mb),
             killer_moves: vec![vec![None; 2]; 100],
             nodes: 0,
-            is_stopping: false
+            depth: 0,
+            tb_hits: 0,
+            tablebase: None,
+            evaluator: Arc::new(HandcraftedEvaluator),
+            time_manager: None,
+            info: None,
+            stop: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.evaluation_cache = EvalCache::new(64);
+    }
+
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    pub fn probe_tablebase(&mut self, board: &mut Board) -> Option<f64> {
+        let tablebase = self.tablebase.clone()?;
+
+        if !tablebase.can_probe(board) {
+            return None;
         }
+
+        let value = match tablebase.probe_wdl(board)? {
+            Wdl::Win => TB_WIN_VALUE,
+            Wdl::CursedWin => TB_CURSED_WIN_VALUE,
+            Wdl::Draw => 0.0,
+            Wdl::BlessedLoss => -TB_CURSED_WIN_VALUE,
+            Wdl::Loss => -TB_WIN_VALUE
+        };
+
+        self.tb_hits += 1;
+
+        Some(if board.turn == PieceColor::White { value } else { -value })
+    }
+
+    // the root is never probed here, so the search always has a move to return
+    fn search_child(&mut self, board: &mut Board, depth: u8, alpha: f64, beta: f64, maximizer: bool) -> SearchResult {
+        if let Some(value) = self.probe_tablebase(board) {
+            self.nodes += 1;
+
+            return SearchResult {
+                value,
+                moves: vec![]
+            }
+        }
+
+        self.search(board, depth, alpha, beta, maximizer)
     }
 
     pub fn store_position(&mut self, board: &Board, depth: u8, node_type: NodeType, score: f64, best_move: Option<Move>) {
+        // a stopped search returns made up scores
+        if self.is_stopping() {
+            return;
+        }
+
         let node = Node {
+            hash: board.hash,
             depth,
             node_type,
             score,
@@ -184,38 +286,91 @@ impl Minimax {
     }
 
     pub fn stop(&mut self) {
-        self.is_stopping = true;
+        self.stop.store(true, Ordering::Relaxed);
     }
 
     pub fn reset_stop(&mut self) {
-        self.is_stopping = false;
+        self.stop.store(false, Ordering::Relaxed);
+    }
+
+    pub fn set_stop_signal(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    fn is_stopping(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
     }
 
     pub fn iterative_deepening(&mut self, board: &mut Board, max_depth: u8, time_limit: u64) -> SearchResult {
-        let start_time = std::time::Instant::now();
+        self.iterative_deepening_timed(board, max_depth, TimeManager::from_budget(time_limit))
+    }
+
+    // receives a SearchInfo for every completed depth
+    pub fn set_info_sender(&mut self, sender: Option<UnboundedSender<SearchInfo>>) {
+        self.info = sender;
+    }
+
+    fn send_info(&self, board: &Board, depth: u8, result: &SearchResult, start: Instant) {
+        let Some(sender) = self.info.as_ref() else {
+            return;
+        };
+
+        let score = if board.turn == PieceColor::White { result.value } else { -result.value };
+
+        // a closed receiver only means nobody is watching
+        let _ = sender.send(SearchInfo {
+            depth: depth as u32,
+            score: to_centipawns(score),
+            nodes: self.nodes,
+            time: start.elapsed(),
+            pv: result.moves.clone()
+        });
+    }
+
+    pub fn iterative_deepening_timed(&mut self, board: &mut Board, max_depth: u8, time_manager: TimeManager) -> SearchResult {
         let mut best_result;
+        let root_moves = board.get_total_legal_moves(None).len();
+        let start = Instant::now();
+
+        // values are from white's point of view, black minimizes them
+        let maximizer = board.turn == PieceColor::White;
+
+        // the node limit and the reported nodes are for this search alone
+        self.nodes = 0;
+        self.depth = 0;
+        self.time_manager = Some(time_manager);
 
         {
             self.move_evaluation_cache.clear();
-            let result = self.search(board, 1, f64::NEG_INFINITY, f64::INFINITY, true);
+            let result = self.search(board, 1, f64::NEG_INFINITY, f64::INFINITY, maximizer);
             best_result = result;
 
-            println!("info string depth 1 moves {:?} score {} nodes {}", best_result.moves, best_result.value, self.nodes);
+            println!("info string depth 1 moves {:?} score {} nodes {} tbhits {}", best_result.moves, best_result.value, self.nodes, self.tb_hits);
+            self.send_info(board, 1, &best_result, start);
+
+            if !self.is_stopping() {
+                self.depth = 1;
+            }
         }
 
+        // nothing to think about with a single legal move or none at all
+        let max_depth = if root_moves <= 1 { 1 } else { max_depth };
+
         for depth in 2..=max_depth {
             self.move_evaluation_cache.clear();
 
+            let previous_best = best_result.moves.first().cloned();
+
             let mut window = 25.0;
             let mut alpha = best_result.value - window;
             let mut beta = best_result.value + window;
 
             loop {
-                let result = self.search(board, depth, alpha, beta, true);
+                let result = self.search(board, depth, alpha, beta, maximizer);
 
                 println!("info string aspwin depth {depth} alpha {alpha} beta {beta} score {} nodes {}", result.value, self.nodes);
 
-                if self.is_stopping {
+                if self.is_stopping() {
                     break;
                 }
 
@@ -245,29 +400,52 @@ impl Minimax {
                 }
             }
 
-            let elapsed = start_time.elapsed().as_millis() as u64;
-            if elapsed > (time_limit * 3) / 4 {
+            // the interrupted iteration is thrown away
+            if self.is_stopping() {
+                break;
+            }
+
+            self.send_info(board, depth, &best_result, start);
+            self.depth = depth;
+
+            let score = if board.turn == PieceColor::White { best_result.value } else { -best_result.value };
+            let best_move_changed = previous_best != best_result.moves.first().cloned();
+
+            let time_manager = self.time_manager.as_mut().unwrap();
+            time_manager.update(best_move_changed, score);
+
+            if time_manager.should_stop() {
                 break;
             }
 
-            println!("info string depth {depth} moves {:?} score {} nodes {}", best_result.moves, best_result.value, self.nodes);
+            println!("info string depth {depth} moves {:?} score {} nodes {} tbhits {}", best_result.moves, best_result.value, self.nodes, self.tb_hits);
         }
 
-        if self.is_stopping {
+        self.time_manager = None;
+
+        if self.is_stopping() {
             self.reset_stop();
         }
 
         best_result
     }
 
+    fn check_time(&mut self) {
+        if self.time_manager.as_ref().is_some_and(|time_manager| time_manager.should_abort(self.nodes)) {
+            self.stop();
+        }
+    }
+
     pub fn search(&mut self, board: &mut Board, depth: u8, _alpha: f64, _beta: f64, maximizer: bool) -> SearchResult {
-        if self.is_stopping {
+        if self.is_stopping() {
             return SearchResult {
                 value: 0.0,
                 moves: vec![]
             }
         }
         self.nodes += 1;
+        self.check_time();
+
         if board.get_result() != ResultType::None || depth == 0 {
             return SearchResult {
                 value: self.quiescence(board, _alpha, _beta, maximizer, 8),
@@ -327,10 +505,10 @@ impl Minimax {
                 };
                                     
 
-                let mut result = self.search(board, new_depth, alpha, beta, false);
+                let mut result = self.search_child(board, new_depth, alpha, beta, false);
 
                 if new_depth < depth - 1 && result.value > alpha {
-                    result = self.search(board, depth - 1, alpha, beta, !maximizer);
+                    result = self.search_child(board, depth - 1, alpha, beta, !maximizer);
                 }
 
                 board.unmake_move(m, &history);
@@ -378,14 +556,14 @@ impl Minimax {
             let mut value = f64::INFINITY;
             let mut moves: Vec<Move> = vec![];
             let mut best_move = None;
-            let mut node_type = NodeType::All;
+            let mut node_type = NodeType::Cut;
             
             let legal_moves = self.sort(board.get_total_legal_moves(None), board, depth);
             
             for m in &legal_moves {
                 let history = board.make_move(m);
 
-                let result = self.search(board, depth - 1, alpha, beta, true);
+                let result = self.search_child(board, depth - 1, alpha, beta, true);
 
                 board.unmake_move(m, &history);
                 if start_hash != board.hash {
@@ -413,7 +591,7 @@ impl Minimax {
                 if beta <= alpha {
                     self.store_killer_move(m, depth);
 
-                    node_type = NodeType::Cut;
+                    node_type = NodeType::All;
                     break
                 }
             }
@@ -433,6 +611,7 @@ impl Minimax {
 
     pub fn quiescence(&mut self, board: &mut Board, mut alpha: f64, mut beta: f64, maximizer: bool, depth: i8) -> f64 {
         self.nodes += 1;
+        self.check_time();
 
         let stand_pat = self.evaluate(board).to_value();
 
@@ -493,7 +672,7 @@ impl Minimax {
         if self.evaluation_cache.contains(board.hash) {
             return *self.evaluation_cache.get(board.hash).unwrap()
         }
-        let value = evaluate(board);
+        let value = self.evaluator.evaluate(board);
         self.evaluation_cache.store(board.hash, value);
 
         value
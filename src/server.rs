@@ -3,25 +3,58 @@ use axum::{
     routing::get,
     Router,
     response::IntoResponse,
-    http::StatusCode,
-    Json, extract::State,
-    routing::post,
+    http::{header, StatusCode},
+    Json, extract::{rejection::JsonRejection, ConnectInfo, Path as AxumPath, Query, Request, State},
+    middleware::{self, Next},
+    response::Response,
+    Extension,
+    routing::{delete, post},
 };
 use serde::{Deserialize, Serialize};
-use std::{io::Write, path::Path, sync::{Arc, Mutex}, time::Duration};
-use tokio::{net::TcpListener, time::timeout};
+use std::{io::Write, net::SocketAddr, path::Path, sync::{atomic::Ordering, Arc, Mutex}, thread, time::{Duration, Instant}};
+use tokio::{net::TcpListener, sync::{mpsc::{self, UnboundedSender}, Semaphore}, time::{interval, timeout}};
 use futures::{SinkExt, StreamExt};
-use std::collections::HashMap;
 use std::env;
 use dotenv::dotenv;
 
-use crate::protocol::UciProtocol;
+use crate::{auth::Auth, api::{self, AnalyseRequest, AnalyseResponse, ApiError, EvaluateResponse, HealthResponse, LegalMovesResponse, PositionRequest}, book::BookLearning, engine::{Engine, EngineType}, protocol::UciProtocol, search::SearchInfo, session::{SessionError, SessionLimits, Sessions}, skill::{Skill, PRESETS}, r#const::{COMMAND_TIMEOUT, DEFAULT_BOOK_DEPTH, DEFAULT_MOVE_OVERHEAD, MAX_SKILL_LEVEL, SESSION_EVICTION_INTERVAL, SKILL_MAX_ELO, SKILL_MIN_ELO}};
 
 struct AppState {
-    protocols: Mutex<HashMap<String, UciProtocol>>,
-    template: Mutex<UciProtocol>
+    auth: Auth,
+    protocols: Mutex<Sessions>,
+    template: Mutex<UciProtocol>,
+    // searches running at the same time, each takes a thread of the blocking pool
+    searches: Semaphore,
+    max_searches: usize,
+    // clients are told apart by X-Forwarded-For when there are no keys
+    trust_proxy: bool
 }
 
+// the options a session may change, the files and paths on the server are the operator's
+const SESSION_OPTIONS: &[&str] = &[
+    "enginetype", "engine type",
+    "enablebook", "enable book",
+    "bookvariety", "book variety", "bookselection", "book selection",
+    "bookdepth", "book depth",
+    "bookmingames", "book min games",
+    "usennue",
+    "move overhead", "moveoverhead",
+    "hash",
+    "threads",
+    "mcts node limit", "mctsnodelimit",
+    "mcts selection", "mctsselection",
+    "mcts prior", "mctsprior",
+    "mcts leaf", "mctsleaf",
+    "skill level", "skilllevel",
+    "uci_limitstrength",
+    "uci_elo",
+    "uci_opponent"
+];
+
+// who a request counts against, the API key or without keys the client's address
+#[derive(Clone)]
+struct Identity(String);
+
 #[derive(Deserialize)]
 struct UciRequest {
     client_id: String,
@@ -33,6 +66,49 @@ struct UciResponse {
     client_id: String,
     response: Vec<String>
 }
+
+#[derive(Serialize)]
+struct SessionResponse {
+    client_id: String,
+    skill_level: u8
+}
+
+// a preset name or a skill level the new session plays at
+#[derive(Deserialize)]
+struct SessionQuery {
+    strength: Option<String>
+}
+
+impl SessionQuery {
+    fn skill(&self) -> Result<Option<Skill>, ApiError> {
+        let Some(strength) = self.strength.as_deref() else {
+            return Ok(None);
+        };
+
+        match Skill::preset(strength) {
+            Some(skill) => Ok(Some(skill)),
+            None => {
+                let presets: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+                Err(ApiError::bad_request(format!("unknown strength {}, use {} or a level from 0 to {}", strength, presets.join(", "), MAX_SKILL_LEVEL)))
+            }
+        }
+    }
+}
+
+// counts only, a client id is all it takes to use a session
+// durations in seconds
+#[derive(Serialize)]
+struct MetricsResponse {
+    sessions: usize,
+    busy: usize,
+    commands: u64,
+    max_sessions: usize,
+    idle_timeout: u64,
+    hash_size: usize,
+    max_searches: usize,
+    searches: usize
+}
+
 struct ResponseWriter {
     lines: Vec<String>,
     buffer: String,
@@ -47,29 +123,11 @@ impl ResponseWriter {
     }
     
     fn get_messages(self) -> Vec<String> {
-        let mut results = Vec::new();
-        let mut info_lines = Vec::new();
-        
-        for line in self.lines {
-            let trimmed = line.trim();
-            if trimmed.starts_with("bestmove") {
-                if !info_lines.is_empty() {
-                    results.push(info_lines.join(" "));
-                    info_lines.clear();
-                }
-                results.push(trimmed.to_string());
-            } else if trimmed.starts_with("info") {
-                info_lines.push(trimmed.to_string());
-            } else if !trimmed.is_empty() {
-                results.push(trimmed.to_string());
-            }
-        }
-        
-        if !info_lines.is_empty() {
-            results.push(info_lines.join(" "));
-        }
-        
-        results
+        self.lines.iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect()
     }
 }
 
@@ -102,119 +160,225 @@ impl std::io::Write for ResponseWriter {
     }
 }
 
-async fn websocket_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    ws.on_upgrade(|socket| connection(socket, state))
+// a proxy appends the address it got the request from, anything before it the client may have made up
+fn client_address(request: &Request, trust_proxy: bool) -> String {
+    let forwarded = request.headers().get("x-forwarded-for").and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .map(str::trim)
+        .filter(|address| !address.is_empty());
+
+    match forwarded {
+        Some(address) if trust_proxy => address.to_string(),
+        _ => request.extensions().get::<ConnectInfo<SocketAddr>>().map_or_else(|| "unknown".to_string(), |ConnectInfo(address)| address.ip().to_string())
+    }
 }
 
-async fn connection(socket: WebSocket, state: Arc<AppState>) {
-    let (mut sender, mut receiver) = socket.split();
+// the key comes as a bearer token, an X-API-Key header or, for browsers opening a WebSocket, an api_key parameter
+async fn authenticate(State(state): State<Arc<AppState>>, mut request: Request, next: Next) -> Result<Response, ApiError> {
+    let headers = request.headers();
 
-    let client_id = uuid::Uuid::new_v4().to_string();
+    let key = headers.get(header::AUTHORIZATION).and_then(|value| value.to_str().ok()).and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|value| value.to_str().ok()))
+        .or_else(|| request.uri().query().and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("api_key="))))
+        .map(|key| key.trim().to_string());
 
-    {
-        match timeout(Duration::from_secs(5), async {
-            let mut protocols = match state.protocols.lock() {
-                Ok(p) => p,
-                Err(e) => e.into_inner(),
-            };
-            
-            let template = match state.template.lock() {
-                Ok(t) => t,
-                Err(e) => e.into_inner(),
-            };
+    state.auth.authenticate(key.as_deref())?;
 
-            let mut new_protocol = UciProtocol::new();
-            new_protocol.engine.set_book_enabled(true);
+    let identity = match key {
+        Some(key) if state.auth.enabled() => key,
+        _ => client_address(&request, state.trust_proxy)
+    };
 
-            if let Some(book) = template.engine.book.as_ref() {
-                new_protocol.engine.book = Some(book.clone());
-            }
+    request.extensions_mut().insert(Identity(identity));
 
-            protocols.insert(client_id.clone(), new_protocol);
-        }).await {
-            Ok(_) => {},
-            Err(_) => {
-                eprintln!("Timeout initializing client {}", client_id);
-                return;
-            }
+    Ok(next.run(request).await)
+}
+
+async fn websocket_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>, Extension(identity): Extension<Identity>, Query(query): Query<SessionQuery>) -> Result<Response, ApiError> {
+    let skill = query.skill()?;
+    Ok(ws.on_upgrade(move |socket| connection(socket, state, identity, skill)))
+}
+
+async fn connection(socket: WebSocket, state: Arc<AppState>, Identity(identity): Identity, skill: Option<Skill>) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let client_id = match create_session(&state, &identity, skill) {
+        Ok(client_id) => client_id,
+        Err(e) => {
+            let _ = sender.send(Message::Text(format!("info string {}", e).into())).await;
+            let _ = sender.send(Message::Close(None)).await;
+            return;
         }
-    }
+    };
 
     println!("connection established with {}", client_id);
 
-    let _ = sender.send(Message::Text(format!("established:{}", client_id).into())).await;
+    // the socket is written from one task, responses and search progress both go through it
+    let (outgoing, mut outgoing_receiver) = mpsc::unbounded_channel::<Message>();
+    let _ = outgoing.send(Message::Text(format!("established:{}", client_id).into()));
 
-    while let Some(Ok(msg)) = receiver.next().await {
-        if let Message::Text(text) = msg {
-            if text.trim().is_empty() {
-                continue;
+    tokio::spawn(async move {
+        while let Some(message) = outgoing_receiver.recv().await {
+            let close = matches!(message, Message::Close(_));
+
+            if sender.send(message).await.is_err() || close {
+                break;
             }
+        }
+    });
 
-            let state = Arc::clone(&state);
-            let client_id_clone = client_id.clone();
-            let text = text.clone();
-            
-            let responses = match timeout(Duration::from_secs(30), 
-                tokio::task::spawn(async move {
-                    process_command(&state, &client_id_clone, &text).await
-                })
-            ).await {
-                Ok(Ok(responses)) => responses,
-                Ok(Err(e)) => {
-                    eprintln!("Task error for client {}: {:?}", client_id, e);
-                    vec!["info string Internal server error".to_string()]
-                },
-                Err(_) => {
-                    eprintln!("Command timed out for client {}", client_id);
-                    vec!["info string Processing timed out".to_string()]
-                }
-            };
+    // commands run one after another, the socket is still read meanwhile so a search can be stopped
+    let (commands, mut command_receiver) = mpsc::unbounded_channel::<String>();
+
+    tokio::spawn({
+        let state = Arc::clone(&state);
+        let identity = identity.clone();
+        let client_id = client_id.clone();
+        let outgoing = outgoing.clone();
 
-            for response in responses {
-                if let Err(_) = sender.send(Message::Text(response.into())).await {
-                    break;
+        async move {
+            while let Some(text) = command_receiver.recv().await {
+                let (info_sender, mut info_receiver) = mpsc::unbounded_channel::<SearchInfo>();
+                let progress = outgoing.clone();
+
+                // ends once the command is done with the sender
+                let forward = tokio::spawn(async move {
+                    while let Some(info) = info_receiver.recv().await {
+                        let _ = progress.send(Message::Text(info.to_uci().into()));
+                    }
+                });
+
+                let result = process_command(&state, &identity, &client_id, &text, Some(info_sender)).await;
+                let _ = forward.await;
+
+                match result {
+                    Ok(responses) => {
+                        for response in responses {
+                            let _ = outgoing.send(Message::Text(response.into()));
+                        }
+                    },
+                    // evicted while the socket was idle
+                    Err(e) if e.status == StatusCode::NOT_FOUND => {
+                        let _ = outgoing.send(Message::Text(format!("info string {}", e.message).into()));
+                        let _ = outgoing.send(Message::Close(None));
+                        break;
+                    },
+                    Err(e) => {
+                        let _ = outgoing.send(Message::Text(format!("info string {}", e.message).into()));
+                    }
                 }
             }
-        } else if let Message::Close(_) = msg {
-            break;
+        }
+    });
+
+    while let Some(Ok(msg)) = receiver.next().await {
+        match msg {
+            Message::Text(text) if text.trim() == "stop" => {
+                if let Ok(responses) = process_command(&state, &identity, &client_id, "stop", None).await {
+                    for response in responses {
+                        let _ = outgoing.send(Message::Text(response.into()));
+                    }
+                }
+            },
+            Message::Text(text) if !text.trim().is_empty() && commands.send(text.to_string()).is_err() => break,
+            Message::Close(_) => break,
+            _ => {}
         }
     }
 
-    match timeout(Duration::from_secs(5), async {
+    // nobody is waiting for the answer of a search that is still running
+    let mut protocols = match state.protocols.lock() {
+        Ok(p) => p,
+        Err(e) => e.into_inner(),
+    };
+
+    if let Some(session) = protocols.remove(&client_id, &identity) {
+        session.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+// search progress goes to info when given, otherwise it is dropped
+// searches count against the limits of identity
+async fn process_command(state: &Arc<AppState>, identity: &str, client_id: &str, command: &str, info: Option<UnboundedSender<SearchInfo>>) -> Result<Vec<String>, ApiError> {
+    // the map is only locked to find the session, the command holds the session's own lock
+    let (protocol, stop) = {
         let mut protocols = match state.protocols.lock() {
             Ok(p) => p,
             Err(e) => e.into_inner(),
         };
-        protocols.remove(&client_id);
-    }).await {
-        Ok(_) => {},
-        Err(_) => eprintln!("Timeout removing client {}", client_id),
+
+        let session = protocols.touch(client_id, identity).map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e.to_string()))?;
+        (Arc::clone(&session.protocol), Arc::clone(&session.stop))
+    };
+
+    let command = command.trim().to_string();
+
+    // does not wait for the session, it is what the running search is waiting for
+    if command == "stop" {
+        stop.store(true, Ordering::Relaxed);
+        return Ok(vec!["ok".to_string()]);
     }
-}
 
-async fn process_command(state: &Arc<AppState>, client_id: &str, command: &str) -> Vec<String> {
-    let protocols_result: Result<std::sync::MutexGuard<'_, HashMap<String, UciProtocol>>, _> = match timeout(Duration::from_secs(5), async {
-        match state.protocols.lock() {
-            Ok(protocols) => Ok::<_, std::sync::PoisonError<std::sync::MutexGuard<'_, HashMap<String, UciProtocol>>>>(protocols),
-            Err(poisoned) => {
-                eprintln!("Recovered from poisoned protocols for {}", client_id);
-                Ok(poisoned.into_inner())
+    let search = command.split_whitespace().next() == Some("go");
+
+    let run = move || {
+        let mut protocol = match protocol.lock() {
+            Ok(p) => p,
+            Err(e) => e.into_inner(),
+        };
+
+        let start = Instant::now();
+        let responses = run_command(&mut protocol, &command, info);
+
+        // every thread of the search counts
+        (responses, start.elapsed() * protocol.engine.search_threads() as u32)
+    };
+
+    if !search {
+        return Ok(match tokio::task::spawn_blocking(run).await {
+            Ok((responses, _)) => responses,
+            Err(e) => {
+                eprintln!("Task error for client {}: {:?}", client_id, e);
+                vec!["info string Internal server error".to_string()]
             }
-        }
-    }).await {
+        });
+    }
+
+    let Ok(Ok(_permit)) = timeout(Duration::from_secs(COMMAND_TIMEOUT), state.searches.acquire()).await else {
+        return Ok(vec!["info string Server busy, try again later".to_string()]);
+    };
+
+    // a search that never got to run is not counted
+    state.auth.start_search(identity, Instant::now())?;
+
+    // a stop sent before this search belonged to an earlier one
+    stop.store(false, Ordering::Relaxed);
+    let mut task = tokio::task::spawn_blocking(run);
+
+    let result = match timeout(Duration::from_secs(COMMAND_TIMEOUT), &mut task).await {
         Ok(result) => result,
         Err(_) => {
-            eprintln!("Timeout acquiring lock for client {}", client_id);
-            return vec!["info string Server busy, try again later".to_string()];
+            eprintln!("Search timed out for client {}", client_id);
+
+            // a stopped search still answers with its best move
+            stop.store(true, Ordering::Relaxed);
+            task.await
         }
     };
-    
-    let mut protocols = match protocols_result {
-        Ok(protocols) => protocols,
-        Err(_) => return vec!["info string Internal server error".to_string()],
-    };
-    let protocol = protocols.entry(client_id.to_string()).or_insert_with(UciProtocol::new);
 
+    Ok(match result {
+        Ok((responses, time)) => {
+            state.auth.record_search(identity, time, Instant::now());
+            responses
+        },
+        Err(e) => {
+            eprintln!("Task error for client {}: {:?}", client_id, e);
+            vec!["info string Internal server error".to_string()]
+        }
+    })
+}
+
+fn run_command(protocol: &mut UciProtocol, command: &str, info: Option<UnboundedSender<SearchInfo>>) -> Vec<String> {
     match command.trim() {
         "uci" => {
             let mut responses = Vec::new();
@@ -222,23 +386,33 @@ async fn process_command(state: &Arc<AppState>, client_id: &str, command: &str)
             responses.push("id author ggod".to_string());
             responses.push("option name EngineType type combo default Minimax var Minimax var MCTS".to_string());
             responses.push("option name EnableBook type check default false".to_string());
+            responses.push("option name BookVariety type combo default Best var Best var Weighted var Score".to_string());
+            responses.push(format!("option name BookDepth type spin default {} min 0 max 1000", DEFAULT_BOOK_DEPTH));
+            responses.push("option name BookMinGames type spin default 1 min 1 max 10000".to_string());
+            responses.push("option name UseNNUE type check default false".to_string());
+            responses.push(format!("option name Move Overhead type spin default {} min 0 max 5000", DEFAULT_MOVE_OVERHEAD));
+            responses.push(format!("option name Hash type spin default {} min 1 max {}", protocol.engine.hash_size, protocol.max_hash_size));
+            responses.push(format!("option name Threads type spin default 1 min 1 max {}", protocol.max_threads));
+            responses.push(format!("option name MCTS Node Limit type spin default {} min 1000 max {}", protocol.engine.mcts_options.max_nodes, protocol.max_mcts_nodes));
+            responses.push("option name MCTS Selection type combo default UCB1 var UCB1 var PUCT".to_string());
+            responses.push("option name MCTS Prior type combo default MoveOrdering var MoveOrdering var Evaluation".to_string());
+            responses.push("option name MCTS Leaf type combo default Quiescence var Quiescence var Minimax var Rollout".to_string());
+            responses.push(format!("option name Skill Level type spin default {} min 0 max {}", protocol.skill_level, MAX_SKILL_LEVEL));
+            responses.push(format!("option name UCI_LimitStrength type check default {}", protocol.limit_strength));
+            responses.push(format!("option name UCI_Elo type spin default {} min {} max {}", protocol.elo, SKILL_MIN_ELO, SKILL_MAX_ELO));
+            responses.push("option name UCI_Opponent type string default <empty>".to_string());
             responses.push("uciok".to_string());
-            return responses;
+            responses
         },
         "isready" => {
-            return vec!["readyok".to_string()];
+            vec!["readyok".to_string()]
         },
         "ucinewgame" => {
-            let book = protocol.engine.book.clone();
-            let enable_book = protocol.engine.enable_book;
-            *protocol = UciProtocol::new();
-            protocol.engine.book = book;
-            protocol.engine.set_book_enabled(enable_book);
-            return vec!["ok".to_string()];
+            protocol.new_game();
+            vec!["ok".to_string()]
         },
-        "stop" => {
-            protocol.engine.stop();
-            return vec!["ok".to_string()];
+        "pgn" => {
+            vec![protocol.game().to_string()]
         },
         cmd if cmd.starts_with("position") => {
             let mut writer = ResponseWriter::new();
@@ -249,41 +423,83 @@ async fn process_command(state: &Arc<AppState>, client_id: &str, command: &str)
             
             writer.flush().unwrap();
 
-            return writer.get_messages();
+            writer.get_messages()
         },
         cmd if cmd.starts_with("go") => {
             let mut writer = ResponseWriter::new();
-            
-            if let Err(e) = protocol.handle_go(cmd, &mut writer) {
+
+            protocol.engine.set_info_sender(info);
+            let result = protocol.handle_go(cmd, &mut writer);
+            protocol.engine.set_info_sender(None);
+
+            if let Err(e) = result {
                 return vec![format!("info string Error executing go command: {}", e)];
             }
             
             writer.flush().unwrap();
 
-            return writer.get_messages();
+            writer.get_messages()
         },
         cmd if cmd.starts_with("setoption") => {
             let mut writer = ResponseWriter::new();
-            
+
+            if let Some(name) = UciProtocol::option_name(cmd).filter(|name| !SESSION_OPTIONS.contains(&name.as_str())) {
+                return vec![format!("info string Option {} can not be set on the server", name)];
+            }
+
             if let Err(e) = protocol.set_option(cmd, &mut writer) {
                 return vec![format!("info string Error executing setoption command: {}", e)];
             }
 
             writer.flush().unwrap();
 
-            return writer.get_messages();
+            writer.get_messages()
         },
         "quit" => {
-            return vec!["Disconnecting".to_string()];
+            vec!["Disconnecting".to_string()]
         },
         _ => {
-            return vec![format!("info string Unknown command: {}", command)];
+            vec![format!("info string Unknown command: {}", command)]
         }
     }
 }
 
-async fn command(State(state): State<Arc<AppState>>, Json(request): Json<UciRequest>) -> Result<Json<UciResponse>, (StatusCode, String)> {
-    let response = process_command(&state, &request.client_id, &request.command).await;
+async fn pgn(State(state): State<Arc<AppState>>, Extension(Identity(identity)): Extension<Identity>, AxumPath(client_id): AxumPath<String>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let protocol = {
+        let protocols = match state.protocols.lock() {
+            Ok(p) => p,
+            Err(e) => e.into_inner(),
+        };
+
+        protocols.get(&client_id, &identity).map(|session| Arc::clone(&session.protocol))
+    };
+
+    let Some(protocol) = protocol else {
+        return Err((StatusCode::NOT_FOUND, format!("Unknown client {}", client_id)));
+    };
+
+    // waits for a running search to finish
+    let game = tokio::task::spawn_blocking(move || match protocol.lock() {
+        Ok(p) => p.game().to_string(),
+        Err(e) => e.into_inner().game().to_string(),
+    }).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(([(header::CONTENT_TYPE, "application/x-chess-pgn")], game))
+}
+
+async fn command(State(state): State<Arc<AppState>>, Extension(Identity(identity)): Extension<Identity>, request: Result<Json<UciRequest>, JsonRejection>) -> Result<Json<UciResponse>, ApiError> {
+    let Json(request) = request?;
+    let (info_sender, mut info_receiver) = mpsc::unbounded_channel::<SearchInfo>();
+    let lines = process_command(&state, &identity, &request.client_id, &request.command, Some(info_sender)).await?;
+
+    // without a stream the progress comes before the rest of the response
+    let mut response = Vec::new();
+
+    while let Ok(info) = info_receiver.try_recv() {
+        response.push(info.to_uci());
+    }
+
+    response.extend(lines);
 
     Ok(Json(UciResponse {
         client_id: request.client_id,
@@ -291,6 +507,155 @@ async fn command(State(state): State<Arc<AppState>>, Json(request): Json<UciRequ
     }))
 }
 
+// a session of identity set up like the template, the books are shared, never copied
+fn create_session(state: &AppState, identity: &str, skill: Option<Skill>) -> Result<String, SessionError> {
+    let mut protocol = UciProtocol::new();
+
+    if let Some(skill) = skill {
+        protocol.set_skill(skill);
+    }
+
+    {
+        let template = match state.template.lock() {
+            Ok(t) => t,
+            Err(e) => e.into_inner(),
+        };
+
+        protocol.engine.set_book_enabled(true);
+        protocol.engine.book = template.engine.book.clone();
+        protocol.engine.polyglot = template.engine.polyglot.clone();
+        protocol.engine.set_learning(template.engine.learning.clone());
+        protocol.engine.set_record_learning(template.engine.record_learning);
+        protocol.engine.set_tablebase(template.engine.tablebase.clone());
+    }
+
+    let mut protocols = match state.protocols.lock() {
+        Ok(p) => p,
+        Err(e) => e.into_inner(),
+    };
+
+    protocols.create(protocol, identity)
+}
+
+async fn new_session(State(state): State<Arc<AppState>>, Extension(Identity(identity)): Extension<Identity>, Query(query): Query<SessionQuery>) -> Result<(StatusCode, Json<SessionResponse>), ApiError> {
+    let skill = query.skill()?;
+
+    match create_session(&state, &identity, skill) {
+        Ok(client_id) => {
+            println!("session created for {}", client_id);
+            Ok((StatusCode::CREATED, Json(SessionResponse { client_id, skill_level: skill.unwrap_or_default().level })))
+        },
+        Err(e) => Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, e.to_string()))
+    }
+}
+
+async fn delete_session(State(state): State<Arc<AppState>>, Extension(Identity(identity)): Extension<Identity>, AxumPath(client_id): AxumPath<String>) -> Result<StatusCode, ApiError> {
+    let mut protocols = match state.protocols.lock() {
+        Ok(p) => p,
+        Err(e) => e.into_inner(),
+    };
+
+    match protocols.remove(&client_id, &identity) {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::new(StatusCode::NOT_FOUND, SessionError::Unknown(client_id).to_string()))
+    }
+}
+
+async fn metrics(State(state): State<Arc<AppState>>) -> Json<MetricsResponse> {
+    let protocols = match state.protocols.lock() {
+        Ok(p) => p,
+        Err(e) => e.into_inner(),
+    };
+
+    let limits = protocols.limits();
+
+    Json(MetricsResponse {
+        sessions: protocols.len(),
+        // held by the command the session is running
+        busy: protocols.iter().filter(|(_, session)| session.protocol.try_lock().is_err()).count(),
+        commands: protocols.iter().map(|(_, session)| session.commands).sum(),
+        max_sessions: limits.max_sessions,
+        idle_timeout: limits.idle_timeout.as_secs(),
+        hash_size: limits.hash_size,
+        max_searches: state.max_searches,
+        searches: state.max_searches - state.searches.available_permits()
+    })
+}
+
+// a fresh engine for every request, sharing the tables of the template but not its book
+fn api_engine(state: &AppState) -> Engine {
+    let template = match state.template.lock() {
+        Ok(t) => t,
+        Err(e) => e.into_inner(),
+    };
+
+    let mut engine = Engine::new(EngineType::Minimax, false);
+    engine.set_tablebase(template.engine.tablebase.clone());
+    engine.set_network(template.engine.network.clone());
+    engine.set_use_nnue(template.engine.use_nnue);
+    engine
+}
+
+async fn analyse(State(state): State<Arc<AppState>>, Extension(Identity(identity)): Extension<Identity>, request: Result<Json<AnalyseRequest>, JsonRejection>) -> Result<Json<AnalyseResponse>, ApiError> {
+    let Json(request) = request?;
+    let mut engine = api_engine(&state);
+    let stop = engine.stop_signal();
+
+    let Ok(Ok(_permit)) = timeout(Duration::from_secs(COMMAND_TIMEOUT), state.searches.acquire()).await else {
+        return Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Server busy, try again later"));
+    };
+
+    state.auth.start_search(&identity, Instant::now())?;
+
+    let start = Instant::now();
+    let mut task = tokio::task::spawn_blocking(move || api::analyse(&mut engine, &request));
+
+    let result = match timeout(Duration::from_secs(COMMAND_TIMEOUT), &mut task).await {
+        Ok(result) => result,
+        Err(_) => {
+            stop.store(true, Ordering::Relaxed);
+            task.await
+        }
+    };
+
+    state.auth.record_search(&identity, start.elapsed(), Instant::now());
+
+    match result {
+        Ok(response) => response.map(Json),
+        Err(e) => Err(ApiError::internal(e.to_string()))
+    }
+}
+
+async fn legal_moves(request: Result<Json<PositionRequest>, JsonRejection>) -> Result<Json<LegalMovesResponse>, ApiError> {
+    let Json(request) = request?;
+    api::legal_moves(&request).map(Json)
+}
+
+async fn evaluate(request: Result<Json<PositionRequest>, JsonRejection>) -> Result<Json<EvaluateResponse>, ApiError> {
+    let Json(request) = request?;
+    api::evaluation(&request).map(Json)
+}
+
+async fn health(State(state): State<Arc<AppState>>) -> Json<HealthResponse> {
+    let clients = match state.protocols.lock() {
+        Ok(p) => p.len(),
+        Err(e) => e.into_inner().len(),
+    };
+
+    let template = match state.template.lock() {
+        Ok(t) => t,
+        Err(e) => e.into_inner(),
+    };
+
+    Json(HealthResponse {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        clients,
+        book: template.engine.book.as_ref().map(|book| book.len()),
+        tablebases: template.engine.tablebase.as_ref().map(|tablebase| tablebase.len())
+    })
+}
+
 pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
     dotenv().ok();
 
@@ -307,14 +672,100 @@ pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
         Err(e) => eprintln!("Failed to load opening book: {}", e),
     }
 
+    if let Ok(learning_path) = env::var("BOOK_LEARNING_PATH") {
+        match BookLearning::open(Path::new(&learning_path)) {
+            Ok(learning) => {
+                println!("Learning book moves in {} with {} entries", learning_path, learning.len());
+                template.engine.set_learning(Some(Arc::new(Mutex::new(learning))));
+            },
+            Err(e) => eprintln!("Failed to open book learning file: {}", e),
+        }
+    }
+
+    // anyone can send a session a made up game, so only a trusted deployment lets them teach the book
+    let session_learning = env::var("SESSION_BOOK_LEARNING").is_ok_and(|value| value == "true");
+    template.engine.set_record_learning(session_learning);
+
+    if let Ok(syzygy_path) = env::var("SYZYGY_PATH") {
+        println!("Loading tablebases from {}", syzygy_path);
+
+        match template.engine.load_tablebase(&syzygy_path) {
+            Ok(tables) => println!("Found {} tablebases", tables),
+            Err(e) => eprintln!("Failed to load tablebases: {}", e),
+        }
+    }
+
+    let limits = SessionLimits::from_env();
+    println!("Allowing {} sessions, {} for each client, of {} MB and {} threads, idle ones are closed after {} s", limits.max_sessions, limits.max_sessions_per_owner, limits.hash_size, limits.max_threads, limits.idle_timeout.as_secs());
+
+    let max_searches = env::var("MAX_SEARCHES").ok()
+        .and_then(|searches| searches.parse().ok())
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get()))
+        .max(1);
+
+    println!("Running up to {} searches at once", max_searches);
+
+    let auth = Auth::from_env();
+    let rate_limits = auth.limits();
+
+    let trust_proxy = env::var("TRUST_PROXY").is_ok_and(|value| value == "true");
+
+    if auth.enabled() {
+        println!("Requests need one of the API keys");
+    } else if trust_proxy {
+        println!("Telling clients apart by the address the proxy forwards");
+    }
+
+    if let Some(searches) = rate_limits.searches {
+        println!("Allowing {} searches per minute for each client", searches);
+    }
+
+    if let Some(cpu_time) = rate_limits.cpu_time {
+        println!("Allowing {} s of search time per hour for each client", cpu_time.as_secs());
+    }
+
     let state = Arc::new(AppState {
-        protocols: Mutex::new(HashMap::new()),
-        template: Mutex::new(template)
+        auth,
+        protocols: Mutex::new(Sessions::new(limits)),
+        template: Mutex::new(template),
+        searches: Semaphore::new(max_searches),
+        max_searches,
+        trust_proxy
+    });
+
+    let eviction_state = Arc::clone(&state);
+    tokio::spawn(async move {
+        let mut ticks = interval(Duration::from_secs(SESSION_EVICTION_INTERVAL).min(limits.idle_timeout).max(Duration::from_secs(1)));
+
+        loop {
+            ticks.tick().await;
+
+            let mut protocols = match eviction_state.protocols.lock() {
+                Ok(p) => p,
+                Err(e) => e.into_inner(),
+            };
+
+            for client_id in protocols.evict_idle(Instant::now()) {
+                println!("session {} expired", client_id);
+            }
+
+            eviction_state.auth.evict(Instant::now());
+        }
     });
 
     let app = Router::new()
         .route("/", get(websocket_handler))
         .route("/uci", post(command))
+        .route("/pgn/{client_id}", get(pgn))
+        .route("/analyse", post(analyse))
+        .route("/legal-moves", post(legal_moves))
+        .route("/evaluate", post(evaluate))
+        .route("/sessions", post(new_session))
+        .route("/sessions/{client_id}", delete(delete_session))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(Arc::clone(&state), authenticate))
+        // open for the health checks of the deployment
+        .route("/health", get(health))
         .with_state(state);
 
     let host = env::var("SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
@@ -323,7 +774,7 @@ pub async fn run_server() -> Result<(), Box<dyn std::error::Error>> {
     let address = format!("{}:{}", host, port);
     let listener = TcpListener::bind(&address).await?;
     println!("Chess engine server listening on {}", address);
-    axum::serve(listener, app).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,194 @@
+use std::{collections::HashMap, env, fmt, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard}, time::{Duration, Instant}};
+
+use crate::{mcts::MctsOptions, protocol::UciProtocol, r#const::{DEFAULT_MAX_SESSIONS, DEFAULT_SESSIONS_PER_OWNER, DEFAULT_SESSION_HASH_SIZE, DEFAULT_SESSION_MCTS_NODES, DEFAULT_SESSION_THREADS, DEFAULT_SESSION_TTL, MAX_THREADS}};
+
+// The clients of the server. Every session holds an engine with its own tables, so
+// their number and size are bounded and sessions nobody used for a while are dropped.
+// Each session has its own lock, the map is only locked to find one. A session belongs to
+// the identity that created it, to any other it does not exist.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionLimits {
+    pub max_sessions: usize,
+    // one identity cannot take all the sessions of the server
+    pub max_sessions_per_owner: usize,
+    pub idle_timeout: Duration,
+    // megabytes for the minimax tables of one session
+    pub hash_size: usize,
+    pub max_mcts_nodes: usize,
+    // a search holds one permit of the server however many threads it runs
+    pub max_threads: usize
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        SessionLimits {
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            max_sessions_per_owner: DEFAULT_SESSIONS_PER_OWNER,
+            idle_timeout: Duration::from_secs(DEFAULT_SESSION_TTL),
+            hash_size: DEFAULT_SESSION_HASH_SIZE,
+            max_mcts_nodes: DEFAULT_SESSION_MCTS_NODES,
+            max_threads: DEFAULT_SESSION_THREADS
+        }
+    }
+}
+
+impl SessionLimits {
+    // MAX_SESSIONS, MAX_SESSIONS_PER_CLIENT, SESSION_TTL in seconds, SESSION_HASH_MB, SESSION_MCTS_NODES and SESSION_THREADS
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok()?.parse().ok()
+        }
+
+        let defaults = SessionLimits::default();
+
+        SessionLimits {
+            max_sessions: var("MAX_SESSIONS").unwrap_or(defaults.max_sessions),
+            max_sessions_per_owner: var::<usize>("MAX_SESSIONS_PER_CLIENT").unwrap_or(defaults.max_sessions_per_owner).max(1),
+            idle_timeout: var("SESSION_TTL").map_or(defaults.idle_timeout, Duration::from_secs),
+            hash_size: var::<usize>("SESSION_HASH_MB").unwrap_or(defaults.hash_size).max(1),
+            max_mcts_nodes: var::<usize>("SESSION_MCTS_NODES").unwrap_or(defaults.max_mcts_nodes).max(1000),
+            max_threads: var::<usize>("SESSION_THREADS").unwrap_or(defaults.max_threads).clamp(1, MAX_THREADS)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionError {
+    Full(usize),
+    Unknown(String)
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::Full(max) => write!(f, "no more than {} sessions are allowed", max),
+            SessionError::Unknown(id) => write!(f, "unknown session {}", id)
+        }
+    }
+}
+
+impl std::error::Error for SessionError {}
+
+pub struct Session {
+    pub protocol: Arc<Mutex<UciProtocol>>,
+    pub owner: String,
+    // stops the session's search without waiting for its lock
+    pub stop: Arc<AtomicBool>,
+    pub created: Instant,
+    pub last_active: Instant,
+    pub commands: u64
+}
+
+impl Session {
+    pub fn lock(&self) -> MutexGuard<'_, UciProtocol> {
+        match self.protocol.lock() {
+            Ok(protocol) => protocol,
+            Err(e) => e.into_inner()
+        }
+    }
+}
+
+pub struct Sessions {
+    sessions: HashMap<String, Session>,
+    limits: SessionLimits
+}
+
+impl Sessions {
+    pub fn new(limits: SessionLimits) -> Self {
+        Sessions {
+            sessions: HashMap::new(),
+            limits
+        }
+    }
+
+    pub fn limits(&self) -> SessionLimits {
+        self.limits
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    // adds the protocol under a new id for owner, held to the limits of a session
+    pub fn create(&mut self, mut protocol: UciProtocol, owner: &str) -> Result<String, SessionError> {
+        if self.sessions.len() >= self.limits.max_sessions {
+            return Err(SessionError::Full(self.limits.max_sessions));
+        }
+
+        if self.sessions.values().filter(|session| session.owner == owner).count() >= self.limits.max_sessions_per_owner {
+            return Err(SessionError::Full(self.limits.max_sessions_per_owner));
+        }
+
+        protocol.max_hash_size = self.limits.hash_size;
+        protocol.max_mcts_nodes = self.limits.max_mcts_nodes;
+        protocol.max_threads = self.limits.max_threads;
+
+        if protocol.engine.hash_size > self.limits.hash_size {
+            protocol.engine.set_hash_size(self.limits.hash_size);
+        }
+
+        if protocol.engine.threads > self.limits.max_threads {
+            protocol.engine.set_threads(self.limits.max_threads);
+        }
+
+        if protocol.engine.mcts_options.max_nodes > self.limits.max_mcts_nodes {
+            protocol.engine.set_mcts_options(MctsOptions { max_nodes: self.limits.max_mcts_nodes, ..protocol.engine.mcts_options });
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Instant::now();
+
+        let stop = protocol.engine.stop_signal();
+        self.sessions.insert(id.clone(), Session { protocol: Arc::new(Mutex::new(protocol)), owner: owner.to_string(), stop, created: now, last_active: now, commands: 0 });
+
+        Ok(id)
+    }
+
+    pub fn get(&self, id: &str, owner: &str) -> Option<&Session> {
+        self.sessions.get(id).filter(|session| session.owner == owner)
+    }
+
+    // the session for a command, which keeps it alive
+    pub fn touch(&mut self, id: &str, owner: &str) -> Result<&mut Session, SessionError> {
+        let session = self.sessions.get_mut(id)
+            .filter(|session| session.owner == owner)
+            .ok_or_else(|| SessionError::Unknown(id.to_string()))?;
+
+        session.last_active = Instant::now();
+        session.commands += 1;
+
+        Ok(session)
+    }
+
+    pub fn remove(&mut self, id: &str, owner: &str) -> Option<Session> {
+        self.get(id, owner)?;
+        self.sessions.remove(id)
+    }
+
+    // drops the sessions idle since before now - timeout and returns their ids, a search they run is stopped
+    pub fn evict_idle(&mut self, now: Instant) -> Vec<String> {
+        let timeout = self.limits.idle_timeout;
+
+        let expired: Vec<String> = self.sessions.iter()
+            .filter(|(_, session)| now.saturating_duration_since(session.last_active) >= timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        for id in &expired {
+            if let Some(session) = self.sessions.remove(id) {
+                session.stop.store(true, Ordering::Relaxed);
+            }
+        }
+
+        expired
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Session)> {
+        self.sessions.iter()
+    }
+}
\ No newline at end of file
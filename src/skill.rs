@@ -0,0 +1,101 @@
+use rand::Rng;
+
+use crate::r#const::{MAX_SKILL_LEVEL, PAWN_VALUE, SKILL_BASE_NODES, SKILL_MAX_ELO, SKILL_MIN_ELO, SKILL_MULTIPV, SKILL_TIME_STEP};
+
+// Playing below full strength. A lower level searches shallower and shorter and
+// then picks among the best few moves, a worse one more often the lower it goes.
+
+// the strengths a client can ask for by name
+pub const PRESETS: [(&str, u8); 5] = [
+    ("beginner", 0),
+    ("casual", 5),
+    ("intermediate", 10),
+    ("advanced", 15),
+    ("expert", MAX_SKILL_LEVEL)
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    pub level: u8
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Skill { level: MAX_SKILL_LEVEL }
+    }
+}
+
+impl Skill {
+    pub fn new(level: u8) -> Self {
+        Skill { level: level.min(MAX_SKILL_LEVEL) }
+    }
+
+    // spread evenly over the levels, the ratings are rough and not measured against other engines
+    pub fn from_elo(elo: u32) -> Self {
+        let elo = elo.clamp(SKILL_MIN_ELO, SKILL_MAX_ELO);
+        let level = ((elo - SKILL_MIN_ELO) as f64 * MAX_SKILL_LEVEL as f64 / (SKILL_MAX_ELO - SKILL_MIN_ELO) as f64).round();
+
+        Skill::new(level as u8)
+    }
+
+    // a preset name or a level
+    pub fn preset(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+
+        match PRESETS.iter().find(|(preset, _)| *preset == name) {
+            Some(&(_, level)) => Some(Skill::new(level)),
+            None => name.parse::<u8>().ok().filter(|level| *level <= MAX_SKILL_LEVEL).map(Skill::new)
+        }
+    }
+
+    pub fn elo(&self) -> u32 {
+        SKILL_MIN_ELO + (SKILL_MAX_ELO - SKILL_MIN_ELO) * self.level as u32 / MAX_SKILL_LEVEL as u32
+    }
+
+    // full strength is not limited at all
+    pub fn enabled(&self) -> bool {
+        self.level < MAX_SKILL_LEVEL
+    }
+
+    pub fn depth(&self) -> u8 {
+        1 + self.level / 2
+    }
+
+    // once the lines have taken this many nodes they are not searched deeper, minimax only
+    pub fn nodes(&self) -> u64 {
+        SKILL_BASE_NODES << (self.level / 2)
+    }
+
+    // milliseconds for the whole move
+    pub fn move_time(&self) -> u64 {
+        SKILL_TIME_STEP * (self.level as u64 + 1)
+    }
+
+    pub fn lines(&self) -> usize {
+        SKILL_MULTIPV
+    }
+
+    // the index of the line to play from centipawn scores sorted best first. Every line gets a random
+    // push that grows with its distance from the best one and with the weakness, so a weak
+    // level drifts towards worse moves while a strong one rarely leaves the best
+    pub fn pick<R: Rng>(&self, scores: &[i32], rng: &mut R) -> usize {
+        let Some(&top) = scores.first() else {
+            return 0;
+        };
+
+        let weakness = 120 - 2 * self.level as i64;
+        let delta = (top as i64 - scores[scores.len() - 1] as i64).min(PAWN_VALUE as i64);
+
+        let mut best = (i64::MIN, 0);
+
+        for (index, &score) in scores.iter().enumerate() {
+            let push = (weakness * (top as i64 - score as i64) + delta * rng.random_range(0..weakness)) / 128;
+
+            if score as i64 + push > best.0 {
+                best = (score as i64 + push, index);
+            }
+        }
+
+        best.1
+    }
+}
\ No newline at end of file
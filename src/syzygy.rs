@@ -0,0 +1,1251 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::board::Board;
+use crate::moves::{Move, MoveType};
+use crate::piece::{PieceColor, PieceType};
+
+// Syzygy tablebase probing, following the layout of the reference implementation
+// used by Stockfish and Fathom. Squares are numbered a1 = 0 ... h8 = 63 inside this
+// module, pieces use the file encoding (1-6 white pawn..king, 9-14 black).
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+const TB_PIECES: usize = 7;
+
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2
+}
+
+impl std::ops::Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            v if v <= -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win
+        }
+    }
+
+    fn sign(self) -> i32 {
+        (self as i32).signum()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ProbeState {
+    Ok,
+    ZeroingBestMove,
+    ChangeStm,
+    Fail
+}
+
+#[derive(Debug, Clone)]
+pub struct RootProbe {
+    pub m: Move,
+    pub wdl: Wdl,
+    pub dtz: i32
+}
+
+struct Tables {
+    binomial: [[u64; 64]; 7],
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+    map_pawns: [usize; 64],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6]
+}
+
+static TABLES: OnceLock<Tables> = OnceLock::new();
+
+fn file_of(square: usize) -> usize { square & 7 }
+fn rank_of(square: usize) -> usize { square >> 3 }
+fn off_a1h8(square: usize) -> i32 { rank_of(square) as i32 - file_of(square) as i32 }
+fn flip_file(square: usize) -> usize { square ^ 7 }
+fn flip_rank(square: usize) -> usize { square ^ 56 }
+
+fn square_distance(a: usize, b: usize) -> usize {
+    file_of(a).abs_diff(file_of(b)).max(rank_of(a).abs_diff(rank_of(b)))
+}
+
+fn init_tables() -> Tables {
+    let mut tables = Tables {
+        binomial: [[0; 64]; 7],
+        map_b1h1h7: [0; 64],
+        map_a1d1d4: [usize::MAX; 64],
+        map_kk: [[0; 64]; 10],
+        map_pawns: [0; 64],
+        lead_pawn_idx: [[0; 64]; 6],
+        lead_pawns_size: [[0; 4]; 6]
+    };
+
+    let mut code = 0;
+    for s in 0..64 {
+        if off_a1h8(s) < 0 {
+            tables.map_b1h1h7[s] = code;
+            code += 1;
+        }
+    }
+
+    let mut diagonal = vec![];
+    code = 0;
+    for s in 0..=27 {
+        if off_a1h8(s) < 0 && file_of(s) <= 3 {
+            tables.map_a1d1d4[s] = code;
+            code += 1;
+        } else if off_a1h8(s) == 0 && file_of(s) <= 3 {
+            diagonal.push(s);
+        }
+    }
+    for s in diagonal {
+        tables.map_a1d1d4[s] = code;
+        code += 1;
+    }
+
+    let mut both_on_diagonal = vec![];
+    let mut code = 0;
+    for idx in 0..10 {
+        for s1 in 0..=27 {
+            if tables.map_a1d1d4[s1] != idx {
+                continue;
+            }
+
+            for s2 in 0..64 {
+                if square_distance(s1, s2) <= 1 || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                    continue;
+                } else if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                    both_on_diagonal.push((idx, s2));
+                } else {
+                    tables.map_kk[idx][s2] = code;
+                    code += 1;
+                }
+            }
+        }
+    }
+    for (idx, s2) in both_on_diagonal {
+        tables.map_kk[idx][s2] = code;
+        code += 1;
+    }
+
+    tables.binomial[0][0] = 1;
+    for n in 1..64 {
+        for k in 0..7 {
+            if k > n {
+                break;
+            }
+            tables.binomial[k][n] = (if k > 0 { tables.binomial[k - 1][n - 1] } else { 0 }) +
+                                    (if k < n { tables.binomial[k][n - 1] } else { 0 });
+        }
+    }
+
+    let mut available_squares = 47;
+    for lead_pawns_count in 1..=5 {
+        for file in 0..4 {
+            let mut idx = 0;
+
+            for rank in 1..7 {
+                let square = rank * 8 + file;
+
+                if lead_pawns_count == 1 {
+                    tables.map_pawns[square] = available_squares;
+                    available_squares -= 1;
+                    tables.map_pawns[flip_file(square)] = available_squares;
+                    available_squares = available_squares.saturating_sub(1);
+                }
+
+                tables.lead_pawn_idx[lead_pawns_count][square] = idx;
+                idx += tables.binomial[lead_pawns_count - 1][tables.map_pawns[square]];
+            }
+
+            tables.lead_pawns_size[lead_pawns_count][file] = idx;
+        }
+    }
+
+    tables
+}
+
+fn tables() -> &'static Tables {
+    TABLES.get_or_init(init_tables)
+}
+
+fn read_u16_le(bytes: &[u8], offset: usize) -> u16 {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => u16::from_le_bytes([b[0], b[1]]),
+        None => 0
+    }
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> u32 {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        None => 0
+    }
+}
+
+fn read_u32_be(bytes: &[u8], offset: usize) -> u32 {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => u32::from_be_bytes([b[0], b[1], b[2], b[3]]),
+        None => 0
+    }
+}
+
+fn read_u64_be(bytes: &[u8], offset: usize) -> u64 {
+    ((read_u32_be(bytes, offset) as u64) << 32) | read_u32_be(bytes, offset + 4) as u64
+}
+
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    pieces: [u8; TB_PIECES],
+    group_len: [usize; TB_PIECES + 1],
+    group_idx: [u64; TB_PIECES + 1],
+    sizeof_block: u64,
+    span: u64,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_length: usize,
+    block_length_size: usize,
+    num_blocks: u32,
+    data: usize,
+    min_sym_len: u8,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: usize,
+    map_idx: [u16; 4]
+}
+
+impl PairsData {
+    fn btree_left(&self, bytes: &[u8], sym: usize) -> usize {
+        let offset = self.btree + sym * 3;
+        (((bytes[offset + 1] & 0xF) as usize) << 8) | bytes[offset] as usize
+    }
+
+    fn btree_right(&self, bytes: &[u8], sym: usize) -> usize {
+        let offset = self.btree + sym * 3;
+        ((bytes[offset + 2] as usize) << 4) | (bytes[offset + 1] >> 4) as usize
+    }
+
+    fn block_length_at(&self, bytes: &[u8], block: usize) -> i64 {
+        read_u16_le(bytes, self.block_length + block * 2) as i64
+    }
+}
+
+struct TableData {
+    bytes: Vec<u8>,
+    // indexed by [file][side]
+    pairs: Vec<Vec<PairsData>>,
+    map: usize
+}
+
+struct Table {
+    key: String,
+    key2: String,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    pawn_count: [usize; 2],
+    wdl_path: Option<PathBuf>,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<TableData>>,
+    dtz: OnceLock<Option<TableData>>
+}
+
+fn piece_code(c: char) -> Option<u8> {
+    match c {
+        'P' => Some(1),
+        'N' => Some(2),
+        'B' => Some(3),
+        'R' => Some(4),
+        'Q' => Some(5),
+        'K' => Some(6),
+        _ => None
+    }
+}
+
+fn swap_key(key: &str) -> String {
+    match key.split_once('v') {
+        Some((white, black)) => format!("{}v{}", black, white),
+        None => key.to_string()
+    }
+}
+
+impl Table {
+    fn new(key: &str) -> Option<Table> {
+        let (white, black) = key.split_once('v')?;
+
+        if !white.starts_with('K') || !black.starts_with('K') {
+            return None;
+        }
+
+        let mut counts = [[0usize; 7]; 2];
+        for (side, pieces) in [white, black].iter().enumerate() {
+            for c in pieces.chars() {
+                counts[side][piece_code(c)? as usize] += 1;
+            }
+        }
+
+        let piece_count = white.len() + black.len();
+        if piece_count > TB_PIECES {
+            return None;
+        }
+
+        let white_pawns = counts[0][1];
+        let black_pawns = counts[1][1];
+
+        let has_unique_pieces = counts.iter().any(|side| (1..6).any(|code| side[code] == 1));
+
+        let leading_white = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if leading_white { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] };
+
+        Some(Table {
+            key: key.to_string(),
+            key2: swap_key(key),
+            piece_count,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces,
+            pawn_count,
+            wdl_path: None,
+            dtz_path: None,
+            wdl: OnceLock::new(),
+            dtz: OnceLock::new()
+        })
+    }
+
+    fn get_data(&self, dtz: bool) -> Option<&TableData> {
+        let (lock, path) = if dtz { (&self.dtz, &self.dtz_path) } else { (&self.wdl, &self.wdl_path) };
+
+        lock.get_or_init(|| {
+            let bytes = fs::read(path.as_ref()?).ok()?;
+            let data = self.parse(bytes, dtz);
+
+            if data.is_none() {
+                eprintln!("info string Corrupted tablebase file {:?}", path);
+            }
+
+            data
+        }).as_ref()
+    }
+
+    fn set_groups(&self, d: &mut PairsData, order: [usize; 2], file: usize) {
+        let tables = tables();
+
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns { 0 } else if self.has_unique_pieces { 3 } else { 2 };
+        d.group_len[n] = 1;
+
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    tables.lead_pawns_size[d.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                d.group_idx[1] = idx;
+                idx *= tables.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= tables.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+
+            k += 1;
+        }
+
+        d.group_idx[n] = idx;
+    }
+
+    fn parse(&self, bytes: Vec<u8>, dtz: bool) -> Option<TableData> {
+        let magic = if dtz { DTZ_MAGIC } else { WDL_MAGIC };
+        if bytes.len() < 6 || bytes[0..4] != magic {
+            return None;
+        }
+
+        let split = self.key != self.key2;
+        if (bytes[4] & 2 != 0) != self.has_pawns || (!dtz && (bytes[4] & 1 != 0) != split) {
+            return None;
+        }
+
+        let mut data = 5;
+
+        let sides = if !dtz && split { 2 } else { 1 };
+        let max_file = if self.has_pawns { 3 } else { 0 };
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+
+        let mut pairs: Vec<Vec<PairsData>> = Vec::with_capacity(max_file + 1);
+
+        for file in 0..=max_file {
+            let mut items = vec![PairsData::default(); sides];
+
+            let first = *bytes.get(data)?;
+            let second = if both_pawns { *bytes.get(data + 1)? } else { 0xFF };
+            let order = [
+                [(first & 0xF) as usize, (second & 0xF) as usize],
+                [(first >> 4) as usize, (second >> 4) as usize]
+            ];
+            data += 1 + both_pawns as usize;
+
+            for k in 0..self.piece_count {
+                let byte = *bytes.get(data)?;
+                for (i, item) in items.iter_mut().enumerate() {
+                    item.pieces[k] = if i == 1 { byte >> 4 } else { byte & 0xF };
+                }
+                data += 1;
+            }
+
+            for (i, item) in items.iter_mut().enumerate() {
+                self.set_groups(item, order[i], file);
+            }
+
+            pairs.push(items);
+        }
+
+        data += data & 1;
+
+        for items in pairs.iter_mut() {
+            for item in items.iter_mut() {
+                data = set_sizes(item, &bytes, data)?;
+            }
+        }
+
+        let mut map = 0;
+        if dtz {
+            map = data;
+
+            for items in pairs.iter_mut() {
+                let d = &mut items[0];
+
+                if d.flags & FLAG_MAPPED != 0 {
+                    if d.flags & FLAG_WIDE != 0 {
+                        data += data & 1;
+                        for i in 0..4 {
+                            d.map_idx[i] = ((data - map) / 2 + 1) as u16;
+                            data += 2 * read_u16_le(&bytes, data) as usize + 2;
+                        }
+                    } else {
+                        for i in 0..4 {
+                            d.map_idx[i] = (data - map + 1) as u16;
+                            data += *bytes.get(data)? as usize + 1;
+                        }
+                    }
+                }
+            }
+
+            data += data & 1;
+        }
+
+        for items in pairs.iter_mut() {
+            for item in items.iter_mut() {
+                item.sparse_index = data;
+                data += item.sparse_index_size * 6;
+            }
+        }
+
+        for items in pairs.iter_mut() {
+            for item in items.iter_mut() {
+                item.block_length = data;
+                data += item.block_length_size * 2;
+            }
+        }
+
+        for items in pairs.iter_mut() {
+            for item in items.iter_mut() {
+                data = (data + 0x3F) & !0x3F;
+                item.data = data;
+                data += item.num_blocks as usize * item.sizeof_block as usize;
+            }
+        }
+
+        if data > bytes.len() {
+            return None;
+        }
+
+        Some(TableData { bytes, pairs, map })
+    }
+}
+
+fn set_symlen(d: &mut PairsData, bytes: &[u8], sym: usize, visited: &mut Vec<bool>) -> u8 {
+    visited[sym] = true;
+
+    let right = d.btree_right(bytes, sym);
+    if right == 0xFFF {
+        return 0;
+    }
+
+    let left = d.btree_left(bytes, sym);
+
+    if !visited[left] {
+        d.symlen[left] = set_symlen(d, bytes, left, visited);
+    }
+
+    if !visited[right] {
+        d.symlen[right] = set_symlen(d, bytes, right, visited);
+    }
+
+    d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1)
+}
+
+fn set_sizes(d: &mut PairsData, bytes: &[u8], mut data: usize) -> Option<usize> {
+    d.flags = *bytes.get(data)?;
+    data += 1;
+
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        d.num_blocks = 0;
+        d.span = 0;
+        d.sparse_index_size = 0;
+        d.block_length_size = 0;
+        d.min_sym_len = *bytes.get(data)?;
+        return Some(data + 1);
+    }
+
+    let groups = d.group_len.iter().position(|&len| len == 0).unwrap_or(TB_PIECES);
+    let tb_size = d.group_idx[groups];
+
+    d.sizeof_block = 1u64 << *bytes.get(data)?;
+    d.span = 1u64 << *bytes.get(data + 1)?;
+    d.sparse_index_size = tb_size.div_ceil(d.span) as usize;
+    let padding = *bytes.get(data + 2)? as usize;
+    d.num_blocks = read_u32_le(bytes, data + 3);
+    d.block_length_size = d.num_blocks as usize + padding;
+    let max_sym_len = *bytes.get(data + 7)?;
+    d.min_sym_len = *bytes.get(data + 8)?;
+    data += 9;
+
+    if max_sym_len < d.min_sym_len {
+        return None;
+    }
+
+    d.lowest_sym = data;
+
+    let size = (max_sym_len - d.min_sym_len + 1) as usize;
+    d.base64 = vec![0; size];
+
+    for i in (0..size - 1).rev() {
+        let lowest = read_u16_le(bytes, d.lowest_sym + i * 2) as u64;
+        let next_lowest = read_u16_le(bytes, d.lowest_sym + (i + 1) * 2) as u64;
+
+        d.base64[i] = d.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+    }
+
+    for i in 0..size {
+        let shift = 64 - i as u32 - d.min_sym_len as u32;
+        d.base64[i] = d.base64[i].checked_shl(shift).unwrap_or(0);
+    }
+
+    data += size * 2;
+
+    let symlen_size = read_u16_le(bytes, data) as usize;
+    data += 2;
+
+    d.btree = data;
+    if d.btree + symlen_size * 3 > bytes.len() {
+        return None;
+    }
+
+    d.symlen = vec![0; symlen_size];
+    let mut visited = vec![false; symlen_size];
+
+    for sym in 0..symlen_size {
+        if !visited[sym] {
+            d.symlen[sym] = set_symlen(d, bytes, sym, &mut visited);
+        }
+    }
+
+    Some(data + symlen_size * 3 + (symlen_size & 1))
+}
+
+fn decompress_pairs(d: &PairsData, bytes: &[u8], idx: u64) -> i32 {
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        return d.min_sym_len as i32;
+    }
+
+    let k = (idx / d.span) as usize;
+
+    let mut block = read_u32_le(bytes, d.sparse_index + k * 6) as usize;
+    let mut offset = read_u16_le(bytes, d.sparse_index + k * 6 + 4) as i64;
+
+    offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+    while offset < 0 {
+        block -= 1;
+        offset += d.block_length_at(bytes, block) + 1;
+    }
+
+    while offset > d.block_length_at(bytes, block) {
+        offset -= d.block_length_at(bytes, block) + 1;
+        block += 1;
+    }
+
+    let mut ptr = d.data + block * d.sizeof_block as usize;
+
+    let mut buf64 = read_u64_be(bytes, ptr);
+    ptr += 8;
+    let mut buf64_size = 64;
+
+    let mut sym;
+
+    loop {
+        let mut len = 0;
+
+        while len + 1 < d.base64.len() && buf64 < d.base64[len] {
+            len += 1;
+        }
+
+        sym = ((buf64 - d.base64[len]) >> (64 - len - d.min_sym_len as usize)) as u16;
+        sym = sym.wrapping_add(read_u16_le(bytes, d.lowest_sym + len * 2));
+
+        let sym_len = *d.symlen.get(sym as usize).unwrap_or(&0) as i64;
+
+        if offset < sym_len + 1 {
+            break;
+        }
+
+        offset -= sym_len + 1;
+        let bits = len + d.min_sym_len as usize;
+        buf64 <<= bits;
+        buf64_size -= bits;
+
+        if buf64_size <= 32 {
+            buf64_size += 32;
+            buf64 |= (read_u32_be(bytes, ptr) as u64) << (64 - buf64_size);
+            ptr += 4;
+        }
+    }
+
+    let mut sym = sym as usize;
+
+    while d.symlen[sym] != 0 {
+        let left = d.btree_left(bytes, sym);
+
+        if offset < d.symlen[left] as i64 + 1 {
+            sym = left;
+        } else {
+            offset -= d.symlen[left] as i64 + 1;
+            sym = d.btree_right(bytes, sym);
+        }
+    }
+
+    d.btree_left(bytes, sym) as i32
+}
+
+fn material_key(board: &Board) -> String {
+    let side = |color: PieceColor| {
+        let bb = &board.bb;
+        let (queens, rooks, bishops, knights, pawns) = if color == PieceColor::White {
+            (bb.white_queens, bb.white_rooks, bb.white_bishops, bb.white_knights, bb.white_pawns)
+        } else {
+            (bb.black_queens, bb.black_rooks, bb.black_bishops, bb.black_knights, bb.black_pawns)
+        };
+
+        let mut key = String::from("K");
+        for (c, pieces) in [('Q', queens), ('R', rooks), ('B', bishops), ('N', knights), ('P', pawns)] {
+            for _ in 0..pieces.count_ones() {
+                key.push(c);
+            }
+        }
+        key
+    };
+
+    format!("{}v{}", side(PieceColor::White), side(PieceColor::Black))
+}
+
+fn to_square(x: usize, y: usize) -> usize {
+    (7 - y) * 8 + x
+}
+
+fn board_piece_code(piece_type: PieceType, color: PieceColor) -> u8 {
+    let code = match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6
+    };
+
+    if color == PieceColor::White { code } else { code + 8 }
+}
+
+fn is_zeroing(m: &Move) -> bool {
+    m.move_type.contains(&MoveType::Capture) || m.piece_type == PieceType::Pawn
+}
+
+fn is_mate(board: &mut Board) -> bool {
+    board.get_check(board.turn).checked != 0 && board.get_total_legal_moves(None).is_empty()
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0
+    }
+}
+
+pub struct Tablebase {
+    tables: HashMap<String, Table>,
+    max_pieces: usize
+}
+
+impl Tablebase {
+    pub fn new() -> Self {
+        Tablebase {
+            tables: HashMap::new(),
+            max_pieces: 0
+        }
+    }
+
+    // accepts a list of directories in the platform's PATH format
+    pub fn load(paths: &str) -> io::Result<Tablebase> {
+        let mut tablebase = Tablebase::new();
+
+        for path in env::split_paths(paths) {
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+
+            tablebase.add_directory(&path)?;
+        }
+
+        Ok(tablebase)
+    }
+
+    pub fn add_directory<P: AsRef<Path>>(&mut self, dir_path: P) -> io::Result<usize> {
+        let mut files = 0;
+
+        for file in fs::read_dir(dir_path)? {
+            let path = file?.path();
+
+            if !path.is_file() {
+                continue;
+            }
+
+            let dtz = match path.extension().map(|e| e.to_string_lossy().to_lowercase()) {
+                Some(extension) if extension == "rtbw" => false,
+                Some(extension) if extension == "rtbz" => true,
+                _ => continue
+            };
+
+            let key = match path.file_stem() {
+                Some(stem) => stem.to_string_lossy().to_string(),
+                None => continue
+            };
+
+            if !self.tables.contains_key(&key) {
+                match Table::new(&key) {
+                    Some(table) => {
+                        self.tables.insert(key.clone(), table);
+                    },
+                    None => continue
+                }
+            }
+
+            let table = self.tables.get_mut(&key).unwrap();
+
+            if dtz {
+                table.dtz_path = Some(path);
+            } else {
+                table.wdl_path = Some(path);
+                self.max_pieces = self.max_pieces.max(table.piece_count);
+            }
+
+            files += 1;
+        }
+
+        Ok(files)
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn can_probe(&self, board: &Board) -> bool {
+        let castling = board.castling.white.0 || board.castling.white.1 || board.castling.black.0 || board.castling.black.1;
+
+        !castling && board.pieces.len() <= self.max_pieces
+    }
+
+    fn find_table(&self, board: &Board) -> Option<&Table> {
+        let key = material_key(board);
+
+        self.tables.get(&key).or_else(|| self.tables.get(&swap_key(&key)))
+    }
+
+    fn probe_table(&self, board: &Board, dtz: bool, wdl: Wdl, state: &mut ProbeState) -> i32 {
+        if board.pieces.len() == 2 {
+            return 0;
+        }
+
+        let table = match self.find_table(board) {
+            Some(table) => table,
+            None => {
+                *state = ProbeState::Fail;
+                return 0;
+            }
+        };
+
+        let data = match table.get_data(dtz) {
+            Some(data) => data,
+            None => {
+                *state = ProbeState::Fail;
+                return 0;
+            }
+        };
+
+        let tables = tables();
+        let bytes = &data.bytes;
+
+        let black_to_move = board.turn == PieceColor::Black;
+        let symmetric_black_to_move = table.key == table.key2 && black_to_move;
+        let black_stronger = material_key(board) != table.key;
+
+        let flip = symmetric_black_to_move || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip as usize) ^ (black_to_move as usize);
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns_count = 0;
+        let mut lead_pawns = 0u64;
+        let mut tb_file = 0;
+
+        let mut occupied: Vec<(usize, u8)> = board.pieces.values()
+            .map(|piece| (to_square(piece.pos.x, piece.pos.y), board_piece_code(piece.piece_type, piece.color)))
+            .collect();
+        occupied.sort_by_key(|&(square, _)| square);
+
+        if table.has_pawns {
+            let lead = data.pairs[0][0].pieces[0] ^ flip_color;
+
+            for &(square, code) in &occupied {
+                if code == lead {
+                    lead_pawns |= 1u64 << square;
+                    squares[size] = square ^ flip_squares;
+                    size += 1;
+                }
+            }
+
+            lead_pawns_count = size;
+
+            let lead_index = (0..lead_pawns_count)
+                .max_by_key(|&i| tables.map_pawns[squares[i]])
+                .unwrap_or(0);
+            squares.swap(0, lead_index);
+
+            tb_file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+
+        if dtz {
+            let flags = data.pairs[tb_file][0].flags;
+            if (flags & FLAG_STM) as usize != stm && (table.key != table.key2 || table.has_pawns) {
+                *state = ProbeState::ChangeStm;
+                return 0;
+            }
+        }
+
+        for &(square, code) in &occupied {
+            if lead_pawns & (1u64 << square) != 0 {
+                continue;
+            }
+
+            squares[size] = square ^ flip_squares;
+            pieces[size] = code ^ flip_color;
+            size += 1;
+        }
+
+        let d = &data.pairs[tb_file][if dtz { 0 } else { stm }];
+
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            for j in (i + 1)..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        if file_of(squares[0]) > 3 {
+            for square in squares.iter_mut().take(size) {
+                *square = flip_file(*square);
+            }
+        }
+
+        let mut idx: u64;
+
+        if table.has_pawns {
+            idx = tables.lead_pawn_idx[lead_pawns_count][squares[0]];
+
+            squares[1..lead_pawns_count].sort_by_key(|&square| tables.map_pawns[square]);
+
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
+                idx += tables.binomial[i][tables.map_pawns[square]];
+            }
+        } else {
+            if rank_of(squares[0]) > 3 {
+                for square in squares.iter_mut().take(size) {
+                    *square = flip_rank(*square);
+                }
+            }
+
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+
+                if off_a1h8(squares[i]) > 0 {
+                    for square in squares.iter_mut().take(size).skip(i) {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if table.has_unique_pieces {
+                let adjust1 = (squares[1] > squares[0]) as u64;
+                let adjust2 = (squares[2] > squares[0]) as u64 + (squares[2] > squares[1]) as u64;
+
+                idx = if off_a1h8(squares[0]) != 0 {
+                    (tables.map_a1d1d4[squares[0]] as u64 * 63 + (squares[1] as u64 - adjust1)) * 62 + squares[2] as u64 - adjust2
+                } else if off_a1h8(squares[1]) != 0 {
+                    (6 * 63 + rank_of(squares[0]) as u64 * 28 + tables.map_b1h1h7[squares[1]] as u64) * 62 + squares[2] as u64 - adjust2
+                } else if off_a1h8(squares[2]) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 +
+                        rank_of(squares[0]) as u64 * 7 * 28 +
+                        (rank_of(squares[1]) as u64 - adjust1) * 28 +
+                        tables.map_b1h1h7[squares[2]] as u64
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 +
+                        rank_of(squares[0]) as u64 * 7 * 6 +
+                        (rank_of(squares[1]) as u64 - adjust1) * 6 +
+                        (rank_of(squares[2]) as u64 - adjust2)
+                };
+            } else {
+                idx = tables.map_kk[tables.map_a1d1d4[squares[0]]][squares[1]];
+            }
+        }
+
+        idx *= d.group_idx[0];
+
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = table.has_pawns && table.pawn_count[1] > 0;
+
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let group_end = group_start + d.group_len[next];
+            squares[group_start..group_end].sort();
+
+            let mut n = 0;
+            for i in 0..d.group_len[next] {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|&&s| square > s).count();
+                n += tables.binomial[i + 1][square - adjust - 8 * remaining_pawns as usize];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        let value = decompress_pairs(d, bytes, idx);
+
+        if !dtz {
+            return value - 2;
+        }
+
+        let mut value = value;
+
+        if d.flags & FLAG_MAPPED != 0 {
+            let map_index = [1, 3, 0, 2, 0][(wdl as i32 + 2) as usize];
+            let offset = d.map_idx[map_index] as usize + value as usize;
+
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16_le(bytes, data.map + offset * 2) as i32
+            } else {
+                *bytes.get(data.map + offset).unwrap_or(&0) as i32
+            };
+        }
+
+        if (wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES == 0) ||
+            (wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES == 0) ||
+            wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss {
+            value *= 2;
+        }
+
+        value + 1
+    }
+
+    // probes captures (and pawn moves when looking for zeroing moves) before the table,
+    // since the tables store "don't care" values where a winning capture exists
+    fn search(&self, board: &mut Board, check_zeroing: bool, state: &mut ProbeState) -> Wdl {
+        let mut best = Wdl::Loss;
+
+        let moves = board.get_total_legal_moves(None);
+        let total = moves.len();
+        let mut count = 0;
+
+        for m in &moves {
+            if !m.move_type.contains(&MoveType::Capture) && (!check_zeroing || m.piece_type != PieceType::Pawn) {
+                continue;
+            }
+
+            count += 1;
+
+            let history = board.make_move(m);
+            let value = -self.search(board, false, state);
+            board.unmake_move(m, &history);
+
+            if *state == ProbeState::Fail {
+                return Wdl::Draw;
+            }
+
+            if value > best {
+                best = value;
+
+                if value >= Wdl::Win {
+                    *state = ProbeState::ZeroingBestMove;
+                    return value;
+                }
+            }
+        }
+
+        let no_more_moves = count > 0 && count == total;
+
+        let value = if no_more_moves {
+            best
+        } else {
+            let value = Wdl::from_value(self.probe_table(board, false, Wdl::Draw, state));
+
+            if *state == ProbeState::Fail {
+                return Wdl::Draw;
+            }
+
+            value
+        };
+
+        if best >= value {
+            *state = if best > Wdl::Draw || no_more_moves { ProbeState::ZeroingBestMove } else { ProbeState::Ok };
+            return best;
+        }
+
+        *state = ProbeState::Ok;
+        value
+    }
+
+    fn probe_dtz_state(&self, board: &mut Board, state: &mut ProbeState) -> i32 {
+        *state = ProbeState::Ok;
+        let wdl = self.search(board, true, state);
+
+        if *state == ProbeState::Fail || wdl == Wdl::Draw {
+            return 0;
+        }
+
+        if *state == ProbeState::ZeroingBestMove {
+            return dtz_before_zeroing(wdl);
+        }
+
+        let dtz = self.probe_table(board, true, wdl, state);
+
+        if *state == ProbeState::Fail {
+            return 0;
+        }
+
+        if *state != ProbeState::ChangeStm {
+            let cursed = wdl == Wdl::BlessedLoss || wdl == Wdl::CursedWin;
+            return (dtz + if cursed { 100 } else { 0 }) * wdl.sign();
+        }
+
+        // the table stores the other side to move, so search one ply
+        let mut min_dtz = 0xFFFF;
+
+        for m in board.get_total_legal_moves(None) {
+            let zeroing = is_zeroing(&m);
+
+            let history = board.make_move(&m);
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(board, false, state))
+            } else {
+                -self.probe_dtz_state(board, state)
+            };
+
+            if dtz == 1 && is_mate(board) {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.sign() {
+                min_dtz = dtz;
+            }
+
+            board.unmake_move(&m, &history);
+
+            if *state == ProbeState::Fail {
+                return 0;
+            }
+        }
+
+        if min_dtz == 0xFFFF { -1 } else { min_dtz }
+    }
+
+    pub fn probe_wdl(&self, board: &mut Board) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        let mut state = ProbeState::Ok;
+        let wdl = self.search(board, false, &mut state);
+
+        if state == ProbeState::Fail {
+            None
+        } else {
+            Some(wdl)
+        }
+    }
+
+    // distance to zeroing the fifty move counter, in plies, signed from the side to move
+    pub fn probe_dtz(&self, board: &mut Board) -> Option<i32> {
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        let mut state = ProbeState::Ok;
+        let dtz = self.probe_dtz_state(board, &mut state);
+
+        if state == ProbeState::Fail {
+            None
+        } else {
+            Some(dtz)
+        }
+    }
+
+    // ranks the root moves by DTZ: the fastest win that the fifty move rule allows,
+    // otherwise a draw, otherwise the longest resistance
+    pub fn probe_root(&self, board: &mut Board) -> Option<RootProbe> {
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        let halfmove_clock = board.halfmove_clock;
+        let mut best: Option<(i32, RootProbe)> = None;
+
+        for m in board.get_total_legal_moves(None) {
+            let mut state = ProbeState::Ok;
+
+            let history = board.make_move(&m);
+
+            let mut dtz = if is_zeroing(&m) {
+                let wdl = -self.search(board, false, &mut state);
+                dtz_before_zeroing(wdl)
+            } else {
+                let dtz = -self.probe_dtz_state(board, &mut state);
+                dtz + dtz.signum()
+            };
+
+            if dtz == 2 && is_mate(board) {
+                dtz = 1;
+            }
+
+            board.unmake_move(&m, &history);
+
+            if state == ProbeState::Fail {
+                return None;
+            }
+
+            let rank = if dtz > 0 {
+                if dtz + halfmove_clock <= 100 { 1000 - dtz } else { 100 - dtz }
+            } else if dtz < 0 {
+                if -dtz * 2 + halfmove_clock < 100 { -1000 - dtz } else { -100 - dtz }
+            } else {
+                0
+            };
+
+            let wdl = if dtz > 0 {
+                if dtz + halfmove_clock <= 100 { Wdl::Win } else { Wdl::CursedWin }
+            } else if dtz < 0 {
+                if -dtz + halfmove_clock <= 100 { Wdl::Loss } else { Wdl::BlessedLoss }
+            } else {
+                Wdl::Draw
+            };
+
+            if best.as_ref().is_none_or(|(best_rank, _)| rank > *best_rank) {
+                best = Some((rank, RootProbe { m: m.clone(), wdl, dtz }));
+            }
+        }
+
+        best.map(|(_, probe)| probe)
+    }
+}
+
+impl Default for Tablebase {
+    fn default() -> Self {
+        Tablebase::new()
+    }
+}
\ No newline at end of file
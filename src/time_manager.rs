@@ -0,0 +1,148 @@
+use std::time::Instant;
+
+use crate::r#const::{BEST_MOVE_CHANGE_FACTOR, DEFAULT_MOVES_TO_GO, MAX_TIME_SCALE, MIN_TIME_SCALE, SCORE_DROP_FACTOR, SCORE_DROP_THRESHOLD, STABILITY_FACTOR, STABLE_ITERATIONS, TIME_CHECK_INTERVAL};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeControl {
+    pub time: Option<u64>,
+    pub increment: u64,
+    pub moves_to_go: Option<u32>,
+    pub move_time: Option<u64>
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    start: Instant,
+    optimum: u64,
+    maximum: u64,
+    scale: f64,
+    stable_iterations: u32,
+    last_score: Option<f64>,
+    // counted from the start of the search
+    max_nodes: Option<u64>
+}
+
+impl TimeManager {
+    // optimum is the budget for starting new iterations, maximum aborts the running one
+    pub fn new(control: &TimeControl, move_overhead: u64) -> Self {
+        if let Some(move_time) = control.move_time {
+            return TimeManager::fixed(move_time.saturating_sub(move_overhead).max(1));
+        }
+
+        let remaining = match control.time {
+            Some(time) => time.saturating_sub(move_overhead).max(1),
+            None => return TimeManager::infinite()
+        };
+
+        let (optimum, maximum) = match control.moves_to_go {
+            // repeating control, the clock is refilled after `moves_to_go` moves
+            Some(moves_to_go) => {
+                let moves_to_go = moves_to_go.max(1) as u64;
+                let per_move = remaining / moves_to_go + control.increment;
+
+                let maximum = (per_move * 3).min(remaining * 4 / 5);
+                let optimum = if moves_to_go == 1 { maximum * 3 / 4 } else { per_move * 3 / 4 };
+
+                (optimum, maximum)
+            },
+            // sudden death, keep a reserve for the moves that are still to come
+            None => {
+                let optimum = remaining / DEFAULT_MOVES_TO_GO + control.increment * 3 / 4;
+                let maximum = (optimum * 4).min(remaining / 4);
+
+                (optimum, maximum)
+            }
+        };
+
+        TimeManager {
+            start: Instant::now(),
+            optimum: optimum.min(maximum).max(1),
+            maximum: maximum.max(1),
+            scale: 1.0,
+            stable_iterations: 0,
+            last_score: None,
+            max_nodes: None
+        }
+    }
+
+    pub fn fixed(time_limit: u64) -> Self {
+        TimeManager {
+            start: Instant::now(),
+            optimum: time_limit,
+            maximum: time_limit,
+            scale: 1.0,
+            stable_iterations: 0,
+            last_score: None,
+            max_nodes: None
+        }
+    }
+
+    // the old behaviour: no new iteration after 3/4 of the budget
+    pub fn from_budget(time_limit: u64) -> Self {
+        TimeManager {
+            optimum: time_limit * 3 / 4,
+            ..TimeManager::fixed(time_limit)
+        }
+    }
+
+    pub fn infinite() -> Self {
+        TimeManager::fixed(u64::MAX)
+    }
+
+    pub fn with_node_limit(self, nodes: u64) -> Self {
+        TimeManager { max_nodes: Some(nodes), ..self }
+    }
+
+    pub fn elapsed(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    pub fn optimum(&self) -> u64 {
+        self.optimum
+    }
+
+    pub fn maximum(&self) -> u64 {
+        self.maximum
+    }
+
+    pub fn soft_limit(&self) -> u64 {
+        ((self.optimum as f64 * self.scale) as u64).min(self.maximum)
+    }
+
+    // called for every node, only looks at the clock every TIME_CHECK_INTERVAL nodes
+    pub fn should_abort(&self, nodes: u64) -> bool {
+        self.max_nodes.is_some_and(|max_nodes| nodes >= max_nodes)
+            || (nodes.is_multiple_of(TIME_CHECK_INTERVAL) && self.elapsed() >= self.maximum)
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.elapsed() >= self.soft_limit()
+    }
+
+    pub fn is_clearly_best(&self) -> bool {
+        self.stable_iterations >= STABLE_ITERATIONS
+    }
+
+    // score is from the point of view of the side to move
+    pub fn update(&mut self, best_move_changed: bool, score: f64) {
+        if best_move_changed {
+            self.stable_iterations = 0;
+            self.scale *= BEST_MOVE_CHANGE_FACTOR;
+        } else {
+            self.stable_iterations += 1;
+
+            if self.is_clearly_best() {
+                self.scale *= STABILITY_FACTOR;
+            }
+        }
+
+        if let Some(last_score) = self.last_score {
+            if last_score - score > SCORE_DROP_THRESHOLD {
+                self.scale = self.scale.max(SCORE_DROP_FACTOR);
+            }
+        }
+
+        self.scale = self.scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+        self.last_score = Some(score);
+    }
+}
\ No newline at end of file
@@ -14,6 +14,21 @@ mod special {
     pub mod control;
     pub mod blocks;
     pub mod evaluation;
+    pub mod pawn_structure;
+    pub mod endgame;
+    pub mod syzygy;
+    pub mod nnue;
+    pub mod time_manager;
+    pub mod mcts;
+    pub mod polyglot;
+    pub mod book;
+    pub mod pgn;
+    pub mod san;
+    pub mod search_info;
+    pub mod api;
+    pub mod session;
+    pub mod auth;
+    pub mod skill;
     pub mod castling;
     pub mod capture;
     // position-specific tests
@@ -0,0 +1,138 @@
+use axum::http::StatusCode;
+use mchess::{api::{self, AnalyseRequest, PositionRequest}, board::{Board, FenError}, engine::{Engine, EngineType}};
+
+fn request(fen: Option<&str>, moves: &[&str]) -> PositionRequest {
+    PositionRequest {
+        fen: fen.map(|fen| fen.to_string()),
+        moves: moves.iter().map(|m| m.to_string()).collect()
+    }
+}
+
+#[test]
+fn test_try_from_fen() {
+    assert!(Board::try_from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").is_ok());
+
+    let syntax = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0",
+        "rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1"
+    ];
+
+    for fen in syntax {
+        assert!(matches!(Board::try_from_fen(fen), Err(FenError::Syntax(_))), "{}", fen);
+    }
+
+    let illegal = [
+        // two white kings, no black pawn on the first rank, castling without a rook, black in check with white to move
+        "4k3/8/8/8/8/8/8/K3K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/p3K3 w - - 0 1",
+        "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+        "4k3/4R3/8/8/8/8/8/4K3 w - - 0 1"
+    ];
+
+    for fen in illegal {
+        assert!(matches!(Board::try_from_fen(fen), Err(FenError::Illegal(_))), "{}", fen);
+    }
+}
+
+#[test]
+fn test_legal_moves() {
+    let response = api::legal_moves(&request(None, &["e2e4", "e7e5", "d1h5", "b8c6", "f1c4", "g8f6"])).unwrap();
+    assert_eq!(response.turn, "white");
+    assert_eq!(response.result, "*");
+    assert!(response.moves.iter().any(|m| m.uci == "h5f7" && m.san == "Qxf7#"));
+
+    // promotions in both notations, the UCI move is accepted in either case
+    let response = api::legal_moves(&request(Some("8/P6k/8/8/8/8/8/K7 w - - 0 1"), &[])).unwrap();
+    assert!(response.moves.iter().any(|m| m.uci == "a7a8q" && m.san == "a8=Q"));
+    assert!(api::position(&request(Some("8/P6k/8/8/8/8/8/K7 w - - 0 1"), &["A7A8N"])).is_ok());
+
+    let mated = api::legal_moves(&request(Some("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1"), &[])).unwrap();
+    assert!(mated.in_check);
+    assert!(mated.moves.is_empty());
+    assert_eq!(mated.result, "1-0");
+
+    assert_eq!(api::legal_moves(&request(None, &["e2e5"])).unwrap_err().status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(api::legal_moves(&request(Some("8/8/8"), &[])).unwrap_err().status, StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_evaluate() {
+    let white = api::evaluation(&request(Some("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1"), &[])).unwrap();
+    let black = api::evaluation(&request(Some("4k3/8/8/8/8/8/8/Q3K3 b - - 0 1"), &[])).unwrap();
+
+    // the score follows the side to move, the terms do not
+    assert!(white.score > 0);
+    assert_eq!(black.score, -white.score);
+    assert_eq!(white.terms.iter().find(|term| term.name == "material").map(|term| (term.white, term.black)), Some((9.0, 0.0)));
+}
+
+#[test]
+fn test_score_units() {
+    let queen_up = request(Some("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), &[]);
+
+    // a queen is worth hundreds of centipawns
+    let evaluated = api::evaluation(&queen_up).unwrap().score;
+    assert!((500..2000).contains(&evaluated), "{}", evaluated);
+
+    let mut engine = Engine::new(EngineType::Minimax, false);
+    let analysed = api::analyse(&mut engine, &AnalyseRequest { position: queen_up, depth: Some(2), ..AnalyseRequest::default() }).unwrap();
+    assert!((500..2000).contains(&analysed.score.unwrap()), "{:?}", analysed.score);
+}
+
+#[test]
+fn test_analyse() {
+    let mut engine = Engine::new(EngineType::Minimax, false);
+
+    let mate = AnalyseRequest {
+        position: request(Some("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1"), &[]),
+        depth: Some(3),
+        ..AnalyseRequest::default()
+    };
+
+    let response = api::analyse(&mut engine, &mate).unwrap();
+    assert_eq!(response.best_move.as_deref(), Some("a1a8"));
+    assert_eq!(response.lines[0].san, "Ra8#");
+    assert_eq!(response.pv[0], "a1a8");
+    assert!(response.score.unwrap() > 0);
+
+    // the best line first, then the others from best to worst
+    let multipv = AnalyseRequest {
+        position: request(None, &["e2e4", "e7e5"]),
+        depth: Some(2),
+        multipv: Some(3),
+        ..AnalyseRequest::default()
+    };
+
+    let response = api::analyse(&mut engine, &multipv).unwrap();
+    assert_eq!(response.lines.len(), 3);
+    assert_eq!(response.lines[0].m, response.best_move.unwrap());
+    assert!(response.lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    assert!(response.lines.iter().all(|line| line.pv[0] == line.m));
+
+    // a move time is shared by the lines
+    let timed = AnalyseRequest {
+        position: request(None, &["e2e4", "e7e5"]),
+        movetime: Some(1000),
+        multipv: Some(3),
+        ..AnalyseRequest::default()
+    };
+
+    let response = api::analyse(&mut engine, &timed).unwrap();
+    assert_eq!(response.lines.len(), 3);
+    assert!(response.lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    assert!(response.time < 2000);
+
+    let over = AnalyseRequest { position: request(Some("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1"), &[]), ..AnalyseRequest::default() };
+    let response = api::analyse(&mut engine, &over).unwrap();
+    assert_eq!(response.best_move, None);
+    assert_eq!(response.result, "1-0");
+
+    let invalid = AnalyseRequest { multipv: Some(0), ..AnalyseRequest::default() };
+    assert_eq!(api::analyse(&mut engine, &invalid).unwrap_err().status, StatusCode::BAD_REQUEST);
+}
\ No newline at end of file
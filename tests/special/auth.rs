@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use mchess::auth::{Auth, AuthError, RateLimits};
+
+fn keys() -> Vec<String> {
+    vec!["alpha".to_string(), " beta ".to_string(), String::new()]
+}
+
+#[test]
+fn test_authenticate() {
+    let auth = Auth::new(keys(), RateLimits::default());
+    assert!(auth.enabled());
+
+    assert_eq!(auth.authenticate(Some("alpha")), Ok(()));
+    assert_eq!(auth.authenticate(Some("beta")), Ok(()));
+    assert_eq!(auth.authenticate(Some("alph")), Err(AuthError::InvalidKey));
+    assert_eq!(auth.authenticate(Some("")), Err(AuthError::MissingKey));
+    assert_eq!(auth.authenticate(None), Err(AuthError::MissingKey));
+
+    // without keys anyone gets in
+    let open = Auth::new(vec![String::new()], RateLimits::default());
+    assert!(!open.enabled());
+    assert_eq!(open.authenticate(None), Ok(()));
+}
+
+#[test]
+fn test_search_limit() {
+    let auth = Auth::new(keys(), RateLimits { searches: Some(2), cpu_time: None });
+    let now = Instant::now();
+
+    assert!(auth.start_search("alpha", now).is_ok());
+    assert!(auth.start_search("alpha", now + Duration::from_secs(10)).is_ok());
+    assert_eq!(auth.start_search("alpha", now + Duration::from_secs(20)), Err(AuthError::TooManySearches(Duration::from_secs(40))));
+
+    // every key has its own count, which starts over after a minute
+    assert!(auth.start_search("beta", now + Duration::from_secs(20)).is_ok());
+    assert!(auth.start_search("alpha", now + Duration::from_secs(60)).is_ok());
+}
+
+#[test]
+fn test_cpu_limit() {
+    let auth = Auth::new(keys(), RateLimits { searches: None, cpu_time: Some(Duration::from_secs(10)) });
+    let now = Instant::now();
+
+    assert!(auth.start_search("alpha", now).is_ok());
+    auth.record_search("alpha", Duration::from_secs(6), now);
+    assert!(auth.start_search("alpha", now).is_ok());
+    auth.record_search("alpha", Duration::from_secs(6), now + Duration::from_secs(600));
+
+    let error = auth.start_search("alpha", now + Duration::from_secs(600)).unwrap_err();
+    assert_eq!(error, AuthError::CpuTimeExceeded(Duration::from_secs(3000)));
+    assert_eq!(error.retry_after(), Some(Duration::from_secs(3000)));
+
+    assert!(auth.start_search("alpha", now + Duration::from_secs(3600)).is_ok());
+
+    // usage is forgotten once its hour has passed
+    auth.evict(now + Duration::from_secs(7200));
+    assert!(auth.start_search("alpha", now + Duration::from_secs(7200)).is_ok());
+}
\ No newline at end of file
@@ -0,0 +1,238 @@
+use std::{env, fs, sync::{Arc, Mutex}};
+
+use mchess::{board::Board, book::{BookLearning, BookMove, BookOptions, BookSelection, OpeningBook}, engine::{Engine, EngineType}, pgn::Game, piece::PieceColor, polyglot::encode_move, protocol::UciProtocol};
+
+const GAMES: &str = r#"[Event "a"]
+[Result "1-0"]
+
+1.d4 Nf6 2.c4 e6 3.Nc3 Bb4 4.e3 O-O 1-0
+
+[Event "b"]
+[Result "0-1"]
+
+1.c4 e6 2.d4 Nf6 3.Nf3 d5 0-1
+
+[Event "c"]
+[Result "1/2-1/2"]
+
+1.Nf3 Nf6 2.c4 e6 3.d4 d5 1/2-1/2
+"#;
+
+const AFTER_NF3: &str = "rnbqkb1r/pppp1ppp/4pn2/8/2PP4/5N2/PP2PPPP/RNBQKB1R b KQkq - 1 3";
+
+fn load_games(name: &str, games: &str) -> OpeningBook {
+    let path = env::temp_dir().join(format!("mchess-book-{}-{}.pgn", name, std::process::id()));
+    fs::write(&path, games).unwrap();
+
+    let mut book = OpeningBook::new();
+    book.load_pgn_file(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    book
+}
+
+fn load(name: &str) -> OpeningBook {
+    load_games(name, GAMES)
+}
+
+fn moves(book: &OpeningBook, board: &mut Board) -> Vec<(String, usize)> {
+    let mut moves: Vec<_> = book.moves(board).into_iter().map(|(m, stats)| (format!("{:?}", m), stats.games)).collect();
+    moves.sort();
+    moves
+}
+
+#[test]
+fn test_transpositions() {
+    let book = load("transpositions");
+
+    let mut board = Board::startpos();
+    assert_eq!(moves(&book, &mut board), vec![("c2c4".to_string(), 1), ("d2d4".to_string(), 1), ("g1f3".to_string(), 1)]);
+
+    // all three games reach the position after 1.d4 Nf6 2.c4 e6
+    let mut board = Board::from_fen("rnbqkb1r/pppp1ppp/4pn2/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3");
+    assert_eq!(moves(&book, &mut board), vec![("b1c3".to_string(), 1), ("g1f3".to_string(), 1)]);
+
+    let mut board = Board::from_fen(AFTER_NF3);
+    assert_eq!(moves(&book, &mut board), vec![("d7d5".to_string(), 2)]);
+    assert_eq!(format!("{:?}", book.get_best_move(&mut board).unwrap()), "d7d5");
+
+    // castling is recorded as well
+    let mut board = Board::from_fen("rnbqk2r/pppp1ppp/4pn2/8/1bPP4/2N1P3/PP3PPP/R1BQKBNR b KQkq - 0 4");
+    assert_eq!(moves(&book, &mut board), vec![("e8g8".to_string(), 1)]);
+
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+    assert!(book.get_best_move(&mut board).is_none());
+}
+
+#[test]
+fn test_book_from_fen() {
+    let mut engine = Engine::new(EngineType::Minimax, true);
+    engine.book = Some(Arc::new(load("fen")));
+
+    // a game set up from a FEN finds the book move
+    let mut board = Board::from_fen(AFTER_NF3);
+    let m = engine.search(&mut board, Some(1), None).unwrap();
+    assert_eq!(format!("{:?}", m), "d7d5");
+}
+
+#[test]
+fn test_results() {
+    let book = load("results");
+
+    // one win for black and one draw
+    let mut board = Board::from_fen(AFTER_NF3);
+    let (_, stats) = book.moves(&mut board)[0];
+    assert_eq!(stats, BookMove { games: 2, wins: 1, draws: 1, losses: 0 });
+    assert_eq!(stats.points(), 3);
+
+    // b1c3 won, g1f3 lost
+    let mut board = Board::from_fen("rnbqkb1r/pppp1ppp/4pn2/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3");
+    let score = BookOptions { selection: BookSelection::Score, ..BookOptions::default() };
+
+    for _ in 0..20 {
+        assert_eq!(format!("{:?}", book.pick_move(&mut board, &score).unwrap()), "b1c3");
+    }
+
+    let weighted = BookOptions { selection: BookSelection::Weighted, ..BookOptions::default() };
+    let m = format!("{:?}", book.pick_move(&mut board, &weighted).unwrap());
+    assert!(m == "b1c3" || m == "g1f3");
+}
+
+#[test]
+fn test_min_games() {
+    let book = load("min-games");
+    let options = BookOptions { min_games: 2, ..BookOptions::default() };
+
+    let mut board = Board::startpos();
+    assert!(book.pick_move(&mut board, &options).is_none());
+
+    let mut board = Board::from_fen(AFTER_NF3);
+    assert_eq!(format!("{:?}", book.pick_move(&mut board, &options).unwrap()), "d7d5");
+}
+
+#[test]
+fn test_book_options() {
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option("setoption name BookVariety value Score", &mut output).unwrap();
+    protocol.set_option("setoption name BookDepth value 0", &mut output).unwrap();
+    protocol.set_option("setoption name BookMinGames value 3", &mut output).unwrap();
+
+    assert_eq!(protocol.engine.book_options, BookOptions { selection: BookSelection::Score, min_games: 3, max_depth: 0 });
+
+    // a book that only knows a poor first move
+    let mut engine = Engine::new(EngineType::Minimax, true);
+    engine.book = Some(Arc::new(load_games("depth", "[Result \"*\"]\n\n1.a3 *\n")));
+
+    let mut board = Board::startpos();
+    assert_eq!(format!("{:?}", engine.search(&mut board, Some(1), None).unwrap()), "a2a3");
+
+    engine.set_book_options(BookOptions { max_depth: 0, ..BookOptions::default() });
+    assert_ne!(format!("{:?}", engine.search(&mut board, Some(1), None).unwrap()), "a2a3");
+}
+#[test]
+fn test_compiled_book() {
+    let book = load("compiled");
+    let path = env::temp_dir().join(format!("mchess-book-{}.mcb", std::process::id()));
+
+    let entries = book.save_compiled(&path).unwrap();
+    let compiled = OpeningBook::load_compiled(&path).unwrap();
+
+    assert_eq!(compiled.len(), book.len());
+    assert_eq!(entries, 19);
+
+    for fen in ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", AFTER_NF3, "rnbqkb1r/pppp1ppp/4pn2/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3"] {
+        let mut board = Board::from_fen(fen);
+        assert_eq!(moves(&compiled, &mut board), moves(&book, &mut board));
+    }
+
+    let mut board = Board::from_fen(AFTER_NF3);
+    assert_eq!(compiled.moves(&mut board)[0].1, BookMove { games: 2, wins: 1, draws: 1, losses: 0 });
+
+    // the engine maps it by extension
+    let mut engine = Engine::new(EngineType::Minimax, true);
+    assert_eq!(engine.load_book(&path).unwrap(), book.len());
+    assert_eq!(format!("{:?}", engine.search(&mut board, Some(1), None).unwrap()), "d7d5");
+
+    // games added to a compiled book are merged with it
+    let mut compiled = OpeningBook::load_compiled(&path).unwrap();
+    let games = env::temp_dir().join(format!("mchess-book-merge-{}.pgn", std::process::id()));
+    fs::write(&games, "[Result \"1-0\"]\n\n1.Nf3 Nf6 2.c4 e6 3.d4 d5 1-0\n").unwrap();
+    compiled.load_pgn_file(&games).unwrap();
+
+    assert_eq!(compiled.moves(&mut board)[0].1.games, 3);
+
+    fs::write(&path, b"MCHSBOOK\x01\x00\x00\x00").unwrap();
+    assert!(OpeningBook::load_compiled(&path).is_err());
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&games).unwrap();
+}
+
+#[test]
+fn test_learning() {
+    let book = load("learning");
+    let path = env::temp_dir().join(format!("mchess-learning-{}.mcb", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    // b1c3 and g1f3 were played once each
+    let start = "rnbqkb1r/pppp1ppp/4pn2/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3";
+    let mut board = Board::from_fen(start);
+    let key = board.polyglot_hash();
+    let knight = |board: &mut Board, san: &str| encode_move(&board.parse_san(san).unwrap());
+    let (nc3, nf3) = (knight(&mut board, "Nc3"), knight(&mut board, "Nf3"));
+
+    let mut learning = BookLearning::open(&path).unwrap();
+    assert!(learning.is_empty());
+
+    // a win doubles the weight of a move
+    assert_eq!(learning.learn(&[(key, nf3, PieceColor::White)], "1-0").unwrap(), 1);
+    let moves = book.moves(&mut board);
+    let adjusted = learning.adjust(&board, moves);
+    let nf3_stats = adjusted.iter().find(|(m, _)| encode_move(m) == nf3).unwrap().1;
+    assert_eq!(nf3_stats.games, 2);
+
+    // unfinished games are not learned from
+    assert_eq!(learning.learn(&[(key, nc3, PieceColor::White)], "*").unwrap(), 0);
+
+    for _ in 0..3 {
+        learning.learn(&[(key, nc3, PieceColor::White)], "0-1").unwrap();
+    }
+
+    // the learned results are kept in their own file
+    let learning = BookLearning::open(&path).unwrap();
+    assert_eq!(learning.len(), 2);
+
+    let mut engine = Engine::new(EngineType::Minimax, true);
+    engine.book = Some(Arc::new(book));
+    engine.set_book_options(BookOptions { selection: BookSelection::Weighted, ..BookOptions::default() });
+    engine.set_learning(Some(Arc::new(Mutex::new(learning))));
+
+    // a move lost three times more than it was won is dropped
+    for _ in 0..10 {
+        assert_eq!(format!("{:?}", engine.search(&mut board, Some(1), None).unwrap()), "g1f3");
+    }
+
+    // a book move the game did not play is not learned
+    let mut game = Game::new(Some(start));
+    let nc3_move = board.parse_san("Nc3").unwrap();
+    game.play(&mut board.clone(), &nc3_move, None);
+    game.result = "1/2-1/2".to_string();
+    assert_eq!(engine.learn(&game).unwrap(), 0);
+
+    // a finished game is learned from once
+    let m = engine.search(&mut board, Some(1), None).unwrap();
+    let mut game = Game::new(Some(start));
+    game.play(&mut board.clone(), &m, None);
+    game.result = "1/2-1/2".to_string();
+    assert_eq!(engine.learn(&game).unwrap(), 1);
+    assert_eq!(engine.learn(&game).unwrap(), 0);
+
+    // a session that only reads the results leaves them alone
+    engine.set_record_learning(false);
+    engine.search(&mut board, Some(1), None).unwrap();
+    assert_eq!(engine.learn(&game).unwrap(), 0);
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,64 @@
+use mchess::{board::Board, endgame::{probe, Endgame}, evaluation::evaluate, r#const::KNOWN_WIN_VALUE};
+
+fn value(fen: &str) -> f64 {
+    let mut board = Board::from_fen(fen);
+    evaluate(&mut board).to_value()
+}
+
+#[test]
+fn test_kpk() {
+    // king in front of the pawn on the sixth rank
+    assert!(value("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1") > KNOWN_WIN_VALUE);
+    assert!(value("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") > KNOWN_WIN_VALUE);
+
+    // stalemate unless white can shoulder the king away
+    assert_eq!(value("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), 0.0);
+    assert!(value("4k3/4P3/4K3/8/8/8/8/8 w - - 0 1") > KNOWN_WIN_VALUE);
+
+    // opposition held by the defender
+    assert_eq!(value("8/3k4/8/3K4/3P4/8/8/8 w - - 0 1"), 0.0);
+    assert!(value("8/3k4/8/3K4/3P4/8/8/8 b - - 0 1") > KNOWN_WIN_VALUE);
+
+    // rook pawn with the defending king in the corner
+    assert_eq!(value("k7/8/8/8/8/8/P7/K7 w - - 0 1"), 0.0);
+
+    // pawn runs away from the king
+    assert!(value("8/8/P7/8/8/8/8/k6K w - - 0 1") > KNOWN_WIN_VALUE);
+    assert!(value("K7/8/8/8/8/8/p7/7k b - - 0 1") < -KNOWN_WIN_VALUE);
+}
+
+#[test]
+fn test_kxk() {
+    let center = value("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1");
+    let edge = value("3k4/8/8/8/8/8/8/Q3K3 w - - 0 1");
+
+    assert!(center > KNOWN_WIN_VALUE);
+    assert!(edge > center);
+
+    assert!(value("8/8/8/3k4/8/8/8/r3K3 w - - 0 1") < -KNOWN_WIN_VALUE);
+}
+
+#[test]
+fn test_kbnk() {
+    // light squared bishop mates in a8 or h1
+    let right_corner = value("k7/8/2K5/8/8/3B4/8/6N1 w - - 0 1");
+    let wrong_corner = value("8/8/8/8/8/2KB4/8/k5N1 w - - 0 1");
+
+    assert!(right_corner > KNOWN_WIN_VALUE);
+    assert!(right_corner > wrong_corner);
+}
+
+#[test]
+fn test_draw_scaling() {
+    // dark squared bishop cannot cover a8
+    assert_eq!(value("k7/8/8/8/8/8/P7/K1B5 w - - 0 1"), 0.0);
+    assert_ne!(value("k7/8/8/8/8/8/P7/KB6 w - - 0 1"), 0.0);
+
+    assert_eq!(value("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"), 0.0);
+
+    let board = Board::from_fen("4k1b1/5p2/8/8/8/8/2P5/2B1K3 w - - 0 1");
+    assert!(matches!(probe(&board), Some(Endgame::Scale(_))));
+
+    let board = Board::from_fen("4kb2/5p2/8/8/8/8/2P5/2B1K3 w - - 0 1");
+    assert!(probe(&board).is_none());
+}
\ No newline at end of file
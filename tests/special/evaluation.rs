@@ -1,6 +1,6 @@
 use core::f64;
 
-use mchess::{board::{Board, ResultType}, evaluation::{evaluate, evaluate_kings_safety}, search::Minimax};
+use mchess::{board::{Board, ResultType}, evaluation::{evaluate, evaluate_breakdown, evaluate_kings_safety}, search::Minimax};
 
 #[test]
 fn test_evaluation() {
@@ -32,4 +32,43 @@ fn evaluate_king_safety() {
 
     let mut engine = Minimax::new();
     println!("{:?}", engine.search(&mut board, 7, f64::NEG_INFINITY, f64::INFINITY, true));
+}
+
+#[test]
+fn test_evaluation_breakdown() {
+    let mut board = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+
+    let breakdown = evaluate_breakdown(&mut board);
+    let total = evaluate(&mut board);
+
+    assert!((breakdown.total().to_value() - total.to_value()).abs() < 1e-9);
+    assert!(breakdown.get("material").is_some());
+    assert!(breakdown.get("outposts").is_some());
+}
+
+#[test]
+fn test_piece_terms() {
+    let mut board = Board::from_fen("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+    let bishop_pair = evaluate_breakdown(&mut board).get("bishop_pair").unwrap();
+    assert!(bishop_pair.white > 0.0 && bishop_pair.black == 0.0);
+
+    let mut board = Board::from_fen("4k3/1p6/8/8/8/8/P7/1R1RK3 w - - 0 1");
+    let rooks = evaluate_breakdown(&mut board).get("rook_files").unwrap();
+    let mut board = Board::from_fen("4k3/1p6/8/8/8/8/PP1P4/1R1RK3 w - - 0 1");
+    let closed_rooks = evaluate_breakdown(&mut board).get("rook_files").unwrap();
+    assert!(rooks.white > 0.0 && closed_rooks.white == 0.0);
+
+    let mut board = Board::from_fen("4k3/8/8/3N4/4P3/8/8/4K3 w - - 0 1");
+    let outposts = evaluate_breakdown(&mut board).get("outposts").unwrap();
+    let mut board = Board::from_fen("4k3/4p3/8/3N4/4P3/8/8/4K3 w - - 0 1");
+    let contested = evaluate_breakdown(&mut board).get("outposts").unwrap();
+    assert!(outposts.white > 0.0 && contested.white == 0.0);
+
+    let mut board = Board::from_fen("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1");
+    let trapped = evaluate_breakdown(&mut board).get("trapped_pieces").unwrap();
+    assert!(trapped.white < 0.0);
+
+    let mut board = Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P2Q/8/PPPP1PPP/RNB1KBNR b KQkq - 1 2");
+    let queen = evaluate_breakdown(&mut board).get("queen_development").unwrap();
+    assert!(queen.white < 0.0 && queen.black == 0.0);
 }
\ No newline at end of file
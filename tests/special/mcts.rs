@@ -0,0 +1,186 @@
+use mchess::{board::Board, mcts::{centipawns, win_probability, Leaf, Mcts, MctsOptions, Prior, Selection}, protocol::UciProtocol};
+
+const FEN: &str = "4k3/8/8/3p4/3P4/8/8/4K3 w - - 0 1";
+
+#[test]
+fn test_tree_reuse() {
+    let mut board = Board::from_fen(FEN);
+    let mut mcts = Mcts::new();
+
+    mcts.search(&mut board, 300);
+    let visits = mcts.root_visits();
+    assert!(visits > 1);
+
+    // the same position continues from the stored tree
+    mcts.search(&mut board, 1);
+    assert!(mcts.root_visits() > visits);
+
+    let pv = mcts.principal_variation();
+    assert!(pv.len() >= 2);
+
+    // our move and the opponent's reply descend into the subtree
+    board.make_move(&pv[0]);
+    board.make_move(&pv[1]);
+
+    mcts.search(&mut board, 1);
+    assert!(mcts.root_visits() > 1);
+
+    // an unrelated position starts over
+    let mut other = Board::startpos();
+    mcts.search(&mut other, 1);
+    assert!(mcts.root_visits() < visits);
+}
+
+#[test]
+fn test_node_limit() {
+    let mut board = Board::from_fen(FEN);
+    let mut mcts = Mcts::new();
+    mcts.set_max_nodes(8);
+
+    assert!(mcts.iterative_deepening(&mut board, 5, 300).is_some());
+    assert!(mcts.tree_size() <= 8);
+    assert!(mcts.root_visits() > 8);
+}
+
+fn puct_best_move(fen: &str, prior: Prior) -> (usize, usize) {
+    let mut board = Board::from_fen(fen);
+    let mut mcts = Mcts::new();
+    mcts.set_options(MctsOptions { selection: Selection::Puct, prior, ..Default::default() });
+
+    let best_move = mcts.search(&mut board, 300).unwrap();
+    (best_move.to.x, best_move.to.y)
+}
+
+#[test]
+fn test_puct_priors() {
+    // promotions dominate the move ordering priors
+    assert_eq!(puct_best_move("8/P3k3/8/8/8/8/5PPP/4K3 w - - 0 40", Prior::MoveOrdering), (0, 0));
+
+    // the queen is hanging
+    assert_eq!(puct_best_move("4k3/8/8/3q4/8/8/5PPP/3RK3 w - - 0 40", Prior::Evaluation), (3, 3));
+}
+
+#[test]
+fn test_puct_node_limit() {
+    let mut board = Board::from_fen(FEN);
+    let mut mcts = Mcts::new();
+    mcts.set_options(MctsOptions { max_nodes: 20, selection: Selection::Puct, ..Default::default() });
+
+    assert!(mcts.search(&mut board, 200).is_some());
+    assert!(mcts.tree_size() <= 20);
+}
+
+#[test]
+fn test_leaf_evaluation() {
+    // the probe searches are much slower per iteration
+    for (leaf, time_limit) in [(Leaf::Quiescence, 300), (Leaf::Minimax, 2500), (Leaf::Rollout, 300)] {
+        let mut board = Board::from_fen("4k3/8/8/3q4/8/8/5PPP/3RK3 w - - 0 40");
+        let mut mcts = Mcts::new();
+        mcts.set_options(MctsOptions { leaf, ..Default::default() });
+        // the static evaluation already punishes hanging pieces, so favour exploitation
+        mcts.exp = 0.2;
+
+        let best_move = mcts.search(&mut board, time_limit).unwrap();
+
+        // the rollouts are too noisy to rely on in a short search
+        if leaf != Leaf::Rollout {
+            assert_eq!((best_move.to.x, best_move.to.y), (3, 3), "{:?}", leaf);
+        }
+    }
+}
+
+#[test]
+fn test_parallel_search() {
+    let mut board = Board::from_fen("4k3/8/8/3q4/8/8/5PPP/3RK3 w - - 0 40");
+    let mut mcts = Mcts::new();
+    mcts.set_threads(4);
+    mcts.exp = 0.2;
+
+    let best_move = mcts.search(&mut board, 500).unwrap();
+    assert_eq!((best_move.to.x, best_move.to.y), (3, 3));
+
+    // the shared tree keeps growing from the same root
+    let visits = mcts.root_visits();
+    mcts.search(&mut board, 100);
+    assert!(mcts.root_visits() > visits);
+
+    let mut board = Board::from_fen(FEN);
+    let mut mcts = Mcts::new();
+    mcts.set_threads(4);
+    mcts.set_max_nodes(30);
+
+    assert!(mcts.search(&mut board, 200).is_some());
+    assert!(mcts.tree_size() <= 30);
+}
+
+#[test]
+fn test_win_probability() {
+    assert!((win_probability(0.0) - 0.5).abs() < 1e-9);
+    assert!((win_probability(4.0) - 10.0 / 11.0).abs() < 1e-9);
+    assert!((win_probability(1.5) + win_probability(-1.5) - 1.0).abs() < 1e-9);
+    assert!(win_probability(1.0) > win_probability(0.5));
+
+    assert_eq!(win_probability(1e10), 1.0);
+    assert_eq!(win_probability(-1e10), 0.0);
+}
+
+#[test]
+fn test_search_stats() {
+    let mut board = Board::from_fen("4k3/8/8/3q4/8/8/5PPP/3RK3 w - - 0 40");
+    let mut mcts = Mcts::new();
+    mcts.set_options(MctsOptions { selection: Selection::Puct, prior: Prior::Evaluation, ..Default::default() });
+
+    assert!(mcts.stats().is_none());
+    let best_move = mcts.search(&mut board, 300).unwrap();
+    let stats = mcts.stats().unwrap();
+
+    // the root itself was evaluated once before it had children
+    let child_visits: u32 = stats.root_moves.iter().map(|root_move| root_move.visits).sum();
+    assert_eq!(stats.visits, child_visits + 1);
+    assert_eq!(stats.root_moves.len(), board.get_total_legal_moves(None).len());
+    assert!(stats.root_moves.windows(2).all(|pair| pair[0].visits >= pair[1].visits));
+
+    let priors: f64 = stats.root_moves.iter().map(|root_move| root_move.prior).sum();
+    assert!((priors - 1.0).abs() < 1e-9);
+
+    assert_eq!(stats.root_moves[0].m, best_move);
+    assert_eq!(stats.pv[0], best_move);
+    assert!(stats.centipawns() > 300);
+
+    let lines = stats.info_lines();
+    assert_eq!(lines.len(), stats.root_moves.len() + 1);
+    assert!(lines.last().unwrap().starts_with(&format!("info depth {} score cp", stats.pv.len())));
+}
+
+#[test]
+fn test_centipawns() {
+    assert_eq!(centipawns(0.5), 0);
+    assert_eq!(centipawns(win_probability(1.5)), 150);
+    assert_eq!(centipawns(win_probability(-3.2)), -320);
+
+    assert!(centipawns(1.0) > 2000);
+    assert!(centipawns(0.0) < -2000);
+}
+
+#[test]
+fn test_selection_option() {
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option("setoption name MCTS Selection value PUCT", &mut output).unwrap();
+    protocol.set_option("setoption name MCTS Prior value Evaluation", &mut output).unwrap();
+    assert_eq!(protocol.engine.mcts_options.selection, Selection::Puct);
+    assert_eq!(protocol.engine.mcts_options.prior, Prior::Evaluation);
+
+    protocol.set_option("setoption name MCTS Leaf value Minimax", &mut output).unwrap();
+    assert_eq!(protocol.engine.mcts_options.leaf, Leaf::Minimax);
+
+    protocol.set_option("setoption name MCTS Selection value minimax", &mut output).unwrap();
+    assert_eq!(protocol.engine.mcts_options.selection, Selection::Puct);
+
+    protocol.set_option("setoption name Threads value 4", &mut output).unwrap();
+    assert_eq!(protocol.engine.threads, 4);
+
+    protocol.set_option("setoption name Threads value 1000", &mut output).unwrap();
+    assert_eq!(protocol.engine.threads, 64);
+}
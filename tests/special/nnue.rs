@@ -0,0 +1,93 @@
+use std::{env, sync::Arc};
+
+use mchess::{board::Board, evaluation::Evaluator, nnue::{Network, NnueEvaluator, NnueState}, protocol::UciProtocol};
+
+const POSITIONS: [&str; 5] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/pppq1ppp/2n2n2/3pp3/3PP3/2N2N2/PPPQ1PPP/R3K2R w KQkq - 0 1",
+    "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1",
+    "1n2k3/P7/8/8/8/8/7p/4K1N1 w - - 0 1",
+    "1n2k3/P7/8/8/8/8/7p/4K1N1 b - - 0 1"
+];
+
+fn assert_accumulator(board: &Board, network: &Network) {
+    let state = board.nnue.as_ref().expect("accumulator detached");
+    assert_eq!(*state.current(), network.refresh(board));
+}
+
+#[test]
+fn test_incremental_accumulator() {
+    let network = Arc::new(Network::random(32, 7).unwrap());
+
+    for fen in POSITIONS {
+        let mut board = Board::from_fen(fen);
+        board.nnue = Some(NnueState::new(network.clone(), &board));
+
+        for m in board.get_total_legal_moves(None) {
+            let history = board.make_move(&m);
+            assert_accumulator(&board, &network);
+
+            for reply in board.get_total_legal_moves(None) {
+                let reply_history = board.make_move(&reply);
+                assert_accumulator(&board, &network);
+
+                board.unmake_move(&reply, &reply_history);
+                assert_accumulator(&board, &network);
+            }
+
+            board.unmake_move(&m, &history);
+            assert_accumulator(&board, &network);
+        }
+    }
+}
+
+#[test]
+fn test_network_file() {
+    let network = Network::random(32, 3).unwrap();
+    let path = env::temp_dir().join(format!("mchess-nnue-{}.bin", std::process::id()));
+
+    network.save(&path).unwrap();
+    let loaded = Network::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let board = Board::startpos();
+    assert_eq!(network.refresh(&board), loaded.refresh(&board));
+    assert!(Network::new(30, vec![], vec![], vec![], 0).is_err());
+}
+
+#[test]
+fn test_nnue_symmetry() {
+    let evaluator = NnueEvaluator::new(Arc::new(Network::random(64, 11).unwrap()));
+
+    // the same position seen from the other side scores the same for the side to move
+    let mut white = Board::from_fen("r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1");
+    let mut black = Board::from_fen("rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 0 1");
+
+    let white_value = evaluator.evaluate(&mut white).to_value();
+    let black_value = evaluator.evaluate(&mut black).to_value();
+
+    assert!((white_value + black_value).abs() < 1e-9);
+}
+
+#[test]
+fn test_use_nnue_option() {
+    let path = env::temp_dir().join(format!("mchess-nnue-option-{}.bin", std::process::id()));
+    Network::random(32, 5).unwrap().save(&path).unwrap();
+
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option("setoption name UseNNUE value true", &mut output).unwrap();
+    assert!(!protocol.engine.use_nnue || protocol.engine.network.is_none());
+
+    protocol.set_option(&format!("setoption name EvalFile value {}", path.display()), &mut output).unwrap();
+    protocol.set_option("setoption name UseNNUE value true", &mut output).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(protocol.engine.network.is_some());
+    assert!(protocol.engine.use_nnue);
+
+    let mut board = Board::startpos();
+    assert!(protocol.engine.search(&mut board, Some(2), None).is_some());
+    assert!(board.nnue.is_some());
+}
\ No newline at end of file
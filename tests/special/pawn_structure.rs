@@ -0,0 +1,106 @@
+use mchess::{board::Board, evaluation::{evaluate_pawns, probe_pawn_structure}};
+
+use crate::common::alg;
+
+#[test]
+fn test_passed_pawns() {
+    let board = Board::from_fen("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+    let entry = probe_pawn_structure(&board);
+
+    assert_eq!(entry.white.passed, alg("d5").to_bitboard());
+
+    let board = Board::from_fen("4k3/2p5/8/3P4/8/8/8/4K3 w - - 0 1");
+    let entry = probe_pawn_structure(&board);
+
+    assert_eq!(entry.white.passed, 0);
+    assert_eq!(entry.black.passed, 0);
+    assert_eq!(entry.white.candidates, 0);
+}
+
+#[test]
+fn test_doubled_and_backward_pawns() {
+    let board = Board::from_fen("4k3/8/8/8/3P4/3P4/8/4K3 w - - 0 1");
+    let entry = probe_pawn_structure(&board);
+
+    assert_eq!(entry.white.doubled, alg("d3").to_bitboard());
+    assert_eq!(entry.white.passed, alg("d4").to_bitboard());
+
+    let board = Board::from_fen("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1");
+    let entry = probe_pawn_structure(&board);
+
+    assert_eq!(entry.white.backward, alg("d3").to_bitboard());
+    assert_eq!(entry.white.connected, alg("c4").to_bitboard());
+}
+
+#[test]
+fn test_candidate_passer() {
+    let board = Board::from_fen("4k3/8/p7/8/1P6/2P5/8/4K3 w - - 0 1");
+    let entry = probe_pawn_structure(&board);
+
+    assert_eq!(entry.white.passed, alg("c3").to_bitboard());
+    assert_ne!(entry.white.candidates & alg("b4").to_bitboard(), 0);
+}
+
+#[test]
+fn test_passed_pawn_advancement() {
+    let mut far = Board::from_fen("4k3/8/3P4/8/8/8/8/4K3 b - - 0 1");
+    let mut near = Board::from_fen("4k3/8/8/8/8/3P4/8/4K3 b - - 0 1");
+
+    assert!(evaluate_pawns(&mut far).to_value() > evaluate_pawns(&mut near).to_value());
+
+    // black king is outside the square of the a-pawn
+    let mut unstoppable = Board::from_fen("8/8/7k/P7/8/8/8/4K3 b - - 0 1");
+    let mut caught = Board::from_fen("8/8/2k5/P7/8/8/8/4K3 b - - 0 1");
+
+    assert!(evaluate_pawns(&mut unstoppable).to_value() > evaluate_pawns(&mut caught).to_value() + 3.0);
+}
+
+#[test]
+fn test_square_of_the_pawn() {
+    // one file further and the king is outside the square, whichever side is to move
+    let boundaries = [
+        ("5k2/8/8/P7/8/8/8/7K b - - 0 1", "4k3/8/8/P7/8/8/8/7K b - - 0 1"),
+        ("4k3/8/8/P7/8/8/8/7K w - - 0 1", "3k4/8/8/P7/8/8/8/7K w - - 0 1")
+    ];
+
+    for (unstoppable, caught) in boundaries {
+        let unstoppable = evaluate_pawns(&mut Board::from_fen(unstoppable)).to_value();
+        let caught = evaluate_pawns(&mut Board::from_fen(caught)).to_value();
+
+        assert!(unstoppable > caught + 3.0, "{} {}", unstoppable, caught);
+        assert!(caught < 4.0, "{}", caught);
+    }
+}
+
+#[test]
+fn test_pawn_hash() {
+    let original = Board::startpos();
+    let mut board = original.clone();
+
+    for m in board.get_total_legal_moves(None) {
+        let history = board.make_move(&m);
+
+        if format!("{:?}", m) == "g1f3" {
+            assert_eq!(board.pawn_hash, original.pawn_hash, "Piece move changed the pawn hash");
+        }
+
+        if format!("{:?}", m) == "e2e4" {
+            let expected = Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+            assert_eq!(board.pawn_hash, expected.pawn_hash, "Incremental pawn hash mismatch");
+        }
+
+        board.unmake_move(&m, &history);
+        assert_eq!(board.pawn_hash, original.pawn_hash, "Pawn hash mismatch after unmake_move");
+    }
+
+    let mut board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    let promoted = Board::from_fen("1Q2k3/8/8/8/8/8/8/4K3 b - - 0 1");
+
+    for m in board.get_total_legal_moves(None) {
+        if format!("{:?}", m) == "b7b8q" {
+            board.make_move(&m);
+            assert_eq!(board.pawn_hash, promoted.pawn_hash, "Promotion did not remove the pawn from the pawn hash");
+            break;
+        }
+    }
+}
\ No newline at end of file
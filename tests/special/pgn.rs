@@ -0,0 +1,189 @@
+use mchess::{pgn::{self, PgnError}, protocol::UciProtocol};
+
+const ANNOTATED: &str = r#"[Event "Annotated"]
+[White "A \"quoted\" name"]
+[Result "1-0"]
+
+{Opening comment}
+1. e4 e5 2. Nf3 $1 Nc6!? (2... d6 {Philidor,
+spread over two lines} 3. d4 (3. Bc4) 3... exd4) 3. Bb5 ; rest of line
+a6 4. Ba4 Nf6 5. 0-0 Be7 1-0
+"#;
+
+#[test]
+fn test_parse_game() {
+    let games = pgn::parse(ANNOTATED).unwrap();
+    assert_eq!(games.len(), 1);
+
+    let game = &games[0];
+    assert_eq!(game.header("White"), Some("A \"quoted\" name"));
+    assert_eq!(game.result, "1-0");
+    assert_eq!(game.comments, vec!["Opening comment"]);
+
+    let sans: Vec<&str> = game.moves.iter().map(|m| m.san.as_str()).collect();
+    assert_eq!(sans, vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "0-0", "Be7"]);
+
+    assert_eq!(game.moves[2].nags, vec![1]);
+    assert_eq!(game.moves[3].nags, vec![5]);
+    assert_eq!(game.moves[4].comments, vec!["rest of line"]);
+    assert_eq!((game.moves[4].line, game.moves[4].column), (7, 53));
+
+    let variation = &game.moves[3].variations[0];
+    assert_eq!(variation.iter().map(|m| m.san.as_str()).collect::<Vec<_>>(), vec!["d6", "d4", "exd4"]);
+    assert_eq!(variation[0].comments, vec!["Philidor, spread over two lines"]);
+    assert_eq!(variation[1].variations[0][0].san, "Bc4");
+
+    game.validate().unwrap();
+
+    let mainline = game.mainline().unwrap();
+    assert_eq!(format!("{:?}", mainline[8]), "e1g1");
+}
+
+#[test]
+fn test_illegal_move() {
+    let games = pgn::parse("[Event \"x\"]\n\n1. e4 e5 2. Ke3 *\n").unwrap();
+    assert_eq!(games[0].result, "*");
+    assert_eq!(games[0].mainline(), Err(PgnError { line: 3, column: 13, message: "illegal move Ke3".to_string() }));
+
+    // illegal moves inside a variation are found as well
+    let games = pgn::parse("1. e4 (1. e5) 1... e5 *").unwrap();
+    assert!(games[0].mainline().is_ok());
+    assert_eq!(games[0].validate().unwrap_err().column, 11);
+}
+
+#[test]
+fn test_syntax_errors() {
+    let error = pgn::parse("[Event \"x\"]\n\n1. e4 (1. d4 d5 *\n").unwrap_err();
+    assert_eq!((error.line, error.column), (3, 17));
+
+    let error = pgn::parse("1. e4 e5) *").unwrap_err();
+    assert_eq!((error.line, error.column, error.message.as_str()), (1, 9, "unexpected )"));
+
+    let error = pgn::parse("[Event \"x]\n\n1. e4 *").unwrap_err();
+    assert_eq!(error.line, 1);
+
+    assert!(pgn::parse("1. e4 {never closed").is_err());
+}
+
+#[test]
+fn test_recovery() {
+    let text = "[Event \"broken\"]\n\n1. e4 e5) 2. Nf3 *\n\n[Event \"fine\"]\n[Result \"0-1\"]\n\n1. d4 d5 0-1\n";
+    let games: Vec<_> = pgn::games(text).collect();
+
+    assert_eq!(games.len(), 2);
+    assert!(games[0].is_err());
+
+    let game = games[1].as_ref().unwrap();
+    assert_eq!(game.header("Event"), Some("fine"));
+    assert_eq!(game.moves.len(), 2);
+}
+
+#[test]
+fn test_setup_position() {
+    let text = "[FEN \"4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1\"]\n[SetUp \"1\"]\n\n1. exd6e.p. Kd7 2. Kd2 *";
+    let games = pgn::parse(text).unwrap();
+
+    let mainline = games[0].mainline().unwrap();
+    assert_eq!(format!("{:?}", mainline[0]), "e5d6");
+    assert_eq!(mainline.len(), 3);
+}
+#[test]
+fn test_export() {
+    let game = &pgn::parse(ANNOTATED).unwrap()[0];
+    let exported = game.to_string();
+
+    assert!(exported.starts_with("[Event \"Annotated\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n"));
+    assert!(exported.contains("[White \"A \\\"quoted\\\" name\"]"));
+    assert!(exported.replace('\n', " ").contains("2. Nf3 $1 Nc6 $5 (2... d6 {Philidor, spread over two lines} 3. d4 (3. Bc4) 3... exd4) 3. Bb5"));
+    assert!(exported.lines().all(|line| line.len() < 80));
+
+    // exporting and parsing again gives the same game
+    let reparsed = &pgn::parse(&exported).unwrap()[0];
+    assert_eq!(reparsed.to_string(), exported);
+    assert_eq!(reparsed.mainline(), game.mainline());
+}
+
+#[test]
+fn test_game_record() {
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option("setoption name UCI_Opponent value none 1850 human Some Player", &mut output).unwrap();
+    protocol.handle_position("position startpos moves f2f3 e7e5 g2g4", &mut output).unwrap();
+    protocol.handle_go("go depth 1", &mut output).unwrap();
+
+    // the GUI sends the engine's move back with the next position
+    let output = String::from_utf8(output).unwrap();
+    let best_move = output.lines().find_map(|line| line.strip_prefix("bestmove ")).unwrap().to_string();
+    protocol.handle_position(&format!("position startpos moves f2f3 e7e5 g2g4 {}", best_move), &mut Vec::new()).unwrap();
+
+    let game = protocol.game();
+    assert_eq!(game.header("Black"), Some("mchess"));
+    assert_eq!(game.header("White"), Some("Some Player"));
+    assert_eq!(game.header("WhiteElo"), Some("1850"));
+    assert!(game.moves[3].comments[0].starts_with("[%eval "));
+
+    if best_move == "d8h4" {
+        assert_eq!(game.moves[3].san, "Qh4#");
+        assert_eq!(game.result, "0-1");
+        assert!(game.to_string().contains("2. g4 Qh4# {[%eval"));
+    }
+
+    // the base time is taken from the first search of the game
+    let mut protocol = UciProtocol::new();
+    protocol.handle_position("position startpos", &mut Vec::new()).unwrap();
+    protocol.handle_go("go depth 1 wtime 60000 btime 60000 winc 1000 binc 1000", &mut Vec::new()).unwrap();
+    assert_eq!(protocol.game().header("TimeControl"), Some("60+1"));
+}
+
+#[test]
+fn test_new_game() {
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option("setoption name EngineType value MCTS", &mut output).unwrap();
+    protocol.set_option("setoption name Threads value 2", &mut output).unwrap();
+    protocol.set_option("setoption name Skill Level value 3", &mut output).unwrap();
+    protocol.handle_position("position startpos moves e2e4 e7e5", &mut output).unwrap();
+    assert_eq!(protocol.game().moves.len(), 2);
+
+    // the game starts over with the options it had
+    protocol.new_game();
+    assert!(protocol.game().moves.is_empty());
+    assert_eq!(protocol.skill_level, 3);
+    assert_eq!(protocol.engine.search_threads(), 2);
+}
+
+#[test]
+fn test_eval_comment() {
+    let fen = "4k3/8/8/8/8/8/8/3QK3 w - - 0 1";
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.handle_position(&format!("position fen {}", fen), &mut output).unwrap();
+    protocol.handle_go("go depth 2", &mut output).unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    let best_move = output.lines().find_map(|line| line.strip_prefix("bestmove ")).unwrap().to_string();
+    protocol.handle_position(&format!("position fen {} moves {}", fen, best_move), &mut Vec::new()).unwrap();
+
+    // a queen up is worth pawns, not centipawns
+    let comment = &protocol.game().moves[0].comments[0];
+    let eval: f64 = comment.trim_start_matches("[%eval ").trim_end_matches(']').parse().unwrap();
+    assert!(eval > 5.0 && eval < 100.0, "{}", comment);
+}
+
+#[test]
+fn test_check_and_result() {
+    let mut protocol = UciProtocol::new();
+    protocol.handle_position("position fen 4k3/8/8/8/8/8/8/R3K3 b - - 0 1 moves e8d7 a1a7 d7c6", &mut Vec::new()).unwrap();
+
+    let game = protocol.game();
+    assert_eq!(game.moves.iter().map(|m| m.san.as_str()).collect::<Vec<_>>(), vec!["Kd7", "Ra7+", "Kc6"]);
+    assert_eq!(game.header("SetUp"), Some("1"));
+    assert!(game.to_string().contains("\n1... Kd7 2. Ra7+ Kc6 *\n"));
+
+    // stalemate is a draw
+    protocol.handle_position("position fen k7/8/1Q6/8/8/8/8/4K3 w - - 0 1 moves e1d2 a8a7 b6c7 a7a8 c7b7", &mut Vec::new()).unwrap();
+    assert_eq!(protocol.game().result, "1/2-1/2");
+}
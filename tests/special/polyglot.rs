@@ -0,0 +1,139 @@
+use std::{env, fs};
+
+use mchess::{board::Board, book::BookSelection, polyglot::{decode_move, encode_move, PolyglotBook, PolyglotEntry}, protocol::UciProtocol};
+
+// the reference keys from the Polyglot book format description
+const KEYS: [(&str, u64); 9] = [
+    ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 0x463b96181691fc9c),
+    ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1", 0x823c9b50fd114196),
+    ("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2", 0x0756b94461c50fb0),
+    ("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2", 0x662fafb965db29d4),
+    ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", 0x22a48b5a8e47ff78),
+    ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3", 0x652a607ca3f242c1),
+    ("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4", 0x00fdd303c946bdd9),
+    ("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3", 0x3c8123ea7b067637),
+    ("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4", 0x5c3f9b829b279560)
+];
+
+fn play(board: &mut Board, uci: &str) {
+    let m = board.get_total_legal_moves(None).into_iter()
+        .find(|m| format!("{:?}", m) == uci)
+        .unwrap();
+
+    board.make_move(&m);
+}
+
+#[test]
+fn test_polyglot_hash() {
+    for (fen, key) in KEYS {
+        assert_eq!(Board::from_fen(fen).polyglot_hash(), key, "{}", fen);
+    }
+
+    // the same keys reached by playing the moves
+    let mut board = Board::startpos();
+    for (uci, (_, key)) in ["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"].iter().zip(&KEYS[1..7]) {
+        play(&mut board, uci);
+        assert_eq!(board.polyglot_hash(), *key, "{}", uci);
+    }
+}
+
+#[test]
+fn test_move_encoding() {
+    let mut board = Board::from_fen("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+    // e1h1 and e1a1, castling is the king taking its own rook
+    let castling = decode_move(&mut board, 7 | (4 << 6)).unwrap();
+    assert_eq!(format!("{:?}", castling), "e1g1");
+    let castling = decode_move(&mut board, 4 << 6).unwrap();
+    assert_eq!(format!("{:?}", castling), "e1c1");
+
+    // b7a8 promoting to a knight
+    let promotion = decode_move(&mut board, 56 | (49 << 6) | (1 << 12)).unwrap();
+    assert_eq!(format!("{:?}", promotion), "b7a8n");
+
+    for m in board.get_total_legal_moves(None) {
+        assert_eq!(format!("{:?}", decode_move(&mut board, encode_move(&m)).unwrap()), format!("{:?}", m));
+    }
+
+    assert!(decode_move(&mut board, 0).is_none());
+}
+
+#[test]
+fn test_book_file() {
+    let mut board = Board::startpos();
+    let mut book = PolyglotBook::new();
+
+    let moves = board.get_total_legal_moves(None);
+    let e4 = moves.iter().find(|m| format!("{:?}", m) == "e2e4").unwrap();
+    let d4 = moves.iter().find(|m| format!("{:?}", m) == "d2d4").unwrap();
+    let a3 = moves.iter().find(|m| format!("{:?}", m) == "a2a3").unwrap();
+
+    book.add(&board, d4, 10);
+    book.add(&board, e4, 8);
+    book.add(&board, e4, 4);
+    book.add(&board, a3, 0);
+
+    play(&mut board, "e2e4");
+    let reply = board.get_total_legal_moves(None).into_iter().find(|m| format!("{:?}", m) == "c7c5").unwrap();
+    book.add(&board, &reply, 1);
+
+    let path = env::temp_dir().join(format!("mchess-polyglot-{}.bin", std::process::id()));
+    book.save(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), 4 * 16);
+
+    let loaded = PolyglotBook::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let start = loaded.entries(0x463b96181691fc9c);
+    assert_eq!(start.len(), 3);
+    assert_eq!(start[0], PolyglotEntry { key: 0x463b96181691fc9c, m: encode_move(e4), weight: 12, learn: 0 });
+    assert_eq!(loaded.entries(0x823c9b50fd114196).len(), 1);
+
+    let mut board = Board::startpos();
+    assert_eq!(loaded.moves(&mut board).len(), 2);
+    assert_eq!(format!("{:?}", loaded.pick_move(&mut board, BookSelection::Best).unwrap()), "e2e4");
+
+    // moves without weight are never picked
+    for _ in 0..20 {
+        let m = format!("{:?}", loaded.pick_move(&mut board, BookSelection::Weighted).unwrap());
+        assert!(m == "e2e4" || m == "d2d4");
+    }
+
+    let mut board = Board::from_fen(KEYS[3].0);
+    assert!(loaded.pick_move(&mut board, BookSelection::Best).is_none());
+}
+
+#[test]
+fn test_corrupted_book() {
+    let path = env::temp_dir().join(format!("mchess-polyglot-corrupted-{}.bin", std::process::id()));
+    fs::write(&path, [0u8; 20]).unwrap();
+
+    assert!(PolyglotBook::load(&path).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_book_option() {
+    let board = Board::startpos();
+    let mut book = PolyglotBook::new();
+    let m = Board::startpos().get_total_legal_moves(None).into_iter().find(|m| format!("{:?}", m) == "g1f3").unwrap();
+    book.add(&board, &m, 1);
+
+    let path = env::temp_dir().join(format!("mchess-polyglot-option-{}.bin", std::process::id()));
+    book.save(&path).unwrap();
+
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option(&format!("setoption name BookFile value {}", path.display()), &mut output).unwrap();
+    protocol.set_option("setoption name EnableBook value true", &mut output).unwrap();
+    protocol.set_option("setoption name BookVariety value Weighted", &mut output).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(protocol.engine.polyglot.as_ref().unwrap().len(), 1);
+    assert_eq!(protocol.engine.book_options.selection, BookSelection::Weighted);
+
+    let mut board = Board::startpos();
+    let m = protocol.engine.search(&mut board, Some(1), None).unwrap();
+    assert_eq!(format!("{:?}", m), "g1f3");
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use mchess::board::{Board, SanError};
+
+const POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    // knights and rooks that need disambiguation by file, rank or both
+    "4k3/8/1N3N2/8/1N3N2/8/8/R3K2R w - - 0 1",
+    "4k3/1P6/8/3pP3/8/8/6p1/4K2R w K d6 0 1",
+    "4k3/1P6/8/3pP3/8/8/6p1/4K2R b K - 0 1",
+    "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"
+];
+
+#[test]
+fn test_san_round_trip() {
+    for fen in POSITIONS {
+        let mut board = Board::from_fen(fen);
+
+        for m in board.get_total_legal_moves(None) {
+            let san = m.to_san(&board);
+            let parsed = board.parse_san(&san).unwrap_or_else(|e| panic!("{} in {}: {}", san, fen, e));
+
+            assert_eq!(format!("{:?}", parsed), format!("{:?}", m), "{} in {}", san, fen);
+
+            // and one ply deeper
+            let history = board.make_move(&m);
+            for reply in board.get_total_legal_moves(None) {
+                let san = reply.to_san(&board);
+                assert_eq!(format!("{:?}", board.parse_san(&san).unwrap()), format!("{:?}", reply), "{} in {}", san, fen);
+            }
+            board.unmake_move(&m, &history);
+        }
+    }
+}
+
+fn parse(fen: &str, san: &str) -> Result<String, SanError> {
+    Board::from_fen(fen).parse_san(san).map(|m| format!("{:?}", m))
+}
+
+#[test]
+fn test_san_variants() {
+    let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(parse(castling, "0-0").unwrap(), "e1g1");
+    assert_eq!(parse(castling, "O-O-O+").unwrap(), "e1c1");
+    assert_eq!(parse(castling, "Ra1-d1").unwrap(), "a1d1");
+
+    let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+    assert_eq!(parse(en_passant, "exd6e.p.").unwrap(), "e5d6");
+    assert_eq!(parse(en_passant, "exd6 e.p.").unwrap(), "e5d6");
+    assert_eq!(parse(en_passant, "ed6").unwrap(), "e5d6");
+
+    let promotion = "4k3/1P6/8/8/8/8/8/4K3 w - - 0 1";
+    assert_eq!(parse(promotion, "b8=Q").unwrap(), "b7b8q");
+    assert_eq!(parse(promotion, "b8N+").unwrap(), "b7b8n");
+    assert_eq!(parse(promotion, "b8(R)").unwrap(), "b7b8r");
+    assert_eq!(parse(promotion, "b8/B").unwrap(), "b7b8b");
+    assert_eq!(parse(promotion, "b8").unwrap(), "b7b8q");
+
+    let knights = "4k3/8/1N3N2/8/1N3N2/8/8/4K3 w - - 0 1";
+    assert_eq!(parse(knights, "Nb6d5").unwrap(), "b6d5");
+    assert_eq!(parse(knights, "Nf4xd5!?").unwrap(), "f4d5");
+    assert_eq!(parse(knights, "N6a4").unwrap(), "b6a4");
+    assert_eq!(parse(knights, "Nbd7").unwrap(), "b6d7");
+    // redundant disambiguation
+    assert_eq!(parse(knights, "Nb6c8").unwrap(), "b6c8");
+
+    assert_eq!(parse(knights, "Nd5"), Err(SanError::Ambiguous("Nd5".to_string())));
+    assert_eq!(parse(knights, "Nd4"), Err(SanError::Illegal("Nd4".to_string())));
+    assert_eq!(parse(knights, "e9"), Err(SanError::Syntax("e9".to_string())));
+    assert_eq!(parse(knights, ""), Err(SanError::Syntax("".to_string())));
+    assert_eq!(parse(knights, "Nbb6d5"), Err(SanError::Syntax("Nbb6d5".to_string())));
+}
\ No newline at end of file
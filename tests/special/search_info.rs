@@ -0,0 +1,96 @@
+use std::{sync::atomic::Ordering, thread, time::{Duration, Instant}};
+
+use mchess::{board::Board, engine::{Engine, EngineType}, search::to_centipawns, time_manager::TimeManager};
+use tokio::sync::mpsc;
+
+const POSITION: &str = "2k2r2/1ppp4/pn5q/8/8/8/3B1PPP/1Q4K1 w - - 0 1";
+
+#[test]
+fn test_search_progress() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut engine = Engine::new(EngineType::Minimax, false);
+    engine.set_info_sender(Some(sender));
+
+    let mut board = Board::from_fen(POSITION);
+    let m = engine.iterative_deepening(&mut board, 3, TimeManager::from_budget(60000)).unwrap();
+
+    let mut infos = Vec::new();
+    while let Ok(info) = receiver.try_recv() {
+        infos.push(info);
+    }
+
+    // one per completed depth, the last one leads with the move played
+    assert_eq!(infos.iter().map(|info| info.depth).collect::<Vec<_>>(), vec![1, 2, 3]);
+    assert!(infos.windows(2).all(|pair| pair[0].nodes <= pair[1].nodes));
+
+    let last = infos.last().unwrap();
+    assert_eq!(last.pv[0], m);
+    assert!(last.to_uci().starts_with(&format!("info depth 3 score cp {} nodes {} nps ", last.score, last.nodes)));
+    assert!(last.to_uci().ends_with(&format!(" pv {}", last.pv.iter().map(|m| format!("{:?}", m)).collect::<Vec<_>>().join(" "))));
+
+    // switching engines keeps the channel
+    engine.switch_to(EngineType::MCTS);
+    engine.iterative_deepening(&mut board, 2, TimeManager::from_budget(200)).unwrap();
+    assert!(receiver.try_recv().is_ok());
+    while receiver.try_recv().is_ok() {}
+
+    // the channel closes once the engine lets go of it
+    engine.set_info_sender(None);
+    assert_eq!(receiver.try_recv(), Err(mpsc::error::TryRecvError::Disconnected));
+}
+
+#[test]
+fn test_stop_from_another_thread() {
+    for engine_type in [EngineType::Minimax, EngineType::MCTS] {
+        let mut engine = Engine::new(engine_type, false);
+        let stop = engine.stop_signal();
+
+        let start = Instant::now();
+        let search = thread::spawn(move || {
+            let mut board = Board::from_fen(POSITION);
+            let m = engine.iterative_deepening(&mut board, 50, TimeManager::fixed(60000));
+
+            (m, engine)
+        });
+
+        thread::sleep(Duration::from_millis(300));
+        stop.store(true, Ordering::Relaxed);
+
+        // the search answers with what it has and is ready for the next one
+        let (m, mut engine) = search.join().unwrap();
+        assert!(m.is_some());
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert!(!stop.load(Ordering::Relaxed));
+
+        // the signal survives switching engines
+        engine.switch_to(EngineType::Minimax);
+        engine.stop();
+        assert!(stop.load(Ordering::Relaxed));
+    }
+}
+
+#[test]
+fn test_search_score_units() {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut engine = Engine::new(EngineType::Minimax, false);
+    engine.set_info_sender(Some(sender));
+
+    // black without a queen, from both sides of the board
+    for (fen, sign) in [("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 1), ("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1", -1)] {
+        let mut board = Board::from_fen(fen);
+        engine.iterative_deepening(&mut board, 2, TimeManager::from_budget(60000)).unwrap();
+
+        let mut last = None;
+        while let Ok(info) = receiver.try_recv() {
+            last = Some(info);
+        }
+
+        // a queen is worth hundreds of centipawns
+        let score = last.unwrap().score * sign;
+        assert!((500..2000).contains(&score), "{}", score);
+    }
+
+    // mates stay finite and can be negated
+    assert_eq!(to_centipawns(1e10), i32::MAX);
+    assert_eq!(-to_centipawns(1e10), to_centipawns(-1e10));
+}
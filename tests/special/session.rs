@@ -0,0 +1,99 @@
+use std::{sync::atomic::Ordering, time::{Duration, Instant}};
+
+use mchess::{protocol::UciProtocol, session::{SessionError, SessionLimits, Sessions}};
+
+fn limits() -> SessionLimits {
+    SessionLimits {
+        max_sessions: 2,
+        max_sessions_per_owner: 2,
+        idle_timeout: Duration::from_secs(60),
+        hash_size: 1,
+        max_mcts_nodes: 5000,
+        max_threads: 2
+    }
+}
+
+#[test]
+fn test_session_limits() {
+    let mut sessions = Sessions::new(limits());
+
+    let first = sessions.create(UciProtocol::new(), "key").unwrap();
+    let second = sessions.create(UciProtocol::new(), "key").unwrap();
+    assert_ne!(first, second);
+    assert_eq!(sessions.create(UciProtocol::new(), "key"), Err(SessionError::Full(2)));
+
+    // the engine is shrunk to the session and cannot be grown again
+    let session = sessions.touch(&first, "key").unwrap();
+    let mut protocol = session.lock();
+    assert_eq!(protocol.engine.hash_size, 1);
+    assert_eq!(protocol.engine.mcts_options.max_nodes, 5000);
+
+    let mut output = Vec::new();
+    protocol.set_option("setoption name Hash value 1024", &mut output).unwrap();
+    protocol.set_option("setoption name MCTS Node Limit value 100000", &mut output).unwrap();
+    protocol.set_option("setoption name Threads value 64", &mut output).unwrap();
+    assert_eq!(protocol.engine.hash_size, 1);
+    assert_eq!(protocol.engine.mcts_options.max_nodes, 5000);
+    assert_eq!(protocol.engine.threads, 2);
+    drop(protocol);
+
+    assert!(sessions.remove(&first, "key").is_some());
+    assert!(sessions.remove(&first, "key").is_none());
+    assert_eq!(sessions.touch(&first, "key").err(), Some(SessionError::Unknown(first.clone())));
+    assert!(sessions.create(UciProtocol::new(), "key").is_ok());
+}
+
+#[test]
+fn test_session_eviction() {
+    let mut sessions = Sessions::new(limits());
+
+    let idle = sessions.create(UciProtocol::new(), "key").unwrap();
+    let active = sessions.create(UciProtocol::new(), "key").unwrap();
+
+    assert!(sessions.evict_idle(Instant::now()).is_empty());
+
+    // a search still running in an evicted session is stopped
+    let stop = sessions.get(&idle, "key").unwrap().stop.clone();
+
+    // a command keeps a session alive
+    let later = Instant::now() + Duration::from_secs(40);
+    sessions.touch(&active, "key").unwrap().last_active = later;
+
+    assert_eq!(sessions.evict_idle(Instant::now() + Duration::from_secs(60)), vec![idle.clone()]);
+    assert!(sessions.get(&idle, "key").is_none());
+    assert!(stop.load(Ordering::Relaxed));
+    assert_eq!(sessions.get(&active, "key").unwrap().commands, 1);
+
+    assert_eq!(sessions.evict_idle(later + Duration::from_secs(60)), vec![active]);
+    assert!(sessions.is_empty());
+}
+
+#[test]
+fn test_session_owner() {
+    let mut sessions = Sessions::new(limits());
+
+    let id = sessions.create(UciProtocol::new(), "key").unwrap();
+
+    // another identity cannot tell the session exists
+    assert_eq!(sessions.touch(&id, "other").err(), Some(SessionError::Unknown(id.clone())));
+    assert!(sessions.get(&id, "other").is_none());
+    assert!(sessions.remove(&id, "other").is_none());
+
+    assert_eq!(sessions.get(&id, "key").unwrap().commands, 0);
+    assert!(sessions.remove(&id, "key").is_some());
+}
+
+#[test]
+fn test_sessions_per_owner() {
+    let mut sessions = Sessions::new(SessionLimits { max_sessions: 3, ..limits() });
+
+    sessions.create(UciProtocol::new(), "key").unwrap();
+    let id = sessions.create(UciProtocol::new(), "key").unwrap();
+
+    // one identity cannot take the sessions of the others
+    assert_eq!(sessions.create(UciProtocol::new(), "key"), Err(SessionError::Full(2)));
+    assert!(sessions.create(UciProtocol::new(), "other").is_ok());
+
+    assert!(sessions.remove(&id, "key").is_some());
+    assert!(sessions.create(UciProtocol::new(), "key").is_ok());
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use std::time::Instant;
+
+use mchess::{board::Board, engine::{Engine, EngineType}, protocol::UciProtocol, skill::Skill, time_manager::TimeManager};
+use rand::{rngs::StdRng, SeedableRng};
+
+#[test]
+fn test_skill_levels() {
+    assert_eq!(Skill::from_elo(0), Skill::new(0));
+    assert_eq!(Skill::from_elo(1600), Skill::new(10));
+    assert_eq!(Skill::from_elo(5000), Skill::default());
+    assert_eq!(Skill::new(10).elo(), 1600);
+    assert!(!Skill::default().enabled());
+
+    assert_eq!(Skill::preset("Beginner"), Some(Skill::new(0)));
+    assert_eq!(Skill::preset("expert"), Some(Skill::default()));
+    assert_eq!(Skill::preset("7"), Some(Skill::new(7)));
+    assert_eq!(Skill::preset("21"), None);
+    assert_eq!(Skill::preset("grandmaster"), None);
+
+    // every level searches at least as much as the one below
+    for level in 1..20 {
+        let (lower, higher) = (Skill::new(level - 1), Skill::new(level));
+        assert!(higher.depth() >= lower.depth() && higher.nodes() >= lower.nodes() && higher.move_time() > lower.move_time());
+    }
+}
+
+#[test]
+fn test_skill_pick() {
+    let mut rng = StdRng::seed_from_u64(7);
+
+    // close moves are all played at the lowest level
+    let close = [50, 40, 30, 20];
+    let mut picked = [0; 4];
+
+    for _ in 0..200 {
+        picked[Skill::new(0).pick(&close, &mut rng)] += 1;
+    }
+
+    assert!(picked.iter().filter(|count| **count > 0).count() > 1, "{:?}", picked);
+
+    // a blunder is never chosen near the top
+    let blunder = [300, -300];
+    assert!((0..200).all(|_| Skill::new(19).pick(&blunder, &mut rng) == 0));
+
+    assert_eq!(Skill::new(0).pick(&[10], &mut rng), 0);
+    assert_eq!(Skill::new(0).pick(&[], &mut rng), 0);
+}
+
+#[test]
+fn test_weakened_search() {
+    let mut engine = Engine::new(EngineType::Minimax, false);
+    engine.set_skill(Skill::new(0));
+
+    let mut board = Board::from_fen("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1");
+    let legal = board.get_total_legal_moves(None);
+
+    // the lowest level thinks for a tenth of a second however long it is given
+    let start = Instant::now();
+    let m = engine.iterative_deepening(&mut board, 30, TimeManager::fixed(10000)).unwrap();
+
+    assert!(legal.contains(&m));
+    assert!(engine.last_score.is_some());
+    assert!(start.elapsed().as_millis() < 2000);
+}
+
+#[test]
+fn test_weakened_search_without_time() {
+    let mut engine = Engine::new(EngineType::Minimax, false);
+    engine.set_skill(Skill::new(0));
+
+    // no line fits in the time, the engine still moves
+    let mut board = Board::from_fen("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1");
+    let m = engine.iterative_deepening(&mut board, 30, TimeManager::fixed(0)).unwrap();
+
+    assert!(board.get_total_legal_moves(None).contains(&m));
+}
+
+#[test]
+fn test_hanging_piece() {
+    // the pawn takes the rook, every other move leaves it
+    let fen = "6k1/5ppp/8/3r4/4P3/8/5PPP/6K1 w - - 0 1";
+
+    for level in [10, 19] {
+        let mut engine = Engine::new(EngineType::Minimax, false);
+        engine.set_skill(Skill::new(level));
+
+        // deeper the king tables misjudge the pawn ending after the capture, so the depth is fixed
+        for _ in 0..3 {
+            let mut board = Board::from_fen(fen);
+            let m = engine.iterative_deepening(&mut board, 4, TimeManager::fixed(10000)).unwrap();
+
+            assert_eq!(format!("{:?}", m), "e4d5", "level {} score {:?}", level, engine.last_score);
+            assert!(engine.last_score.unwrap() > 0, "level {} score {:?}", level, engine.last_score);
+        }
+    }
+}
+
+#[test]
+fn test_skill_options() {
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option("setoption name Skill Level value 5", &mut output).unwrap();
+    assert_eq!(protocol.engine.skill, Skill::new(5));
+
+    // the rating only counts while the strength is limited
+    protocol.set_option("setoption name UCI_Elo value 2000", &mut output).unwrap();
+    assert_eq!(protocol.engine.skill, Skill::new(5));
+
+    protocol.set_option("setoption name UCI_LimitStrength value true", &mut output).unwrap();
+    assert_eq!(protocol.engine.skill, Skill::new(15));
+
+    protocol.set_option("setoption name UCI_LimitStrength value false", &mut output).unwrap();
+    protocol.set_option("setoption name Skill Level value 50", &mut output).unwrap();
+    assert!(!protocol.engine.skill.enabled());
+}
\ No newline at end of file
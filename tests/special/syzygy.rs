@@ -0,0 +1,170 @@
+use std::{env, fs, path::PathBuf};
+
+use mchess::{board::Board, protocol::UciProtocol, syzygy::{Tablebase, Wdl}};
+
+// the 3 piece tables and KBNvK, KBvK and KNvK are there for the underpromotions of KPvK
+// and the captures of KBNvK. KBNvK is compressed with symbol pairs and maps its DTZ values
+fn tablebase() -> Tablebase {
+    let tablebase = Tablebase::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy")).unwrap();
+    assert_eq!(tablebase.max_pieces(), 4);
+
+    tablebase
+}
+
+#[test]
+fn test_missing_tables() {
+    let tablebase = Tablebase::load("").unwrap();
+    assert!(tablebase.is_empty());
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), None);
+    assert_eq!(tablebase.probe_dtz(&mut board), None);
+    assert!(tablebase.probe_root(&mut board).is_none());
+
+    assert!(Tablebase::load("/nonexistent/syzygy").is_err());
+}
+
+#[test]
+fn test_corrupted_table() {
+    let dir: PathBuf = env::temp_dir().join(format!("mchess-syzygy-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("KQvK.rtbw"), [0u8; 64]).unwrap();
+    fs::write(dir.join("README.txt"), "not a table").unwrap();
+
+    let tablebase = Tablebase::load(dir.to_str().unwrap()).unwrap();
+    assert_eq!(tablebase.len(), 1);
+    assert_eq!(tablebase.max_pieces(), 3);
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1");
+    assert!(tablebase.can_probe(&board));
+    assert_eq!(tablebase.probe_wdl(&mut board), None);
+
+    // too many pieces for the loaded tables
+    let board = Board::from_fen("8/8/8/3k4/8/8/1P6/Q3K3 w - - 0 1");
+    assert!(!tablebase.can_probe(&board));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_probe_wdl() {
+    let tablebase = tablebase();
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Win));
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/q3K3 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Loss));
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/q3K3 b - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Win));
+
+    let mut board = Board::from_fen("8/8/8/8/8/2k5/8/K2r4 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Loss));
+
+    let mut board = Board::from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Win));
+
+    let mut board = Board::from_fen("8/3k4/8/3K4/3P4/8/8/8 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Draw));
+
+    let mut board = Board::from_fen("8/8/8/8/8/8/P7/K1k5 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Win));
+
+    // stalemated
+    let mut board = Board::from_fen("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Draw));
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/B3K3 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Draw));
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/BN2K3 w - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Win));
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/BN2K3 b - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Loss));
+
+    // the king takes the knight
+    let mut board = Board::from_fen("8/8/8/8/8/8/1B4k1/K5N1 b - - 0 1");
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Draw));
+}
+
+#[test]
+fn test_probe_dtz() {
+    let tablebase = tablebase();
+
+    // mate counts as zeroing
+    let mut board = Board::from_fen("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(1));
+
+    let mut board = Board::from_fen("k6R/8/1K6/8/8/8/8/8 b - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(-1));
+
+    // the longest wins, mate in 10 with the queen and in 16 with the rook
+    let mut board = Board::from_fen("8/8/8/5k2/8/8/1Q6/K7 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(19));
+
+    let mut board = Board::from_fen("8/8/8/8/4k3/8/1Q6/K7 b - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(-20));
+
+    let mut board = Board::from_fen("8/8/8/8/8/2k5/1R6/K7 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(31));
+
+    let mut board = Board::from_fen("8/8/8/8/8/8/1Rk5/K7 b - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(-32));
+
+    // black with the queen
+    let mut board = Board::from_fen("k7/1q6/8/8/5K2/8/8/8 b - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(19));
+
+    // the pawn move wins at once, in the other the king makes way first
+    let mut board = Board::from_fen("8/8/8/8/8/8/P7/K1k5 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(1));
+
+    let mut board = Board::from_fen("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(3));
+
+    let mut board = Board::from_fen("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(-4));
+
+    let mut board = Board::from_fen("8/3k4/8/3K4/3P4/8/8/8 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(0));
+
+    // the longest bishop and knight mate takes 33 moves
+    let mut board = Board::from_fen("8/8/8/8/8/8/2k5/KNB5 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(65));
+
+    let mut board = Board::from_fen("8/8/8/8/8/1k6/8/KNB5 b - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(-66));
+
+    let mut board = Board::from_fen("knb5/8/1K6/8/8/8/8/8 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(-66));
+}
+
+#[test]
+fn test_probe_root() {
+    let tablebase = tablebase();
+
+    let mut board = Board::from_fen("8/8/8/3k4/8/8/8/Q3K3 w - - 0 1");
+    assert_eq!(tablebase.probe_dtz(&mut board), Some(15));
+
+    let probe = tablebase.probe_root(&mut board).unwrap();
+    assert_eq!(probe.wdl, Wdl::Win);
+
+    board.make_move(&probe.m);
+    assert_eq!(tablebase.probe_wdl(&mut board), Some(Wdl::Loss));
+}
+
+#[test]
+fn test_tablebase_move_info() {
+    let mut protocol = UciProtocol::new();
+    let mut output = Vec::new();
+
+    protocol.set_option(concat!("setoption name SyzygyPath value ", env!("CARGO_MANIFEST_DIR"), "/tests/syzygy"), &mut output).unwrap();
+    protocol.handle_position("position fen 8/8/8/3k4/8/8/8/Q3K3 w - - 0 1", &mut output).unwrap();
+    protocol.handle_go("go depth 5", &mut output).unwrap();
+
+    // the probe is reported to the client, not printed
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.lines().any(|line| line.starts_with("info string tablebase move") && line.ends_with("tbhits 1")));
+}
\ No newline at end of file
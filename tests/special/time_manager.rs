@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use mchess::{board::Board, search::Minimax, time_manager::{TimeControl, TimeManager}};
+
+#[test]
+fn test_allocation() {
+    let sudden_death = TimeManager::new(&TimeControl { time: Some(60000), ..Default::default() }, 0);
+    assert_eq!(sudden_death.optimum(), 2000);
+    assert_eq!(sudden_death.maximum(), 8000);
+
+    // increment is mostly spent on the current move
+    let increment = TimeManager::new(&TimeControl { time: Some(60000), increment: 1000, ..Default::default() }, 0);
+    assert_eq!(increment.optimum(), 2750);
+
+    let repeating = TimeManager::new(&TimeControl { time: Some(60000), moves_to_go: Some(10), ..Default::default() }, 0);
+    assert_eq!(repeating.optimum(), 4500);
+    assert_eq!(repeating.maximum(), 18000);
+
+    // last move before the time control never uses the whole clock
+    let last_move = TimeManager::new(&TimeControl { time: Some(10000), moves_to_go: Some(1), ..Default::default() }, 0);
+    assert_eq!(last_move.maximum(), 8000);
+    assert_eq!(last_move.optimum(), 6000);
+
+    let move_time = TimeManager::new(&TimeControl { time: Some(60000), move_time: Some(1000), ..Default::default() }, 50);
+    assert_eq!(move_time.optimum(), 950);
+    assert_eq!(move_time.maximum(), 950);
+
+    let overhead = TimeManager::new(&TimeControl { time: Some(3100), ..Default::default() }, 100);
+    assert_eq!(overhead.optimum(), 100);
+    assert_eq!(overhead.maximum(), 400);
+}
+
+#[test]
+fn test_soft_limit() {
+    let control = TimeControl { time: Some(60000), ..Default::default() };
+
+    let mut unstable = TimeManager::new(&control, 0);
+    unstable.update(true, 0.0);
+    assert!(unstable.soft_limit() > unstable.optimum());
+
+    let mut dropping = TimeManager::new(&control, 0);
+    dropping.update(false, 1.0);
+    dropping.update(false, 0.0);
+    assert!(dropping.soft_limit() > dropping.optimum());
+
+    let mut stable = TimeManager::new(&control, 0);
+    for _ in 0..6 {
+        stable.update(false, 0.5);
+    }
+    assert!(stable.is_clearly_best());
+    assert!(stable.soft_limit() < stable.optimum());
+    assert!(stable.soft_limit() >= stable.optimum() / 2);
+
+    for _ in 0..20 {
+        unstable.update(true, 0.0);
+    }
+    assert!(unstable.soft_limit() <= unstable.maximum());
+}
+
+#[test]
+fn test_hard_limit() {
+    let mut board = Board::from_fen("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1");
+    let mut minimax = Minimax::new();
+
+    let start = Instant::now();
+    let result = minimax.iterative_deepening_timed(&mut board, 30, TimeManager::fixed(300));
+
+    assert!(!result.moves.is_empty());
+    assert!(start.elapsed().as_millis() < 3000);
+}
+
+#[test]
+fn test_single_reply() {
+    // the king has a single legal move
+    let mut board = Board::from_fen("7k/8/5K2/8/8/8/8/6R1 b - - 0 1");
+    let mut minimax = Minimax::new();
+
+    let result = minimax.iterative_deepening_timed(&mut board, 30, TimeManager::infinite());
+    assert_eq!(result.moves.len(), 1);
+}
+
+#[test]
+fn test_node_limit() {
+    let mut board = Board::from_fen("r1bq1rk1/pp2bppp/2n1pn2/2pp4/3P4/2PBPN2/PP1N1PPP/R1BQ1RK1 w - - 0 1");
+    let mut minimax = Minimax::new();
+
+    let result = minimax.iterative_deepening_timed(&mut board, 30, TimeManager::infinite().with_node_limit(5000));
+
+    // the count starts over with every search, a quiescence search still runs to its end
+    assert!(!result.moves.is_empty());
+    assert!(minimax.nodes < 10000, "{}", minimax.nodes);
+}